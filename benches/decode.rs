@@ -0,0 +1,77 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rs_simple_redis::{ProtocolLimits, RespFrameDecoder};
+
+// 模拟网络读取，每次只收到一小块数据
+const CHUNK_SIZE: usize = 4096;
+
+fn big_array(n: usize) -> Vec<u8> {
+    let mut data = format!("*{}\r\n", n).into_bytes();
+    for i in 0..n {
+        let value = format!("value-{}", i);
+        data.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+    }
+    data
+}
+
+fn nested_array(depth: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for _ in 0..depth {
+        data.extend_from_slice(b"*2\r\n:1\r\n");
+    }
+    data.extend_from_slice(b"$5\r\nhello\r\n");
+    data
+}
+
+fn pipeline(n: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..n {
+        let key = format!("key-{}", i);
+        data.extend_from_slice(
+            format!(
+                "*3\r\n$3\r\nset\r\n${}\r\n{}\r\n$5\r\nvalue\r\n",
+                key.len(),
+                key
+            )
+            .as_bytes(),
+        );
+    }
+    data
+}
+
+fn decode_streaming(data: &[u8]) -> usize {
+    // 嵌套的用例比默认的最大深度还要深
+    let mut decoder = RespFrameDecoder::with_limits(ProtocolLimits {
+        max_depth: usize::MAX,
+        ..Default::default()
+    });
+    let mut buf = BytesMut::new();
+    let mut count = 0;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        buf.extend_from_slice(chunk);
+        while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+            black_box(frame);
+            count += 1;
+        }
+    }
+    count
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let inputs = [
+        ("big_array_2000", big_array(2000)),
+        ("nested_array_500", nested_array(500)),
+        ("pipeline_1000", pipeline(1000)),
+    ];
+
+    let mut group = c.benchmark_group("decode");
+    for (name, data) in inputs.iter() {
+        group.bench_with_input(BenchmarkId::new("streaming", name), data, |b, data| {
+            b.iter(|| decode_streaming(data))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
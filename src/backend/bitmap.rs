@@ -0,0 +1,557 @@
+use super::string::take_string_buf;
+use super::Backend;
+use crate::cmd::CommandError;
+use crate::{Object, Value};
+use bytes::Bytes;
+
+/// bit 偏移量的上限，和 redis 一样限制在 512MB 以内
+pub const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8;
+
+/// BITCOUNT 和 BITPOS 的范围单位
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// BITCOUNT 和 BITPOS 的范围，都是闭区间，负数表示从末尾开始数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitRange {
+    pub start: i64,
+    /// BITPOS 可以只给 start，这时查找 0 时会把字符串右边当作无限的 0
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// BITFIELD 的整数类型，比如 i8、u16，有符号最多 64 位，无符号最多 63 位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+/// BITFIELD 写入的值超出类型范围时的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitOverflow {
+    /// 回绕，和 C 语言里的整数溢出一样
+    #[default]
+    Wrap,
+    /// 饱和到最大值或者最小值
+    Sat,
+    /// 不写入，回复 nil
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitFieldOp {
+    Get {
+        ty: BitFieldType,
+        offset: u64,
+    },
+    Set {
+        ty: BitFieldType,
+        offset: u64,
+        value: i64,
+        overflow: BitOverflow,
+    },
+    IncrBy {
+        ty: BitFieldType,
+        offset: u64,
+        delta: i64,
+        overflow: BitOverflow,
+    },
+}
+
+impl BitFieldType {
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1i128 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1i128 << (self.bits - 1)) - 1
+        } else {
+            (1i128 << self.bits) - 1
+        }
+    }
+
+    /// 按照溢出方式把 value 放进类型的范围里，FAIL 并且溢出时返回 None
+    fn fit(&self, value: i128, overflow: BitOverflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+
+        match overflow {
+            BitOverflow::Fail => None,
+            BitOverflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            BitOverflow::Wrap => {
+                let wrapped = value.rem_euclid(1i128 << self.bits);
+                if self.signed && wrapped > self.max() {
+                    Some((wrapped - (1i128 << self.bits)) as i64)
+                } else {
+                    Some(wrapped as i64)
+                }
+            }
+        }
+    }
+}
+
+impl BitFieldOp {
+    fn is_write(&self) -> bool {
+        !matches!(self, BitFieldOp::Get { .. })
+    }
+
+    // 字段结束的位置，用来计算字符串需要扩展到多长
+    fn end(&self) -> u64 {
+        match *self {
+            BitFieldOp::Get { ty, offset }
+            | BitFieldOp::Set { ty, offset, .. }
+            | BitFieldOp::IncrBy { ty, offset, .. } => offset + ty.bits as u64,
+        }
+    }
+}
+
+impl Backend {
+    /// 设置 offset 上的 bit，返回原来的 bit，字符串不够长时用 0 填充
+    pub fn setbit(&self, key: Bytes, offset: u64, on: bool) -> Result<u8, CommandError> {
+        self.write(key, |slot| {
+            let mut buf = take_string_buf(slot, (offset / 8 + 1) as usize)?;
+            let old = get_bit(&buf, offset);
+            set_bit(&mut buf, offset, on);
+            *slot = Some(Value::String(buf.freeze()));
+            Ok(old as u8)
+        })
+    }
+
+    pub fn getbit(&self, key: &[u8], offset: u64) -> Result<u8, CommandError> {
+        Ok(self.get(key)?.map_or(0, |s| {
+            (offset < s.len() as u64 * 8 && get_bit(&s, offset)) as u8
+        }))
+    }
+
+    /// 统计范围内 1 的个数，没有范围时统计整个字符串
+    pub fn bitcount(&self, key: &[u8], range: Option<BitRange>) -> Result<u64, CommandError> {
+        let Some(value) = self.get(key)? else {
+            return Ok(0);
+        };
+
+        let Some((start, end)) = bit_range(&value, range) else {
+            return Ok(0);
+        };
+
+        let mut count = 0;
+        let mut i = start;
+        while i <= end {
+            if i % 8 == 0 && i + 7 <= end {
+                count += value[(i / 8) as usize].count_ones() as u64;
+                i += 8;
+            } else {
+                count += get_bit(&value, i) as u64;
+                i += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// 查找第一个等于 bit 的位置，找不到时返回 -1
+    pub fn bitpos(
+        &self,
+        key: &[u8],
+        bit: bool,
+        range: Option<BitRange>,
+    ) -> Result<i64, CommandError> {
+        // key 不存在时当作无限长的 0
+        let Some(value) = self.get(key)? else {
+            return Ok(if bit { -1 } else { 0 });
+        };
+
+        let Some((start, end)) = bit_range(&value, range) else {
+            return Ok(-1);
+        };
+
+        // 一次跳过整个字节里都不匹配的部分
+        let skip = if bit { 0x00 } else { 0xff };
+        let mut i = start;
+        while i <= end {
+            if i % 8 == 0 && i + 7 <= end && value[(i / 8) as usize] == skip {
+                i += 8;
+                continue;
+            }
+            if get_bit(&value, i) == bit {
+                return Ok(i as i64);
+            }
+            i += 1;
+        }
+
+        // 查找 0 并且没有指定 end 时，字符串右边当作是 0
+        let end_given = range.is_some_and(|range| range.end.is_some());
+        if !bit && !end_given {
+            Ok(end as i64 + 1)
+        } else {
+            Ok(-1)
+        }
+    }
+
+    /// 把多个 key 按位运算的结果保存到 dest，返回结果的长度
+    /// 不存在的 key 当作空字符串，比较短的字符串用 0 补齐，结果为空时删除 dest
+    pub fn bitop(
+        &self,
+        op: BitOperation,
+        dest: Bytes,
+        keys: &[Bytes],
+    ) -> Result<usize, CommandError> {
+        let locked = keys.iter().chain([&dest]).cloned().collect::<Vec<_>>();
+        self.atomically(locked, |keyspace| {
+            let mut sources = Vec::with_capacity(keys.len());
+            for key in keys {
+                let source = keyspace.read(key, |value| match value {
+                    Value::String(s) => Ok(s.clone()),
+                    _ => Err(CommandError::WrongType),
+                });
+                sources.push(source.transpose()?.unwrap_or_default());
+            }
+
+            let len = sources.iter().map(Bytes::len).max().unwrap_or(0);
+            let byte = |source: &Bytes, i: usize| source.get(i).copied().unwrap_or(0);
+            let result = (0..len)
+                .map(|i| {
+                    let mut bytes = sources.iter().map(|source| byte(source, i));
+                    let first = bytes.next().unwrap_or(0);
+                    match op {
+                        BitOperation::And => bytes.fold(first, |acc, b| acc & b),
+                        BitOperation::Or => bytes.fold(first, |acc, b| acc | b),
+                        BitOperation::Xor => bytes.fold(first, |acc, b| acc ^ b),
+                        BitOperation::Not => !first,
+                    }
+                })
+                .collect::<Bytes>();
+
+            if result.is_empty() {
+                keyspace.write(dest, |slot| *slot = None);
+            } else {
+                keyspace.insert(dest, Object::new(Value::String(result)));
+            }
+            Ok(len)
+        })
+    }
+
+    /// 按顺序执行 BITFIELD 的子命令，每个子命令对应一个回复，FAIL 溢出时是 None
+    pub fn bitfield(
+        &self,
+        key: Bytes,
+        ops: &[BitFieldOp],
+    ) -> Result<Vec<Option<i64>>, CommandError> {
+        // 只有 GET 的时候不会创建 key
+        if !ops.iter().any(BitFieldOp::is_write) {
+            let value = self.get(&key)?.unwrap_or_default();
+            return Ok(ops
+                .iter()
+                .map(|op| match *op {
+                    BitFieldOp::Get { ty, offset } => Some(get_field(&value, ty, offset)),
+                    _ => None,
+                })
+                .collect());
+        }
+
+        self.write(key, |slot| {
+            // 和 redis 一样先把字符串扩展到写入需要的长度，即使写入因为溢出失败
+            let len = ops
+                .iter()
+                .filter(|op| op.is_write())
+                .map(|op| op.end().div_ceil(8))
+                .max()
+                .unwrap_or(0);
+            let mut buf = take_string_buf(slot, len as usize)?;
+
+            let ret = ops
+                .iter()
+                .map(|op| match *op {
+                    BitFieldOp::Get { ty, offset } => Some(get_field(&buf, ty, offset)),
+                    BitFieldOp::Set {
+                        ty,
+                        offset,
+                        value,
+                        overflow,
+                    } => {
+                        let old = get_field(&buf, ty, offset);
+                        let value = ty.fit(value as i128, overflow)?;
+                        set_field(&mut buf, ty, offset, value);
+                        Some(old)
+                    }
+                    BitFieldOp::IncrBy {
+                        ty,
+                        offset,
+                        delta,
+                        overflow,
+                    } => {
+                        let old = get_field(&buf, ty, offset);
+                        let value = ty.fit(old as i128 + delta as i128, overflow)?;
+                        set_field(&mut buf, ty, offset, value);
+                        Some(value)
+                    }
+                })
+                .collect();
+
+            *slot = Some(Value::String(buf.freeze()));
+            Ok(ret)
+        })
+    }
+}
+
+// 把范围换算成 bit 的闭区间，和 redis 一样处理负数和越界，范围为空时返回 None
+fn bit_range(value: &[u8], range: Option<BitRange>) -> Option<(u64, u64)> {
+    let bits = value.len() as i64 * 8;
+    let (start, end, unit) = match range {
+        None => (0, -1, BitUnit::Byte),
+        Some(range) => (range.start, range.end.unwrap_or(-1), range.unit),
+    };
+    let len = match unit {
+        BitUnit::Byte => value.len() as i64,
+        BitUnit::Bit => bits,
+    };
+
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 { (len + end).max(0) } else { end };
+    let end = end.min(len - 1);
+    if start > end {
+        return None;
+    }
+
+    match unit {
+        BitUnit::Byte => Some((start as u64 * 8, end as u64 * 8 + 7)),
+        BitUnit::Bit => Some((start as u64, end as u64)),
+    }
+}
+
+// bit 的顺序和 redis 一样，每个字节的最高位是第 0 位
+fn get_bit(buf: &[u8], offset: u64) -> bool {
+    buf.get((offset / 8) as usize)
+        .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+}
+
+fn set_bit(buf: &mut [u8], offset: u64, on: bool) {
+    let byte = &mut buf[(offset / 8) as usize];
+    let mask = 0x80 >> (offset % 8);
+    if on {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+}
+
+// 读取 offset 开始的字段，高位在前，超出字符串的部分当作 0
+fn get_field(buf: &[u8], ty: BitFieldType, offset: u64) -> i64 {
+    let mut value = 0u64;
+    for i in 0..ty.bits as u64 {
+        value = (value << 1) | get_bit(buf, offset + i) as u64;
+    }
+
+    if ty.signed && ty.bits < 64 && value & (1 << (ty.bits - 1)) != 0 {
+        // 符号扩展
+        (value | (u64::MAX << ty.bits)) as i64
+    } else {
+        value as i64
+    }
+}
+
+fn set_field(buf: &mut [u8], ty: BitFieldType, offset: u64, value: i64) {
+    let value = value as u64;
+    for i in 0..ty.bits as u64 {
+        let bit = value >> (ty.bits as u64 - 1 - i) & 1;
+        set_bit(buf, offset + i, bit == 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I8: BitFieldType = BitFieldType {
+        signed: true,
+        bits: 8,
+    };
+    const U2: BitFieldType = BitFieldType {
+        signed: false,
+        bits: 2,
+    };
+
+    #[test]
+    fn test_setbit_getbit() {
+        let backend = Backend::new();
+
+        assert_eq!(backend.setbit("key".into(), 7, true).unwrap(), 0);
+        assert_eq!(backend.setbit("key".into(), 7, true).unwrap(), 1);
+        assert_eq!(backend.get(b"key").unwrap().unwrap(), "\x01");
+        assert_eq!(backend.getbit(b"key", 7).unwrap(), 1);
+        assert_eq!(backend.getbit(b"key", 100).unwrap(), 0);
+
+        assert_eq!(backend.setbit("key".into(), 17, true).unwrap(), 0);
+        assert_eq!(backend.get(b"key").unwrap().unwrap(), "\x01\x00\x40");
+    }
+
+    #[test]
+    fn test_bitcount_bitpos_ranges() {
+        let backend = Backend::new();
+        backend.set("key".into(), Bytes::from_static(b"\xff\xf0\x00"));
+
+        let range = |start, end, unit| Some(BitRange { start, end, unit });
+        assert_eq!(backend.bitcount(b"key", None).unwrap(), 12);
+        assert_eq!(
+            backend
+                .bitcount(b"key", range(1, Some(-1), BitUnit::Byte))
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            backend
+                .bitcount(b"key", range(5, Some(10), BitUnit::Bit))
+                .unwrap(),
+            6
+        );
+        assert_eq!(
+            backend
+                .bitcount(b"key", range(2, Some(1), BitUnit::Byte))
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(backend.bitpos(b"key", false, None).unwrap(), 12);
+        assert_eq!(
+            backend
+                .bitpos(b"key", true, range(1, None, BitUnit::Byte))
+                .unwrap(),
+            8
+        );
+        assert_eq!(
+            backend
+                .bitpos(b"key", true, range(12, None, BitUnit::Bit))
+                .unwrap(),
+            -1
+        );
+
+        // 全是 1 的时候，没有给 end 会返回字符串之后的第一个位置
+        backend.set("ones".into(), Bytes::from_static(b"\xff\xff"));
+        assert_eq!(backend.bitpos(b"ones", false, None).unwrap(), 16);
+        assert_eq!(
+            backend
+                .bitpos(b"ones", false, range(0, Some(-1), BitUnit::Byte))
+                .unwrap(),
+            -1
+        );
+        assert_eq!(backend.bitpos(b"missing", false, None).unwrap(), 0);
+        assert_eq!(backend.bitpos(b"missing", true, None).unwrap(), -1);
+    }
+
+    #[test]
+    fn test_bitop() {
+        let backend = Backend::new();
+        backend.set("a".into(), Bytes::from_static(b"\x0f\xff"));
+        backend.set("b".into(), Bytes::from_static(b"\xf0"));
+
+        let keys = [Bytes::from("a"), Bytes::from("b"), Bytes::from("missing")];
+        assert_eq!(
+            backend
+                .bitop(BitOperation::Or, "dest".into(), &keys[..2])
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            backend.get(b"dest").unwrap().unwrap(),
+            b"\xff\xff".as_slice()
+        );
+        backend
+            .bitop(BitOperation::And, "dest".into(), &keys)
+            .unwrap();
+        assert_eq!(
+            backend.get(b"dest").unwrap().unwrap(),
+            b"\x00\x00".as_slice()
+        );
+        backend
+            .bitop(BitOperation::Xor, "dest".into(), &keys)
+            .unwrap();
+        assert_eq!(
+            backend.get(b"dest").unwrap().unwrap(),
+            b"\xff\xff".as_slice()
+        );
+        backend
+            .bitop(BitOperation::Not, "dest".into(), &keys[1..2])
+            .unwrap();
+        assert_eq!(backend.get(b"dest").unwrap().unwrap(), b"\x0f".as_slice());
+
+        // 结果为空时删除 dest
+        assert_eq!(
+            backend
+                .bitop(BitOperation::Not, "dest".into(), &keys[2..])
+                .unwrap(),
+            0
+        );
+        assert_eq!(backend.key_type(b"dest"), "none");
+    }
+
+    #[test]
+    fn test_bitfield_overflow() {
+        let backend = Backend::new();
+
+        let incr = |ty, delta, overflow| BitFieldOp::IncrBy {
+            ty,
+            offset: 0,
+            delta,
+            overflow,
+        };
+        let ret = backend
+            .bitfield(
+                "key".into(),
+                &[
+                    incr(I8, 127, BitOverflow::Wrap),
+                    incr(I8, 1, BitOverflow::Wrap),
+                    incr(I8, -10, BitOverflow::Sat),
+                    incr(I8, -200, BitOverflow::Sat),
+                    incr(I8, -1, BitOverflow::Fail),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            ret,
+            vec![Some(127), Some(-128), Some(-128), Some(-128), None]
+        );
+
+        let ret = backend
+            .bitfield(
+                "key".into(),
+                &[
+                    BitFieldOp::Set {
+                        ty: U2,
+                        offset: 100,
+                        value: 5,
+                        overflow: BitOverflow::Wrap,
+                    },
+                    BitFieldOp::Get {
+                        ty: U2,
+                        offset: 100,
+                    },
+                    incr(U2, 4, BitOverflow::Fail),
+                ],
+            )
+            .unwrap();
+        assert_eq!(ret, vec![Some(0), Some(1), None]);
+        // 写入的位置决定了字符串的长度
+        assert_eq!(backend.strlen(b"key").unwrap(), 13);
+    }
+}
@@ -0,0 +1,543 @@
+use super::{Backend, Keyspace};
+use crate::cmd::CommandError;
+use bytes::Bytes;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::sync::oneshot;
+
+// 用一个 key 上的数据满足等待的客户端，返回 true 表示已经满足，或者客户端已经不再等待
+type ServeFn = Box<dyn FnMut(Keyspace<'_>, &Bytes) -> bool + Send>;
+
+/// 阻塞在 key 上的客户端，BLPOP、BLMOVE 这类命令在没有数据时在这里排队
+/// 和 redis 一样，写入数据的命令在写完之后按照排队的顺序直接为等待的客户端取走数据，
+/// 所以先阻塞的客户端一定先拿到数据，也不会出现被唤醒之后数据已经被别人取走的情况
+/// 写入和满足等待的客户端在同一次加锁里完成，其他客户端看不到中间状态
+///
+/// 加锁的顺序总是先 waiters 再分片，不能在持有分片锁的时候等待 waiters 的锁
+/// 分片上没有阻塞的客户端时，写入数据的命令只需要这个分片的锁，不用经过 waiters 的锁
+pub(crate) struct Waiters {
+    state: Mutex<WaitState>,
+    // 每个分片上阻塞的客户端个数，包括还在检查数据、没有排队的客户端
+    blocked: Box<[AtomicUsize]>,
+}
+
+#[derive(Default)]
+struct WaitState {
+    next_id: u64,
+    // 每个 key 上排队的客户端，先阻塞的在前面
+    queues: HashMap<Bytes, VecDeque<u64>>,
+    waiters: HashMap<u64, Waiter>,
+}
+
+struct Waiter {
+    keys: Vec<Bytes>,
+    // 满足这个客户端之后会有新数据的 key，比如 BLMOVE 的 destination
+    wakes: Vec<Bytes>,
+    serve: ServeFn,
+}
+
+impl fmt::Debug for Waiters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Waiters")
+            .field("blocked", &self.lock().waiters.len())
+            .finish()
+    }
+}
+
+impl Waiters {
+    pub(crate) fn new(shards: usize) -> Self {
+        Self {
+            state: Mutex::default(),
+            blocked: (0..shards).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WaitState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // 分片上是否有阻塞的客户端，需要在持有分片锁的时候检查
+    // 客户端先计数再加锁检查数据，所以这里看到 0 的话，客户端检查数据时一定能看到这次写入
+    fn has_blocked(&self, shard: usize) -> bool {
+        self.blocked[shard].load(Ordering::SeqCst) > 0
+    }
+}
+
+impl WaitState {
+    fn register(&mut self, waiter: Waiter) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        for key in &waiter.keys {
+            self.queues.entry(key.clone()).or_default().push_back(id);
+        }
+        self.waiters.insert(id, waiter);
+        id
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Waiter> {
+        let waiter = self.waiters.remove(&id)?;
+        for key in &waiter.keys {
+            if let Some(queue) = self.queues.get_mut(key) {
+                queue.retain(|other| *other != id);
+                if queue.is_empty() {
+                    self.queues.remove(key);
+                }
+            }
+        }
+        Some(waiter)
+    }
+
+    // serve(wakes) 可能访问的所有 key：wakes 本身，加上满足这些 key 上的客户端之后
+    // 又有新数据的 key，比如 BLMOVE 的 destination，一直传递下去
+    fn related_keys(&self, wakes: &[Bytes]) -> HashSet<Bytes> {
+        let mut related = wakes.iter().cloned().collect::<HashSet<_>>();
+        let mut pending = wakes.to_vec();
+        while let Some(key) = pending.pop() {
+            for id in self.queues.get(&key).into_iter().flatten() {
+                for wake in &self.waiters[id].wakes {
+                    if related.insert(wake.clone()) {
+                        pending.push(wake.clone());
+                    }
+                }
+            }
+        }
+        related
+    }
+
+    // wakes 上有了新数据，按照排队的顺序满足等待的客户端
+    // ks 必须已经锁住 related_keys(wakes) 里的所有 key
+    fn serve(&mut self, ks: Keyspace<'_>, wakes: &[Bytes]) {
+        let mut ready = wakes.iter().cloned().collect::<VecDeque<_>>();
+        while let Some(key) = ready.pop_front() {
+            let Some(queue) = self.queues.get(&key) else {
+                continue;
+            };
+            for id in queue.iter().copied().collect::<Vec<_>>() {
+                let Some(waiter) = self.waiters.get_mut(&id) else {
+                    continue;
+                };
+                if (waiter.serve)(ks, &key) {
+                    if let Some(waiter) = self.remove(id) {
+                        ready.extend(waiter.wakes);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 客户端不再等待时（超时、连接断开）把它从队列里移除
+struct WaitGuard<'a> {
+    backend: &'a Backend,
+    id: u64,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.backend.waiters.lock().remove(self.id);
+    }
+}
+
+// 在 block_on 的整个过程中把客户端计入 keys 所在分片的阻塞个数
+struct BlockedGuard<'a> {
+    backend: &'a Backend,
+    shards: Vec<usize>,
+}
+
+impl<'a> BlockedGuard<'a> {
+    fn new(backend: &'a Backend, keys: &[Bytes]) -> Self {
+        let mut shards = keys
+            .iter()
+            .map(|key| backend.shard(key))
+            .collect::<Vec<_>>();
+        shards.sort_unstable();
+        shards.dedup();
+        for &shard in &shards {
+            backend.waiters.blocked[shard].fetch_add(1, Ordering::SeqCst);
+        }
+        Self { backend, shards }
+    }
+}
+
+impl Drop for BlockedGuard<'_> {
+    fn drop(&mut self) {
+        for &shard in &self.shards {
+            self.backend.waiters.blocked[shard].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Backend {
+    /// 依次用 keys 上的数据尝试满足 serve，第一个返回 Some 或者出错的结果就是命令的结果
+    /// 所有 key 都不满足时返回 None，整个过程独占 keys 所在的分片
+    pub(crate) fn try_serve<T>(
+        &self,
+        keys: &[Bytes],
+        mut serve: impl FnMut(Keyspace<'_>, &Bytes) -> Result<Option<T>, CommandError>,
+    ) -> Result<Option<T>, CommandError> {
+        self.atomically(keys, |ks| serve_first(ks, keys, &mut serve))
+    }
+
+    /// 独占 keys 所在的分片执行 f，然后在同一个锁里满足 wakes 上等待的客户端
+    /// 写入数据的命令都通过这里修改，写入的数据不会在满足等待的客户端之前被其他命令取走
+    /// wakes 是 f 可能写入新数据的 key，需要是 keys 的一部分
+    /// wakes 所在的分片上没有阻塞的客户端时只锁 keys 所在的分片，不同分片上的写入互不影响
+    pub(crate) fn write_and_wake<K, T>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+        wakes: &[Bytes],
+        f: impl FnOnce(Keyspace<'_>) -> T,
+    ) -> T
+    where
+        K: AsRef<[u8]>,
+    {
+        let shards = keys
+            .into_iter()
+            .map(|key| self.shard(key.as_ref()))
+            .collect::<Vec<_>>();
+        let mut f = Some(f);
+        let value = self.with_shards(shards.clone(), |ks| {
+            if wakes
+                .iter()
+                .any(|key| self.waiters.has_blocked(self.shard(key)))
+            {
+                return None;
+            }
+            f.take().map(|f| f(ks))
+        });
+        if let Some(value) = value {
+            return value;
+        }
+
+        // 有客户端阻塞在 wakes 的分片上，重新按照先 waiters 再分片的顺序加锁
+        let f = f.expect("f is only taken by the fast path");
+        let mut state = self.waiters.lock();
+        let shards = shards
+            .into_iter()
+            .chain(state.related_keys(wakes).iter().map(|key| self.shard(key)))
+            .collect();
+        self.with_shards(shards, |ks| {
+            let value = f(ks);
+            state.serve(ks, wakes);
+            value
+        })
+    }
+
+    /// 和 try_serve 一样，但是所有 key 都不满足时排队等待，直到其他客户端写入数据，或者 until 完成
+    /// until 完成表示超时或者客户端断开了连接，这时候返回 None
+    /// 已经为客户端取走的数据一定会返回，不会因为超时或者断开连接丢掉
+    /// wakes 是满足之后会有新数据的 key，这些 key 上等待的客户端也会被唤醒
+    pub(crate) async fn block_on<T, F>(
+        &self,
+        keys: Vec<Bytes>,
+        wakes: Vec<Bytes>,
+        until: impl Future<Output = ()>,
+        mut serve: F,
+    ) -> Result<Option<T>, CommandError>
+    where
+        T: Send + 'static,
+        F: FnMut(Keyspace<'_>, &Bytes) -> Result<Option<T>, CommandError> + Send + 'static,
+    {
+        // 先计数再检查数据，这之后的写入要么被这里的检查看到，要么经过 waiters 的锁
+        let _blocked = BlockedGuard::new(self, &keys);
+        let (tx, mut rx) = oneshot::channel();
+        let guard = {
+            // 检查数据和排队都在 waiters 的锁里完成，写入数据的命令在这之后才能检查等待的客户端
+            // 所以不会错过在这两步之间写入的数据，也不会插队到已经排队的客户端前面
+            let mut state = self.waiters.lock();
+            let shards = keys
+                .iter()
+                .chain(&state.related_keys(&wakes))
+                .map(|key| self.shard(key))
+                .collect();
+            let value = self.with_shards(shards, |ks| {
+                let value = serve_first(ks, &keys, &mut serve)?;
+                if value.is_some() {
+                    state.serve(ks, &wakes);
+                }
+                Ok::<_, CommandError>(value)
+            })?;
+            if value.is_some() {
+                return Ok(value);
+            }
+
+            let mut tx = Some(tx);
+            let serve = move |ks: Keyspace<'_>, key: &Bytes| {
+                // 客户端已经不再等待时不能取走数据
+                match &tx {
+                    Some(tx) if !tx.is_closed() => {}
+                    _ => return true,
+                }
+                match serve(ks, key) {
+                    Ok(None) => false,
+                    result => {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(result);
+                        }
+                        true
+                    }
+                }
+            };
+            let id = state.register(Waiter {
+                keys,
+                wakes,
+                serve: Box::new(serve),
+            });
+            WaitGuard { backend: self, id }
+        };
+
+        let result = tokio::select! {
+            biased;
+            result = &mut rx => result.ok(),
+            _ = until => None,
+        };
+        drop(guard);
+
+        match result {
+            Some(result) => result,
+            // 超时的同时可能刚好被满足了，移出队列之后再检查一次
+            None => rx.try_recv().unwrap_or(Ok(None)),
+        }
+    }
+
+    /// 当前阻塞在 key 上的客户端个数
+    pub fn blocked_clients(&self) -> usize {
+        self.waiters.lock().waiters.len()
+    }
+}
+
+// 依次用 keys 上的数据尝试满足 serve，返回第一个 Some 或者错误
+fn serve_first<T>(
+    ks: Keyspace<'_>,
+    keys: &[Bytes],
+    mut serve: impl FnMut(Keyspace<'_>, &Bytes) -> Result<Option<T>, CommandError>,
+) -> Result<Option<T>, CommandError> {
+    for key in keys {
+        if let Some(value) = serve(ks, key)? {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aggregate, ListEnd, SetOperation, Value, ZAddOptions, ZRangeBy, ZRangeSpec};
+    use anyhow::Result;
+    use std::future::pending;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    // 从列表头部取一个元素
+    fn pop_front(ks: Keyspace<'_>, key: &Bytes) -> Result<Option<Bytes>, CommandError> {
+        Ok(ks.write(key.clone(), |slot| match slot {
+            Some(Value::List(list)) => list.pop_front(),
+            _ => None,
+        }))
+    }
+
+    async fn wait_blocked(backend: &Backend, n: usize) {
+        while backend.blocked_clients() != n {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_waiters_are_served_in_order() -> Result<()> {
+        let backend = Backend::new();
+
+        let mut tasks = Vec::new();
+        for i in 0..3 {
+            let client = backend.clone();
+            tasks.push(tokio::spawn(async move {
+                client
+                    .block_on(vec!["q".into()], vec![], pending(), pop_front)
+                    .await
+            }));
+            wait_blocked(&backend, i + 1).await;
+        }
+
+        let wakes = [Bytes::from("q")];
+        backend.write_and_wake(&wakes, &wakes, |ks| {
+            ks.write("q".into(), |slot| {
+                *slot = Some(Value::List(["a", "b"].map(Bytes::from).into()));
+            })
+        });
+        assert_eq!(tasks.remove(0).await??, Some("a".into()));
+        assert_eq!(tasks.remove(0).await??, Some("b".into()));
+        assert_eq!(backend.blocked_clients(), 1);
+
+        // 取消等待的客户端会离开队列
+        let task = tasks.remove(0);
+        task.abort();
+        assert!(task.await.is_err());
+        assert_eq!(backend.blocked_clients(), 0);
+
+        // 超时之后不再排队
+        let value = backend
+            .block_on(
+                vec!["other".into()],
+                vec![],
+                tokio::time::sleep(Duration::from_millis(10)),
+                pop_front,
+            )
+            .await?;
+        assert_eq!(value, None);
+        assert_eq!(backend.blocked_clients(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_writes_skip_waiters_lock_without_blocked_clients() -> Result<()> {
+        let backend = Backend::new();
+
+        // 阻塞在其他分片上的客户端不影响这里的写入
+        let other = (0..)
+            .map(|i| Bytes::from(format!("other{}", i)))
+            .find(|key| backend.shard(key) != backend.shard(b"q"))
+            .unwrap();
+        let waiter = {
+            let client = backend.clone();
+            tokio::spawn(async move {
+                client
+                    .block_on(vec![other], vec![], pending(), pop_front)
+                    .await
+            })
+        };
+        wait_blocked(&backend, 1).await;
+
+        // 持有 waiters 的锁，写入仍然能完成
+        let ret = write_while_waiters_locked(&backend, |backend| {
+            backend.push("q".into(), ListEnd::Right, vec!["a".into()])
+        });
+        assert_eq!(ret?, 1);
+
+        waiter.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_writes_skip_waiters_lock_without_blocked_clients() -> Result<()> {
+        let backend = Backend::new();
+        let members = vec![(1.0, "a".into()), (2.0, "b".into())];
+        let spec = ZRangeSpec {
+            by: ZRangeBy::Rank(0, -1),
+            rev: false,
+            limit: None,
+        };
+        let ret = write_while_waiters_locked(&backend, move |backend| {
+            backend.zadd("z".into(), &ZAddOptions::default(), members)?;
+            backend.zincrby("z".into(), &ZAddOptions::default(), 1.0, "a".into())?;
+            backend.zrangestore("r".into(), b"z", &spec)?;
+            let keys = ["z".into(), "r".into()];
+            backend.zopstore(SetOperation::Union, "u".into(), &keys, &[], Aggregate::Sum)
+        });
+        assert_eq!(ret?, 2);
+        assert_eq!(backend.zscore(b"u", b"a")?, Some(4.0));
+        Ok(())
+    }
+
+    // 持有 waiters 的锁的同时在另一个线程里执行 f，f 需要等待 waiters 的锁时返回前会超时失败
+    fn write_while_waiters_locked<T: Send + 'static>(
+        backend: &Backend,
+        f: impl FnOnce(&Backend) -> T + Send + 'static,
+    ) -> T {
+        let state = backend.waiters.lock();
+        let (done, finished) = std::sync::mpsc::channel();
+        let writer = {
+            let backend = backend.clone();
+            std::thread::spawn(move || {
+                let ret = f(&backend);
+                let _ = done.send(());
+                ret
+            })
+        };
+        let finished = finished.recv_timeout(Duration::from_secs(5));
+        drop(state);
+        assert!(finished.is_ok(), "write waited for the waiters lock");
+        writer.join().expect("writer panicked")
+    }
+
+    #[tokio::test]
+    async fn test_queued_waiter_wins_race_with_pop() -> Result<()> {
+        let backend = Backend::new();
+        let waiter = {
+            let client = backend.clone();
+            tokio::spawn(async move {
+                client
+                    .block_on(vec!["q".into()], vec![], pending(), pop_front)
+                    .await
+            })
+        };
+        wait_blocked(&backend, 1).await;
+
+        // 另一个客户端一直在 LPOP，push 之后的数据只能交给已经排队的客户端
+        let stop = AtomicBool::new(false);
+        let stolen = std::thread::scope(|scope| {
+            let popper = scope.spawn(|| {
+                let mut stolen = Vec::new();
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(values) = backend.pop("q".into(), ListEnd::Left, 1)? {
+                        stolen.extend(values);
+                    }
+                }
+                Ok::<_, CommandError>(stolen)
+            });
+            backend.push("q".into(), ListEnd::Right, vec!["a".into()])?;
+            stop.store(true, Ordering::Relaxed);
+            popper.join().expect("popper panicked")
+        })?;
+
+        assert!(stolen.is_empty());
+        assert_eq!(waiter.await??, Some("a".into()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_served_value_is_returned_after_close() -> Result<()> {
+        let backend = Backend::new();
+        let (close, closed) = oneshot::channel::<()>();
+        let waiter = {
+            let client = backend.clone();
+            tokio::spawn(async move {
+                let closed = async {
+                    let _ = closed.await;
+                };
+                client
+                    .block_on(vec!["q".into()], vec![], closed, pop_front)
+                    .await
+            })
+        };
+        wait_blocked(&backend, 1).await;
+
+        // 数据已经交给等待的客户端之后连接才断开，数据仍然要回复给它
+        backend.push("q".into(), ListEnd::Right, vec!["a".into()])?;
+        let _ = close.send(());
+        assert_eq!(waiter.await??, Some("a".into()));
+        assert_eq!(backend.llen(b"q")?, 0);
+
+        // 还没有拿到数据就断开时和超时一样，之后写入的数据不会被取走
+        let (close, closed) = oneshot::channel::<()>();
+        let waiter = {
+            let client = backend.clone();
+            tokio::spawn(async move {
+                let closed = async {
+                    let _ = closed.await;
+                };
+                client
+                    .block_on(vec!["q".into()], vec![], closed, pop_front)
+                    .await
+            })
+        };
+        wait_blocked(&backend, 1).await;
+        let _ = close.send(());
+        assert_eq!(waiter.await??, None);
+        backend.push("q".into(), ListEnd::Right, vec!["b".into()])?;
+        assert_eq!(backend.llen(b"q")?, 1);
+        Ok(())
+    }
+}
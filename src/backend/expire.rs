@@ -0,0 +1,310 @@
+use super::Backend;
+use bytes::Bytes;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+// 和 redis 的 activeExpireCycle 一样，每 100ms 执行一次，每次最多用 25ms
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+const ACTIVE_EXPIRE_TIME_LIMIT: Duration = Duration::from_millis(25);
+// 每个分片每轮抽样的带过期时间的 key 的个数
+const ACTIVE_EXPIRE_SAMPLES: usize = 20;
+
+/// 当前的毫秒时间戳
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// EXPIRE 系列命令的 NX、XX、GT、LT 选项，可以组合使用
+/// 没有过期时间的 key 当作 ttl 无穷大来比较
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExpireCondition {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+}
+
+impl ExpireCondition {
+    pub(crate) fn allows(&self, current: Option<u64>, when: i64) -> bool {
+        match current {
+            None => !self.xx && !self.gt,
+            Some(current) => {
+                let current = current as i64;
+                !self.nx && (!self.gt || when > current) && (!self.lt || when < current)
+            }
+        }
+    }
+}
+
+/// 一个分片里带过期时间的 key，active expire 从这里随机抽样，
+/// 没有过期时间的 key 再多也不影响抽样的开销
+#[derive(Debug, Default)]
+pub(crate) struct VolatileKeys {
+    keys: Vec<Bytes>,
+    // key 在 keys 里的下标，删除时把最后一个 key 换到这个位置
+    index: HashMap<Bytes, usize>,
+}
+
+impl VolatileKeys {
+    pub(crate) fn insert(&mut self, key: Bytes) {
+        if !self.index.contains_key(&key) {
+            self.index.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &[u8]) {
+        let Some(i) = self.index.remove(key) else {
+            return;
+        };
+        self.keys.swap_remove(i);
+        if let Some(moved) = self.keys.get(i) {
+            self.index.insert(moved.clone(), i);
+        }
+    }
+
+    // 随机取最多 n 个不重复的 key
+    pub(crate) fn sample(&self, rng: &mut impl Rng, n: usize) -> Vec<Bytes> {
+        self.keys.choose_multiple(rng, n).cloned().collect()
+    }
+}
+
+impl Backend {
+    /// 设置 key 的过期时间（毫秒时间戳），key 不存在或者条件不满足时返回 false
+    /// 过期时间已经过去的话直接删除 key
+    pub fn expire_at(&self, key: bytes::Bytes, when: i64, condition: ExpireCondition) -> bool {
+        let now = now_ms() as i64;
+
+        self.write_object(key, |slot| {
+            let Some(object) = slot else {
+                return false;
+            };
+            if !condition.allows(object.expires_at, when) {
+                return false;
+            }
+
+            if when <= now {
+                *slot = None;
+            } else {
+                object.expires_at = Some(when as u64);
+            }
+            true
+        })
+    }
+
+    /// key 的过期时间，key 不存在时返回 None，没有过期时间时返回 Some(None)
+    pub fn expire_time(&self, key: &[u8]) -> Option<Option<u64>> {
+        self.read_object(key, |object| object.expires_at)
+    }
+
+    /// 去掉 key 的过期时间，返回 key 之前是否有过期时间
+    pub fn persist(&self, key: bytes::Bytes) -> bool {
+        self.write_object(key, |slot| match slot {
+            Some(object) => object.expires_at.take().is_some(),
+            None => false,
+        })
+    }
+
+    /// 随机抽样带过期时间的 key，删除其中已经过期的，返回删除的个数
+    /// 和 redis 一样，一个分片里过期的比例超过 25% 时继续抽样，直到用完这一轮的时间
+    /// 抽样和删除都持有分片的读锁，和其他单个 key 的写操作一样，不会和多个 key 的操作交错
+    pub fn active_expire_cycle(&self) -> usize {
+        let start = Instant::now();
+        let mut rng = rand::thread_rng();
+        let first = rng.gen_range(0..self.locks.len());
+        let mut removed = 0;
+
+        for i in 0..self.locks.len() {
+            let shard = (first + i) % self.locks.len();
+
+            loop {
+                let (sampled, expired) = self.with_shard(shard, |ks| {
+                    ks.expire_sample(shard, ACTIVE_EXPIRE_SAMPLES, &mut rng)
+                });
+                removed += expired;
+
+                if start.elapsed() > ACTIVE_EXPIRE_TIME_LIMIT {
+                    return removed;
+                }
+                if sampled == 0 || expired * 4 <= sampled {
+                    break;
+                }
+            }
+        }
+
+        removed
+    }
+
+    /// 在后台定期执行 active expire，backend 被释放之后任务自动退出
+    /// 每一轮会持有分片的锁执行最多 ACTIVE_EXPIRE_TIME_LIMIT，放在 blocking 线程里执行，
+    /// 不占用处理连接的 worker 线程
+    pub fn spawn_active_expire(&self) -> JoinHandle<()> {
+        let inner = std::sync::Arc::downgrade(&self.0);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let cycle =
+                    tokio::task::spawn_blocking(move || Backend(inner).active_expire_cycle());
+                if cycle.await.is_err() {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Object, Value};
+
+    #[test]
+    fn test_expire_condition() {
+        let nx = ExpireCondition {
+            nx: true,
+            ..Default::default()
+        };
+        assert!(nx.allows(None, 10));
+        assert!(!nx.allows(Some(5), 10));
+
+        let gt = ExpireCondition {
+            gt: true,
+            ..Default::default()
+        };
+        assert!(!gt.allows(None, 10));
+        assert!(gt.allows(Some(5), 10));
+        assert!(!gt.allows(Some(10), 10));
+
+        let lt = ExpireCondition {
+            lt: true,
+            ..Default::default()
+        };
+        assert!(lt.allows(None, 10));
+        assert!(!lt.allows(Some(5), 10));
+
+        let xx_gt = ExpireCondition {
+            xx: true,
+            gt: true,
+            ..Default::default()
+        };
+        assert!(!xx_gt.allows(None, 10));
+        assert!(xx_gt.allows(Some(5), 10));
+    }
+
+    // 所有分片里带过期时间的 key 的个数
+    fn volatile_len(backend: &Backend) -> usize {
+        backend
+            .volatile
+            .iter()
+            .map(|volatile| volatile.lock().unwrap().keys.len())
+            .sum()
+    }
+
+    // 插入一个已经过期的 key，访问时才发现过期
+    fn insert_expired(backend: &Backend, key: String) {
+        let mut object = Object::new(Value::String("value".into()));
+        object.expires_at = Some(now_ms() - 1);
+        backend.write_object(key.into(), |slot| *slot = Some(object));
+    }
+
+    #[test]
+    fn test_lazy_and_active_expire() {
+        let backend = Backend::new();
+
+        for i in 0..100 {
+            insert_expired(&backend, format!("key{}", i));
+        }
+        backend.set("live".into(), "value".into());
+        assert_eq!(volatile_len(&backend), 100);
+
+        assert_eq!(backend.get(b"key0").unwrap(), None);
+        assert_eq!(backend.db.len(), 100);
+        assert_eq!(volatile_len(&backend), 99);
+
+        assert_eq!(backend.active_expire_cycle(), 99);
+        assert_eq!(backend.db.len(), 1);
+        assert_eq!(volatile_len(&backend), 0);
+        assert_eq!(backend.key_type(b"live"), "string");
+    }
+
+    #[test]
+    fn test_active_expire_skips_keys_without_ttl() {
+        let backend = Backend::new();
+
+        // 没有过期时间的 key 很多时，抽样只看带过期时间的 key，过期的 key 一轮就能删完
+        for i in 0..10_000 {
+            backend.set(format!("live{}", i).into(), "value".into());
+        }
+        for i in 0..10 {
+            insert_expired(&backend, format!("key{}", i));
+        }
+        assert_eq!(backend.active_expire_cycle(), 10);
+        assert_eq!(backend.db.len(), 10_000);
+
+        // 修改过期时间、覆盖和删除 key 都会更新抽样的范围
+        let cond = ExpireCondition::default();
+        let later = now_ms() as i64 + 100_000;
+        assert!(backend.expire_at("live0".into(), later, cond));
+        assert!(backend.expire_at("live1".into(), later, cond));
+        assert!(backend.expire_at("live2".into(), later, cond));
+        assert_eq!(volatile_len(&backend), 3);
+        assert!(backend.persist("live0".into()));
+        backend.set("live1".into(), "value".into());
+        backend.write("live2".into(), |slot| *slot = None);
+        assert_eq!(volatile_len(&backend), 0);
+        assert_eq!(backend.active_expire_cycle(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_active_expire() {
+        let backend = Backend::new();
+        for i in 0..10 {
+            insert_expired(&backend, format!("key{}", i));
+        }
+
+        let task = backend.spawn_active_expire();
+        while !backend.db.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(volatile_len(&backend), 0);
+
+        // backend 释放之后任务退出
+        drop(backend);
+        tokio::time::timeout(ACTIVE_EXPIRE_INTERVAL * 3, task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_expire_at_and_persist() {
+        let backend = Backend::new();
+        let now = now_ms() as i64;
+        let cond = ExpireCondition::default();
+
+        assert!(!backend.expire_at("key".into(), now + 1000, cond));
+
+        backend.set("key".into(), "value".into());
+        assert!(backend.expire_at("key".into(), now + 1000, cond));
+        assert_eq!(backend.expire_time(b"key"), Some(Some(now as u64 + 1000)));
+
+        assert!(backend.persist("key".into()));
+        assert!(!backend.persist("key".into()));
+        assert_eq!(backend.expire_time(b"key"), Some(None));
+
+        // 过期时间已经过去，直接删除
+        assert!(backend.expire_at("key".into(), now - 1, cond));
+        assert_eq!(backend.expire_time(b"key"), None);
+    }
+}
@@ -0,0 +1,582 @@
+use super::scan::{scan_page, ScanOptions};
+use super::Backend;
+use crate::cmd::{parse_f64, parse_i64, CommandError};
+use crate::{now_ms, ExpireCondition, Hash, SetExpire, SetOptions, Value};
+use bytes::Bytes;
+use rand::seq::{IteratorRandom, SliceRandom};
+use std::collections::HashMap;
+
+/// HRANDFIELD、SRANDMEMBER 的 count 为负数时最多返回的个数
+/// 负数的 count 不受 key 大小的限制，超过时直接报错，不然一条命令就能占满内存
+pub const MAX_RANDOM_COUNT: u64 = 1 << 20;
+
+impl Backend {
+    // 只读访问一个 hash，key 不存在时返回 None
+    // 有 field 过期时先把它们删掉，这样读到的都是没有过期的 field
+    fn read_hash<T>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&Hash) -> T,
+    ) -> Result<Option<T>, CommandError> {
+        let has_expired = self.read(key, |value| match value {
+            Value::Hash(hash) => hash.has_expired(now_ms()),
+            _ => false,
+        });
+        if has_expired == Some(true) {
+            self.write(Bytes::copy_from_slice(key), |slot| {
+                if let Some(Value::Hash(hash)) = slot {
+                    hash.remove_expired(now_ms());
+                }
+            });
+        }
+
+        self.read(key, |value| match value {
+            Value::Hash(hash) => Ok(f(hash)),
+            _ => Err(CommandError::WrongType),
+        })
+        .transpose()
+    }
+
+    // 修改一个 hash，key 不存在时从空的 hash 开始，最后一个 field 被删掉时 key 也会被删除
+    // 和 read_hash 一样，f 看不到过期的 field
+    fn write_hash<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut Hash) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        self.write(key, |slot| {
+            match slot.get_or_insert_with(|| Value::Hash(Hash::new())) {
+                Value::Hash(hash) => {
+                    hash.remove_expired(now_ms());
+                    f(hash)
+                }
+                _ => Err(CommandError::WrongType),
+            }
+        })
+    }
+
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Result<Option<Bytes>, CommandError> {
+        self.read_hash(key, |hash| hash.get(field).cloned())
+            .map(Option::flatten)
+    }
+
+    /// 返回 field 是否是新加入的
+    pub fn hset(&self, key: Bytes, field: Bytes, value: Bytes) -> Result<bool, CommandError> {
+        Ok(self.hmset(key, vec![(field, value)])? == 1)
+    }
+
+    /// 设置多个 field，返回新加入的 field 个数
+    pub fn hmset(&self, key: Bytes, fields: Vec<(Bytes, Bytes)>) -> Result<usize, CommandError> {
+        self.write_hash(key, |hash| {
+            let mut added = 0;
+            for (field, value) in fields {
+                if hash.insert(field, value).is_none() {
+                    added += 1;
+                }
+            }
+            Ok(added)
+        })
+    }
+
+    /// 只在 field 不存在时设置
+    pub fn hsetnx(&self, key: Bytes, field: Bytes, value: Bytes) -> Result<bool, CommandError> {
+        self.write_hash(key, |hash| {
+            if hash.contains_key(&field) {
+                return Ok(false);
+            }
+            hash.insert(field, value);
+            Ok(true)
+        })
+    }
+
+    pub fn hgetall(&self, key: &[u8]) -> Result<Option<HashMap<Bytes, Bytes>>, CommandError> {
+        self.read_hash(key, |hash| {
+            hash.iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        })
+    }
+
+    /// 返回删除的 field 个数
+    pub fn hdel(&self, key: Bytes, fields: &[Bytes]) -> Result<usize, CommandError> {
+        self.write_hash(key, |hash| {
+            Ok(fields
+                .iter()
+                .filter(|field| hash.remove(field).is_some())
+                .count())
+        })
+    }
+
+    pub fn hlen(&self, key: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.read_hash(key, Hash::len)?.unwrap_or(0))
+    }
+
+    pub fn hexists(&self, key: &[u8], field: &[u8]) -> Result<bool, CommandError> {
+        Ok(self
+            .read_hash(key, |hash| hash.contains_key(field))?
+            .unwrap_or(false))
+    }
+
+    pub fn hkeys(&self, key: &[u8]) -> Result<Vec<Bytes>, CommandError> {
+        Ok(self
+            .read_hash(key, |hash| hash.keys().cloned().collect())?
+            .unwrap_or_default())
+    }
+
+    pub fn hvals(&self, key: &[u8]) -> Result<Vec<Bytes>, CommandError> {
+        Ok(self
+            .read_hash(key, |hash| hash.values().cloned().collect())?
+            .unwrap_or_default())
+    }
+
+    pub fn hmget(&self, key: &[u8], fields: &[Bytes]) -> Result<Vec<Option<Bytes>>, CommandError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                fields
+                    .iter()
+                    .map(|field| hash.get(field).cloned())
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// field 的值的长度，不存在时是 0
+    pub fn hstrlen(&self, key: &[u8], field: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.hget(key, field)?.map_or(0, |value| value.len()))
+    }
+
+    /// 把 field 的值当作整数加上 delta，field 不存在时当作 0
+    pub fn hincr_by(&self, key: Bytes, field: Bytes, delta: i64) -> Result<i64, CommandError> {
+        self.write_hash(key, |hash| {
+            let current = match hash.get(&field) {
+                None => 0,
+                Some(value) => parse_i64(value).map_err(|_| {
+                    CommandError::InvalidArgument("hash value is not an integer".to_string())
+                })?,
+            };
+
+            let value = current.checked_add(delta).ok_or_else(|| {
+                CommandError::InvalidArgument("increment or decrement would overflow".to_string())
+            })?;
+            hash.insert_keep_ttl(field, value.to_string().into());
+            Ok(value)
+        })
+    }
+
+    /// 把 field 的值当作浮点数加上 delta，返回的是保存下来的字符串
+    pub fn hincr_by_float(
+        &self,
+        key: Bytes,
+        field: Bytes,
+        delta: f64,
+    ) -> Result<Bytes, CommandError> {
+        self.write_hash(key, |hash| {
+            let current = match hash.get(&field) {
+                None => 0.0,
+                Some(value) => parse_f64(value).map_err(|_| {
+                    CommandError::InvalidArgument("hash value is not a float".to_string())
+                })?,
+            };
+
+            let value = current + delta;
+            if !value.is_finite() {
+                return Err(CommandError::InvalidArgument(
+                    "increment would produce NaN or Infinity".to_string(),
+                ));
+            }
+
+            let value = Bytes::from(format!("{}", value));
+            hash.insert_keep_ttl(field, value.clone());
+            Ok(value)
+        })
+    }
+
+    /// 随机返回 field 和 value
+    /// count 为正数时返回不重复的 field，最多返回整个 hash，为负数时可能重复，一共返回 -count 个
+    pub fn hrandfield(&self, key: &[u8], count: i64) -> Result<Vec<(Bytes, Bytes)>, CommandError> {
+        check_random_count(count)?;
+        let mut rng = rand::thread_rng();
+        let pair = |(field, value): (&Bytes, &Bytes)| (field.clone(), value.clone());
+
+        Ok(self
+            .read_hash(key, |hash| {
+                if count >= 0 && count as usize >= hash.len() {
+                    hash.iter().map(pair).collect()
+                } else if count >= 0 {
+                    hash.iter()
+                        .choose_multiple(&mut rng, count as usize)
+                        .into_iter()
+                        .map(pair)
+                        .collect()
+                } else {
+                    // 只遍历一次 hash，之后随机取下标
+                    let entries = hash.iter().collect::<Vec<_>>();
+                    (0..count.unsigned_abs())
+                        .filter_map(|_| entries.choose(&mut rng).copied().map(pair))
+                        .collect()
+                }
+            })?
+            .unwrap_or_default())
+    }
+
+    /// 返回下一次的游标和这一次遍历到的 field、value
+    pub fn hscan(
+        &self,
+        key: &[u8],
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> Result<(u64, Vec<(Bytes, Bytes)>), CommandError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                let (next, page) = scan_page(hash.iter(), |(field, _)| field, cursor, options);
+                let page = page
+                    .into_iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect();
+                (next, page)
+            })?
+            .unwrap_or_default())
+    }
+
+    /// 设置 field 的过期时间（毫秒时间戳），每个 field 对应一个结果:
+    /// -2 field 不存在，0 条件不满足，1 设置成功，2 时间已经过去所以直接删除了 field
+    pub fn hexpire_at(
+        &self,
+        key: Bytes,
+        fields: &[Bytes],
+        when: i64,
+        condition: ExpireCondition,
+    ) -> Result<Vec<i64>, CommandError> {
+        let now = now_ms() as i64;
+
+        self.write_hash(key, |hash| {
+            Ok(fields
+                .iter()
+                .map(|field| {
+                    if !hash.contains_key(field) {
+                        -2
+                    } else if !condition.allows(hash.expire_time(field), when) {
+                        0
+                    } else if when <= now {
+                        hash.remove(field);
+                        2
+                    } else {
+                        hash.set_expire(field, Some(when as u64));
+                        1
+                    }
+                })
+                .collect())
+        })
+    }
+
+    /// field 的过期时间，field 不存在时是 None，没有过期时间时是 Some(None)
+    pub fn hexpire_time(
+        &self,
+        key: &[u8],
+        fields: &[Bytes],
+    ) -> Result<Vec<Option<Option<u64>>>, CommandError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                fields
+                    .iter()
+                    .map(|field| hash.contains_key(field).then(|| hash.expire_time(field)))
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// 去掉 field 的过期时间，每个 field 对应一个结果:
+    /// -2 field 不存在，-1 没有过期时间，1 去掉了过期时间
+    pub fn hpersist(&self, key: Bytes, fields: &[Bytes]) -> Result<Vec<i64>, CommandError> {
+        self.write_hash(key, |hash| {
+            Ok(fields
+                .iter()
+                .map(|field| {
+                    if !hash.contains_key(field) {
+                        -2
+                    } else if hash.expire_time(field).is_none() {
+                        -1
+                    } else {
+                        hash.set_expire(field, None);
+                        1
+                    }
+                })
+                .collect())
+        })
+    }
+
+    /// 返回 field 的值，同时修改它们的过期时间，KeepTtl 表示不修改，Clear 表示去掉过期时间
+    pub fn hgetex(
+        &self,
+        key: Bytes,
+        fields: &[Bytes],
+        expire: SetExpire,
+    ) -> Result<Vec<Option<Bytes>>, CommandError> {
+        let now = now_ms();
+
+        self.write_hash(key, |hash| {
+            let values = fields
+                .iter()
+                .map(|field| hash.get(field).cloned())
+                .collect();
+
+            for field in fields {
+                match expire {
+                    SetExpire::KeepTtl => {}
+                    SetExpire::Clear => hash.set_expire(field, None),
+                    SetExpire::At(at) if at <= now => {
+                        hash.remove(field);
+                    }
+                    SetExpire::At(at) => hash.set_expire(field, Some(at)),
+                }
+            }
+            Ok(values)
+        })
+    }
+
+    /// 设置多个 field 和它们的过期时间，nx 要求所有 field 都不存在，xx 要求所有 field 都存在
+    /// 返回是否设置成功，条件不满足时一个 field 都不会修改
+    pub fn hsetex(
+        &self,
+        key: Bytes,
+        fields: Vec<(Bytes, Bytes)>,
+        options: SetOptions,
+    ) -> Result<bool, CommandError> {
+        let now = now_ms();
+
+        self.write_hash(key, |hash| {
+            if options.nx && fields.iter().any(|(field, _)| hash.contains_key(field)) {
+                return Ok(false);
+            }
+            if options.xx && !fields.iter().all(|(field, _)| hash.contains_key(field)) {
+                return Ok(false);
+            }
+
+            for (field, value) in fields {
+                let ttl = match options.expire {
+                    SetExpire::Clear => None,
+                    SetExpire::At(at) => Some(at),
+                    SetExpire::KeepTtl => hash.expire_time(&field),
+                };
+                if ttl.is_some_and(|at| at <= now) {
+                    hash.remove(&field);
+                    continue;
+                }
+                hash.insert(field.clone(), value);
+                hash.set_expire(&field, ttl);
+            }
+            Ok(true)
+        })
+    }
+}
+
+// 检查负数的 count 有没有超过 MAX_RANDOM_COUNT
+pub(super) fn check_random_count(count: i64) -> Result<(), CommandError> {
+    if count < 0 && count.unsigned_abs() > MAX_RANDOM_COUNT {
+        return Err(CommandError::InvalidArgument(format!(
+            "count is too large, at most {} random elements can be returned",
+            MAX_RANDOM_COUNT
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_fields() {
+        let backend = Backend::new();
+
+        let fields = vec![
+            ("a".into(), "1".into()),
+            ("b".into(), "2".into()),
+            ("a".into(), "3".into()),
+        ];
+        assert_eq!(backend.hmset("hash".into(), fields).unwrap(), 2);
+        assert_eq!(backend.hget(b"hash", b"a").unwrap().unwrap(), "3");
+        assert!(!backend
+            .hsetnx("hash".into(), "a".into(), "x".into())
+            .unwrap());
+        assert_eq!(backend.hlen(b"hash").unwrap(), 2);
+        assert!(backend.hexists(b"hash", b"b").unwrap());
+
+        let fields = [Bytes::from("a"), Bytes::from("c")];
+        assert_eq!(
+            backend.hmget(b"hash", &fields).unwrap(),
+            vec![Some("3".into()), None]
+        );
+        assert_eq!(backend.hdel("hash".into(), &fields).unwrap(), 1);
+        assert_eq!(backend.hkeys(b"hash").unwrap(), vec![Bytes::from("b")]);
+
+        // 最后一个 field 被删除之后 key 也不存在了
+        assert_eq!(backend.hdel("hash".into(), &["b".into()]).unwrap(), 1);
+        assert_eq!(backend.key_type(b"hash"), "none");
+        assert_eq!(backend.hdel("hash".into(), &["b".into()]).unwrap(), 0);
+        assert_eq!(backend.key_type(b"hash"), "none");
+    }
+
+    #[test]
+    fn test_hincr_by() {
+        let backend = Backend::new();
+
+        assert_eq!(backend.hincr_by("h".into(), "n".into(), 5).unwrap(), 5);
+        assert_eq!(backend.hincr_by("h".into(), "n".into(), -7).unwrap(), -2);
+        assert_eq!(
+            backend.hincr_by_float("h".into(), "n".into(), 0.5).unwrap(),
+            "-1.5"
+        );
+        assert!(backend.hincr_by("h".into(), "n".into(), 1).is_err());
+
+        // 出错的时候不会留下一个空的 hash
+        backend.set("s".into(), "x".into());
+        assert!(matches!(
+            backend.hincr_by("s".into(), "n".into(), 1),
+            Err(CommandError::WrongType)
+        ));
+        assert!(backend
+            .hincr_by_float("h3".into(), "n".into(), f64::INFINITY)
+            .is_err());
+        assert_eq!(backend.key_type(b"h3"), "none");
+    }
+
+    #[test]
+    fn test_hrandfield() {
+        let backend = Backend::new();
+        let fields = (0..5)
+            .map(|i| (Bytes::from(i.to_string()), Bytes::from("v")))
+            .collect();
+        backend.hmset("hash".into(), fields).unwrap();
+
+        assert_eq!(backend.hrandfield(b"hash", 10).unwrap().len(), 5);
+        let picked = backend.hrandfield(b"hash", 3).unwrap();
+        assert_eq!(picked.len(), 3);
+        assert!(picked.iter().all(|(field, _)| picked
+            .iter()
+            .filter(|(other, _)| other == field)
+            .count()
+            == 1));
+        // 负数时可以重复，每一个都是 hash 里的 field 和对应的 value
+        let picked = backend.hrandfield(b"hash", -20).unwrap();
+        assert_eq!(picked.len(), 20);
+        assert!(picked
+            .iter()
+            .all(|(field, value)| field.len() == 1 && field[0].is_ascii_digit() && value == "v"));
+        assert!(backend.hrandfield(b"missing", -20).unwrap().is_empty());
+
+        // 负数的 count 太大时报错，不会真的生成这么多个
+        let max = MAX_RANDOM_COUNT as i64;
+        assert!(backend.hrandfield(b"hash", -1_000_000_000_000).is_err());
+        assert!(backend.hrandfield(b"hash", -max - 1).is_err());
+        assert_eq!(backend.hrandfield(b"hash", i64::MAX).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_field_expire() {
+        let backend = Backend::new();
+        let fields = vec![("a".into(), "1".into()), ("b".into(), "2".into())];
+        backend.hmset("hash".into(), fields).unwrap();
+
+        let later = now_ms() as i64 + 100_000;
+        let all = [Bytes::from("a"), Bytes::from("b"), Bytes::from("c")];
+        let cond = ExpireCondition::default();
+        assert_eq!(
+            backend
+                .hexpire_at("hash".into(), &all, later, cond)
+                .unwrap(),
+            vec![1, 1, -2]
+        );
+        assert_eq!(
+            backend.hexpire_time(b"hash", &all).unwrap(),
+            vec![Some(Some(later as u64)), Some(Some(later as u64)), None]
+        );
+
+        // HINCRBY 保留过期时间，HSET 会清除
+        backend.hincr_by("hash".into(), "a".into(), 1).unwrap();
+        backend.hset("hash".into(), "b".into(), "3".into()).unwrap();
+        assert_eq!(
+            backend.hexpire_time(b"hash", &all[..2]).unwrap(),
+            vec![Some(Some(later as u64)), Some(None)]
+        );
+        assert_eq!(
+            backend.hpersist("hash".into(), &all).unwrap(),
+            vec![1, -1, -2]
+        );
+
+        // 过期时间已经过去的 field 会被直接删除，所有 field 都过期之后 key 也不存在了
+        assert_eq!(
+            backend
+                .hexpire_at("hash".into(), &all[..1], 0, cond)
+                .unwrap(),
+            vec![2]
+        );
+        assert_eq!(backend.hlen(b"hash").unwrap(), 1);
+        backend
+            .write_hash("hash".into(), |hash| {
+                hash.set_expire(b"b".as_slice(), Some(now_ms() - 1));
+                Ok(())
+            })
+            .unwrap();
+        assert!(backend.hgetall(b"hash").unwrap().is_none());
+        assert_eq!(backend.key_type(b"hash"), "none");
+    }
+    #[test]
+    fn test_active_expire_removes_fields() {
+        let backend = Backend::new();
+        let fields = vec![
+            ("a".into(), "1".into()),
+            ("b".into(), "2".into()),
+            ("c".into(), "3".into()),
+        ];
+        backend.hmset("hash".into(), fields.clone()).unwrap();
+        backend.hmset("gone".into(), fields).unwrap();
+        backend
+            .write_hash("hash".into(), |hash| {
+                hash.set_expire(b"a".as_slice(), Some(now_ms() - 1));
+                hash.set_expire(b"b".as_slice(), Some(now_ms() + 100_000));
+                Ok(())
+            })
+            .unwrap();
+        backend
+            .write_hash("gone".into(), |hash| {
+                for field in [b"a", b"b", b"c"] {
+                    hash.set_expire(field.as_slice(), Some(now_ms() - 1));
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        // 没有读过的 hash 里过期的 field 也会被删掉，field 全部过期时 key 也被删除
+        assert_eq!(backend.active_expire_cycle(), 2);
+        assert!(!backend.db.contains_key(b"gone".as_slice()));
+        let len = |backend: &Backend| match &backend.db.get(b"hash".as_slice()).unwrap().value {
+            Value::Hash(hash) => hash.len(),
+            _ => unreachable!(),
+        };
+        assert_eq!(len(&backend), 2);
+
+        // 还有 field 带过期时间时 key 留在抽样范围里，去掉之后就不再抽样了
+        let volatile = |backend: &Backend| -> usize {
+            backend
+                .volatile
+                .iter()
+                .map(|volatile| {
+                    volatile
+                        .lock()
+                        .unwrap()
+                        .sample(&mut rand::thread_rng(), 10)
+                        .len()
+                })
+                .sum()
+        };
+        assert_eq!(volatile(&backend), 1);
+        assert_eq!(
+            backend.hpersist("hash".into(), &["b".into()]).unwrap(),
+            vec![1]
+        );
+        assert_eq!(volatile(&backend), 0);
+        assert_eq!(backend.active_expire_cycle(), 0);
+        assert_eq!(len(&backend), 2);
+    }
+}
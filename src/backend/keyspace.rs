@@ -0,0 +1,256 @@
+use super::expire::VolatileKeys;
+use crate::{now_ms, Object, Value};
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
+use dashmap::mapref::one::Ref;
+use dashmap::DashMap;
+use rand::Rng;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// 对 keyspace 的直接访问，不加分片锁
+/// 单个 key 的操作通过 Backend 上的同名方法访问，多个 key 的操作在 Backend::atomically 里面使用
+/// 只能访问调用者已经锁住的分片上的 key
+#[derive(Debug, Clone, Copy)]
+pub struct Keyspace<'a> {
+    db: &'a DashMap<Bytes, Object>,
+    // 每个分片里带过期时间的 key，和 db 在同一个 DashMap 分片锁里修改，所以总是一致的
+    volatile: &'a [Mutex<VolatileKeys>],
+    // 已经锁住的分片，按下标排序
+    shards: &'a [usize],
+}
+
+impl<'a> Keyspace<'a> {
+    pub(crate) fn new(
+        db: &'a DashMap<Bytes, Object>,
+        volatile: &'a [Mutex<VolatileKeys>],
+        shards: &'a [usize],
+    ) -> Self {
+        Self {
+            db,
+            volatile,
+            shards,
+        }
+    }
+
+    fn volatile(&self, shard: usize) -> MutexGuard<'a, VolatileKeys> {
+        self.volatile[shard]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // key 有没有过期时间发生了变化，必须在持有 key 所在的 DashMap 分片锁的时候调用
+    fn track_expiry(&self, key: &Bytes, before: bool, after: bool) {
+        if before == after {
+            return;
+        }
+        let mut volatile = self.volatile(self.db.determine_map(key));
+        if after {
+            volatile.insert(key.clone());
+        } else {
+            volatile.remove(key);
+        }
+    }
+
+    // 访问没有锁住的 key 是调用者的 bug，测试的时候检查出来
+    fn check_locked(&self, key: &[u8]) {
+        debug_assert!(
+            self.shards
+                .binary_search(&self.db.determine_map(key))
+                .is_ok(),
+            "key {:?} is not locked",
+            key
+        );
+    }
+
+    /// 只读访问一个 key，key 不存在时返回 None
+    pub fn read<T>(&self, key: &[u8], f: impl FnOnce(&Value) -> T) -> Option<T> {
+        self.read_object(key, |object| f(&object.value))
+    }
+
+    /// 和 read 一样，但是可以拿到过期时间
+    /// 访问到已经过期的 key 时顺便把它删掉，也就是 redis 的惰性删除
+    pub fn read_object<T>(&self, key: &[u8], f: impl FnOnce(&Object) -> T) -> Option<T> {
+        self.check_locked(key);
+        let now = now_ms();
+        {
+            let object = self.db.get(key)?;
+            if !object.is_expired(now) {
+                return Some(f(&object));
+            }
+        }
+
+        self.remove_expired(key, now);
+        None
+    }
+
+    // 删除已经过期的 key，返回是否删除了
+    fn remove_expired(&self, key: &[u8], now: u64) -> bool {
+        self.db
+            .remove_if(key, |key, object| {
+                let expired = object.is_expired(now);
+                if expired {
+                    self.track_expiry(key, true, false);
+                }
+                expired
+            })
+            .is_some()
+    }
+
+    // 删除 hash 里已经过期的 field，返回是否删除了，所有 field 都删掉时 key 也会被删除
+    fn remove_expired_fields(&self, key: &Bytes, now: u64) -> bool {
+        let has_expired = self.read(
+            key,
+            |value| matches!(value, Value::Hash(hash) if hash.has_expired(now)),
+        );
+        has_expired == Some(true)
+            && self.write(key.clone(), |slot| match slot {
+                Some(Value::Hash(hash)) => hash.remove_expired(now) > 0,
+                _ => false,
+            })
+    }
+
+    /// active expire：从 shard 里随机抽样最多 n 个带过期时间的 key，删除其中已经过期的 key，
+    /// 以及 hash 里已经过期的 field，返回抽样和有数据过期的 key 的个数
+    pub(crate) fn expire_sample(
+        &self,
+        shard: usize,
+        n: usize,
+        rng: &mut impl Rng,
+    ) -> (usize, usize) {
+        debug_assert!(self.shards.binary_search(&shard).is_ok());
+        // 先复制出来再释放 volatile 的锁，删除的时候还要加锁
+        let sampled = self.volatile(shard).sample(rng, n);
+        let now = now_ms();
+        let expired = sampled
+            .iter()
+            .filter(|key| self.remove_expired(key, now) || self.remove_expired_fields(key, now))
+            .count();
+        (sampled.len(), expired)
+    }
+
+    /// 和 read_object 一样，但是返回引用，可以同时持有多个 key 的引用，SINTER 这类命令不需要复制集合
+    /// 调用者独占了 key 所在的分片，所以持有引用的时候没有其他客户端修改这个分片，
+    /// 但是自己也要先释放引用再修改同一个分片上的 key
+    /// 过期的 key 当作不存在，不在这里删除，因为删除需要等待其他引用释放
+    pub(crate) fn get(&self, key: &[u8]) -> Option<Ref<'a, Bytes, Object>> {
+        self.check_locked(key);
+        self.db
+            .get(key)
+            .filter(|object| !object.is_expired(now_ms()))
+    }
+
+    pub fn exists(&self, key: &[u8]) -> bool {
+        self.read_object(key, |_| ()).is_some()
+    }
+
+    /// 修改一个 key，f 拿到的是 key 当前的值，不存在时是 None
+    /// f 把它设置为 None 或者一个空的集合时，key 会被删除，原来的过期时间保持不变
+    /// 整个过程持有 key 所在分片的锁，所以对单个 key 的读改写是原子的
+    pub fn write<T>(&self, key: Bytes, f: impl FnOnce(&mut Option<Value>) -> T) -> T {
+        self.write_object(key, |slot| {
+            let expires_at = slot.as_ref().and_then(|object| object.expires_at);
+            let value = slot.take().map(|object| object.value);
+            let mut guard = ValueGuard {
+                slot,
+                value,
+                expires_at,
+            };
+            f(&mut guard.value)
+        })
+    }
+
+    /// 和 write 一样，但是可以修改过期时间，已经过期的 key 当作不存在
+    pub fn write_object<T>(&self, key: Bytes, f: impl FnOnce(&mut Option<Object>) -> T) -> T {
+        self.check_locked(&key);
+        let now = now_ms();
+
+        let mut guard = match self.db.entry(key) {
+            Entry::Occupied(entry) if entry.get().is_expired(now) => SlotGuard {
+                ks: *self,
+                had_expiry: entry.get().has_expiry(),
+                entry: Some(Entry::Occupied(entry)),
+                slot: None,
+            },
+            Entry::Occupied(mut entry) => {
+                // 用一个不需要分配内存的值占位，把原来的值拿出来交给 f
+                let placeholder = Object::new(Value::String(Bytes::new()));
+                let object = std::mem::replace(entry.get_mut(), placeholder);
+                SlotGuard {
+                    ks: *self,
+                    had_expiry: object.has_expiry(),
+                    entry: Some(Entry::Occupied(entry)),
+                    slot: Some(object),
+                }
+            }
+            entry => SlotGuard {
+                ks: *self,
+                had_expiry: false,
+                entry: Some(entry),
+                slot: None,
+            },
+        };
+        f(&mut guard.slot)
+    }
+
+    /// 覆盖 key 原来的值，包括过期时间
+    pub fn insert(&self, key: Bytes, object: Object) {
+        self.check_locked(&key);
+        let has_expiry = object.has_expiry();
+        match self.db.entry(key) {
+            Entry::Occupied(mut entry) => {
+                self.track_expiry(entry.key(), entry.get().has_expiry(), has_expiry);
+                entry.insert(object);
+            }
+            Entry::Vacant(entry) => {
+                self.track_expiry(entry.key(), false, has_expiry);
+                entry.insert(object);
+            }
+        }
+    }
+}
+
+// write_object 交给 f 的值，drop 的时候写回 keyspace
+// f panic 的时候也会写回，key 不会留下占位用的空字符串
+struct SlotGuard<'a> {
+    ks: Keyspace<'a>,
+    // 原来的值有没有过期时间，写回的时候据此维护 volatile
+    had_expiry: bool,
+    entry: Option<Entry<'a, Bytes, Object>>,
+    slot: Option<Object>,
+}
+
+impl Drop for SlotGuard<'_> {
+    fn drop(&mut self) {
+        let object = self.slot.take().filter(|object| !object.value.is_empty());
+        // 还持有 entry，也就是 DashMap 的分片锁，和写回在同一个锁里
+        if let Some(entry) = &self.entry {
+            let has_expiry = object.as_ref().is_some_and(Object::has_expiry);
+            self.ks
+                .track_expiry(entry.key(), self.had_expiry, has_expiry);
+        }
+        match (self.entry.take(), object) {
+            (Some(Entry::Occupied(mut entry)), Some(object)) => *entry.get_mut() = object,
+            (Some(Entry::Occupied(entry)), None) => {
+                entry.remove();
+            }
+            (Some(Entry::Vacant(entry)), Some(object)) => {
+                entry.insert(object);
+            }
+            _ => {}
+        }
+    }
+}
+
+// write 交给 f 的值，drop 的时候带上原来的过期时间放回 slot，f panic 的时候也一样
+struct ValueGuard<'a> {
+    slot: &'a mut Option<Object>,
+    value: Option<Value>,
+    expires_at: Option<u64>,
+}
+
+impl Drop for ValueGuard<'_> {
+    fn drop(&mut self) {
+        let expires_at = self.expires_at;
+        *self.slot = self.value.take().map(|value| Object { value, expires_at });
+    }
+}
@@ -0,0 +1,540 @@
+use super::{Backend, Keyspace};
+use crate::cmd::CommandError;
+use crate::Value;
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::future::Future;
+
+/// 列表的两端，LPUSH、LPOP 操作的是 Left，RPUSH、RPOP 操作的是 Right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl ListEnd {
+    fn push(self, list: &mut VecDeque<Bytes>, value: Bytes) {
+        match self {
+            ListEnd::Left => list.push_front(value),
+            ListEnd::Right => list.push_back(value),
+        }
+    }
+
+    fn pop(self, list: &mut VecDeque<Bytes>) -> Option<Bytes> {
+        match self {
+            ListEnd::Left => list.pop_front(),
+            ListEnd::Right => list.pop_back(),
+        }
+    }
+}
+
+impl Backend {
+    // 只读访问一个列表，key 不存在时返回 None
+    fn read_list<T>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&VecDeque<Bytes>) -> T,
+    ) -> Result<Option<T>, CommandError> {
+        self.read(key, |value| match value {
+            Value::List(list) => Ok(f(list)),
+            _ => Err(CommandError::WrongType),
+        })
+        .transpose()
+    }
+
+    // 修改一个列表，参考 write_list_in
+    fn write_list<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut VecDeque<Bytes>) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        self.with_shard(self.shard(&key), |ks| write_list_in(ks, key, f))
+    }
+
+    // 和 write_list 一样，但是在同一个锁里把新数据交给阻塞在 key 上的客户端
+    fn push_list<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut VecDeque<Bytes>) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        let wakes = [key];
+        self.write_and_wake(&wakes, &wakes, |ks| write_list_in(ks, wakes[0].clone(), f))
+    }
+
+    /// 依次把 values 放到列表的一端，返回新的长度
+    pub fn push(
+        &self,
+        key: Bytes,
+        end: ListEnd,
+        values: Vec<Bytes>,
+    ) -> Result<usize, CommandError> {
+        self.push_list(key, |list| {
+            for value in values {
+                end.push(list, value);
+            }
+            Ok(list.len())
+        })
+    }
+
+    /// 和 push 一样，但是 key 不存在时什么都不做，返回 0
+    pub fn pushx(
+        &self,
+        key: Bytes,
+        end: ListEnd,
+        values: Vec<Bytes>,
+    ) -> Result<usize, CommandError> {
+        self.push_list(key, |list| {
+            if list.is_empty() {
+                return Ok(0);
+            }
+            for value in values {
+                end.push(list, value);
+            }
+            Ok(list.len())
+        })
+    }
+
+    /// 从一端弹出最多 count 个元素，key 不存在时返回 None
+    pub fn pop(
+        &self,
+        key: Bytes,
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<Vec<Bytes>>, CommandError> {
+        self.write_list(key, |list| Ok(pop_list(list, end, count)))
+    }
+
+    /// 从第一个非空的列表的一端弹出最多 count 个元素，返回 key 和弹出的元素，所有列表都是空的时返回 None
+    pub fn mpop(
+        &self,
+        keys: &[Bytes],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>, CommandError> {
+        self.try_serve(keys, |ks, key| pop_in(ks, key, end, count))
+    }
+
+    /// BLPOP、BRPOP、BLMPOP：和 mpop 一样，但是所有列表都是空的时等待其他客户端写入，
+    /// 直到 until 完成，也就是超时或者客户端断开连接，这时候返回 None
+    pub async fn bpop(
+        &self,
+        keys: Vec<Bytes>,
+        end: ListEnd,
+        count: usize,
+        until: impl Future<Output = ()>,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>, CommandError> {
+        self.block_on(keys, vec![], until, move |ks, key| {
+            pop_in(ks, key, end, count)
+        })
+        .await
+    }
+
+    pub fn llen(&self, key: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.read_list(key, VecDeque::len)?.unwrap_or(0))
+    }
+
+    /// start 和 stop 都是闭区间，负数表示从末尾开始数
+    pub fn lrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Bytes>, CommandError> {
+        let range = self.read_list(key, |list| match list_range(list.len(), start, stop) {
+            Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+            None => Vec::new(),
+        })?;
+        Ok(range.unwrap_or_default())
+    }
+
+    pub fn lindex(&self, key: &[u8], index: i64) -> Result<Option<Bytes>, CommandError> {
+        let value = self.read_list(key, |list| {
+            list_index(list.len(), index).map(|index| list[index].clone())
+        })?;
+        Ok(value.flatten())
+    }
+
+    pub fn lset(&self, key: Bytes, index: i64, value: Bytes) -> Result<(), CommandError> {
+        self.write_list(key, |list| {
+            if list.is_empty() {
+                return Err(CommandError::InvalidArgument("no such key".to_string()));
+            }
+            let index = list_index(list.len(), index)
+                .ok_or_else(|| CommandError::InvalidArgument("index out of range".to_string()))?;
+            list[index] = value;
+            Ok(())
+        })
+    }
+
+    /// 在第一个等于 pivot 的元素前面或者后面插入 value，返回新的长度
+    /// 找不到 pivot 时返回 -1，key 不存在时返回 0
+    pub fn linsert(
+        &self,
+        key: Bytes,
+        before: bool,
+        pivot: &[u8],
+        value: Bytes,
+    ) -> Result<i64, CommandError> {
+        self.push_list(key, |list| {
+            if list.is_empty() {
+                return Ok(0);
+            }
+            let Some(index) = list.iter().position(|item| item == pivot) else {
+                return Ok(-1);
+            };
+            list.insert(if before { index } else { index + 1 }, value);
+            Ok(list.len() as i64)
+        })
+    }
+
+    /// 删除等于 value 的元素，count 大于 0 时从头开始删除最多 count 个，小于 0 时从尾部开始，
+    /// 等于 0 时全部删除，返回删除的个数
+    pub fn lrem(&self, key: Bytes, count: i64, value: &[u8]) -> Result<usize, CommandError> {
+        self.write_list(key, |list| {
+            let limit = match count {
+                0 => usize::MAX,
+                _ => count.unsigned_abs().try_into().unwrap_or(usize::MAX),
+            };
+
+            let mut removed = 0;
+            if count < 0 {
+                let mut index = list.len();
+                while index > 0 && removed < limit {
+                    index -= 1;
+                    if list[index] == value {
+                        list.remove(index);
+                        removed += 1;
+                    }
+                }
+            } else {
+                list.retain(|item| {
+                    if removed < limit && item == value {
+                        removed += 1;
+                        return false;
+                    }
+                    true
+                });
+            }
+            Ok(removed)
+        })
+    }
+
+    /// 只保留 start 到 stop 之间的元素，范围为空时整个列表都会被删除
+    pub fn ltrim(&self, key: Bytes, start: i64, stop: i64) -> Result<(), CommandError> {
+        self.write_list(key, |list| {
+            match list_range(list.len(), start, stop) {
+                Some((start, stop)) => {
+                    list.truncate(stop + 1);
+                    list.drain(..start);
+                }
+                None => list.clear(),
+            }
+            Ok(())
+        })
+    }
+
+    /// 返回等于 element 的元素的下标，rank 表示从第几个匹配开始，负数表示从尾部往前找，不能是 0
+    /// count 为 0 表示返回所有匹配，maxlen 为 0 表示不限制比较的元素个数
+    /// 不管从哪一端开始找，下标都是从头开始算的
+    pub fn lpos(
+        &self,
+        key: &[u8],
+        element: &[u8],
+        rank: i64,
+        count: usize,
+        maxlen: usize,
+    ) -> Result<Vec<usize>, CommandError> {
+        let count = if count == 0 { usize::MAX } else { count };
+        let maxlen = if maxlen == 0 { usize::MAX } else { maxlen };
+        let skip = rank
+            .unsigned_abs()
+            .saturating_sub(1)
+            .try_into()
+            .unwrap_or(usize::MAX);
+
+        let positions = self.read_list(key, |list| {
+            let indexes: Box<dyn Iterator<Item = usize>> = if rank < 0 {
+                Box::new((0..list.len()).rev())
+            } else {
+                Box::new(0..list.len())
+            };
+            indexes
+                .take(maxlen)
+                .filter(|&index| list[index] == element)
+                .skip(skip)
+                .take(count)
+                .collect()
+        })?;
+        Ok(positions.unwrap_or_default())
+    }
+
+    /// 从 source 的一端弹出一个元素放到 destination 的一端，返回这个元素
+    /// 两个 key 在同一个锁里修改，其他命令不会看到元素已经弹出但还没有放进去的中间状态
+    pub fn lmove(
+        &self,
+        source: Bytes,
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>, CommandError> {
+        let wakes = [destination];
+        self.write_and_wake([&source, &wakes[0]], &wakes, |ks| {
+            lmove_in(ks, &source, &wakes[0], from, to)
+        })
+    }
+
+    /// 和 lmove 一样，但是 source 是空的时等待其他客户端写入，
+    /// 直到 until 完成，也就是超时或者客户端断开连接，这时候返回 None
+    pub async fn blmove(
+        &self,
+        source: Bytes,
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+        until: impl Future<Output = ()>,
+    ) -> Result<Option<Bytes>, CommandError> {
+        let wakes = vec![destination.clone()];
+        self.block_on(vec![source], wakes, until, move |ks, source| {
+            lmove_in(ks, source, &destination, from, to)
+        })
+        .await
+    }
+}
+
+// 在 keyspace 里修改一个列表，key 不存在时从空列表开始，列表被清空时 key 也会被删除
+// 所以 LPUSHX 这类不能创建 key 的命令要自己检查列表是否为空
+fn write_list_in<T>(
+    ks: Keyspace<'_>,
+    key: Bytes,
+    f: impl FnOnce(&mut VecDeque<Bytes>) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    ks.write(key, |slot| {
+        match slot.get_or_insert_with(|| Value::List(VecDeque::new())) {
+            Value::List(list) => f(list),
+            _ => Err(CommandError::WrongType),
+        }
+    })
+}
+
+fn pop_list(list: &mut VecDeque<Bytes>, end: ListEnd, count: usize) -> Option<Vec<Bytes>> {
+    if list.is_empty() {
+        return None;
+    }
+    let count = count.min(list.len());
+    Some((0..count).filter_map(|_| end.pop(list)).collect())
+}
+
+// 在 keyspace 里弹出元素，给阻塞命令和 mpop 使用，key 不存在时返回 None
+fn pop_in(
+    ks: Keyspace<'_>,
+    key: &Bytes,
+    end: ListEnd,
+    count: usize,
+) -> Result<Option<(Bytes, Vec<Bytes>)>, CommandError> {
+    let values = ks.write(key.clone(), |slot| match slot {
+        None => Ok(None),
+        Some(Value::List(list)) => Ok(pop_list(list, end, count)),
+        Some(_) => Err(CommandError::WrongType),
+    })?;
+    Ok(values.map(|values| (key.clone(), values)))
+}
+
+// LMOVE 需要同时修改两个 key，只能在 atomically 里面调用
+fn lmove_in(
+    ks: Keyspace<'_>,
+    source: &Bytes,
+    destination: &Bytes,
+    from: ListEnd,
+    to: ListEnd,
+) -> Result<Option<Bytes>, CommandError> {
+    let is_list = |key: &[u8]| ks.read(key, |value| matches!(value, Value::List(_)));
+    match is_list(source) {
+        None => return Ok(None),
+        Some(false) => return Err(CommandError::WrongType),
+        Some(true) => {}
+    }
+    // 目标的类型不对时不能从 source 里弹出元素
+    if is_list(destination) == Some(false) {
+        return Err(CommandError::WrongType);
+    }
+
+    // 同一个 key 时在一次修改里完成，否则只有一个元素的列表会被删除再重新创建，丢掉过期时间
+    if source == destination {
+        return Ok(ks.write(source.clone(), |slot| match slot {
+            Some(Value::List(list)) => {
+                let value = from.pop(list)?;
+                to.push(list, value.clone());
+                Some(value)
+            }
+            _ => None,
+        }));
+    }
+
+    let value = ks.write(source.clone(), |slot| match slot {
+        Some(Value::List(list)) => from.pop(list),
+        _ => None,
+    });
+    if let Some(value) = &value {
+        ks.write(destination.clone(), |slot| {
+            if let Value::List(list) = slot.get_or_insert_with(|| Value::List(VecDeque::new())) {
+                to.push(list, value.clone());
+            }
+        });
+    }
+    Ok(value)
+}
+
+// 把 LRANGE 这类命令的闭区间换算成下标，和 redis 一样处理负数和越界，范围为空时返回 None
+pub(crate) fn list_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+// 负数下标从末尾开始数，越界时返回 None
+fn list_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&'static str]) -> Vec<Bytes> {
+        items.iter().map(|item| Bytes::from(*item)).collect()
+    }
+
+    #[test]
+    fn test_push_pop_range() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            backend
+                .pushx("list".into(), ListEnd::Left, list(&["x"]))
+                .unwrap(),
+            0
+        );
+        assert_eq!(backend.key_type(b"list"), "none");
+
+        backend
+            .push("list".into(), ListEnd::Left, list(&["b", "a"]))
+            .unwrap();
+        backend
+            .push("list".into(), ListEnd::Right, list(&["c", "d"]))
+            .unwrap();
+        assert_eq!(
+            backend.lrange(b"list", 0, -1).unwrap(),
+            list(&["a", "b", "c", "d"])
+        );
+        assert_eq!(backend.lrange(b"list", -3, 1).unwrap(), list(&["b"]));
+        assert_eq!(backend.lrange(b"list", 2, 100).unwrap(), list(&["c", "d"]));
+        assert!(backend.lrange(b"list", 5, 10).unwrap().is_empty());
+        assert_eq!(backend.lindex(b"list", -1).unwrap(), Some("d".into()));
+        assert_eq!(backend.lindex(b"list", 4).unwrap(), None);
+
+        assert_eq!(
+            backend.pop("list".into(), ListEnd::Right, 3).unwrap(),
+            Some(list(&["d", "c", "b"]))
+        );
+        assert_eq!(
+            backend.pop("list".into(), ListEnd::Left, 3).unwrap(),
+            Some(list(&["a"]))
+        );
+        // 最后一个元素弹出之后 key 也被删除了
+        assert_eq!(backend.key_type(b"list"), "none");
+        assert_eq!(backend.pop("list".into(), ListEnd::Left, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_list_edit() {
+        let backend = Backend::new();
+        let items = list(&["a", "b", "a", "c", "a"]);
+        backend.push("list".into(), ListEnd::Right, items).unwrap();
+
+        assert_eq!(backend.lrem("list".into(), -2, b"a").unwrap(), 2);
+        assert_eq!(
+            backend.lrange(b"list", 0, -1).unwrap(),
+            list(&["a", "b", "c"])
+        );
+        assert_eq!(
+            backend
+                .linsert("list".into(), true, b"c", "x".into())
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            backend
+                .linsert("list".into(), false, b"nope", "x".into())
+                .unwrap(),
+            -1
+        );
+        backend.lset("list".into(), -1, "z".into()).unwrap();
+        assert!(backend.lset("list".into(), 10, "z".into()).is_err());
+        assert!(backend.lset("missing".into(), 0, "z".into()).is_err());
+        assert_eq!(backend.key_type(b"missing"), "none");
+
+        backend.ltrim("list".into(), 1, -2).unwrap();
+        assert_eq!(backend.lrange(b"list", 0, -1).unwrap(), list(&["b", "x"]));
+        backend.ltrim("list".into(), 5, 10).unwrap();
+        assert_eq!(backend.key_type(b"list"), "none");
+    }
+
+    #[test]
+    fn test_lpos() {
+        let backend = Backend::new();
+        let items = list(&["a", "b", "c", "1", "2", "3", "c", "c"]);
+        backend.push("list".into(), ListEnd::Right, items).unwrap();
+
+        assert_eq!(backend.lpos(b"list", b"c", 1, 1, 0).unwrap(), vec![2]);
+        assert_eq!(backend.lpos(b"list", b"c", 2, 1, 0).unwrap(), vec![6]);
+        assert_eq!(backend.lpos(b"list", b"c", 1, 0, 0).unwrap(), vec![2, 6, 7]);
+        assert_eq!(backend.lpos(b"list", b"c", -1, 2, 0).unwrap(), vec![7, 6]);
+        assert_eq!(backend.lpos(b"list", b"c", 1, 0, 3).unwrap(), vec![2]);
+        assert!(backend.lpos(b"list", b"z", 1, 0, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lmove() {
+        let backend = Backend::new();
+        backend
+            .push("src".into(), ListEnd::Right, list(&["a", "b"]))
+            .unwrap();
+        backend.set("str".into(), "x".into());
+
+        // 目标类型不对时 source 不会被修改
+        assert!(matches!(
+            backend.lmove("src".into(), "str".into(), ListEnd::Left, ListEnd::Left),
+            Err(CommandError::WrongType)
+        ));
+        assert_eq!(backend.llen(b"src").unwrap(), 2);
+
+        let moved = backend
+            .lmove("src".into(), "dst".into(), ListEnd::Right, ListEnd::Left)
+            .unwrap();
+        assert_eq!(moved, Some("b".into()));
+        assert_eq!(backend.lrange(b"dst", 0, -1).unwrap(), list(&["b"]));
+
+        // 同一个 key 时相当于旋转列表
+        backend
+            .push("src".into(), ListEnd::Right, list(&["c"]))
+            .unwrap();
+        backend
+            .lmove("src".into(), "src".into(), ListEnd::Left, ListEnd::Right)
+            .unwrap();
+        assert_eq!(backend.lrange(b"src", 0, -1).unwrap(), list(&["c", "a"]));
+        assert_eq!(
+            backend
+                .lmove("none".into(), "dst".into(), ListEnd::Left, ListEnd::Left)
+                .unwrap(),
+            None
+        );
+    }
+}
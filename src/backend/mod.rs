@@ -1,7 +1,35 @@
-use crate::RespFrame;
+mod bitmap;
+mod blocking;
+mod expire;
+mod hash;
+mod keyspace;
+mod list;
+mod scan;
+mod set;
+mod skiplist;
+mod string;
+mod value;
+mod zset;
+
+pub use bitmap::{
+    BitFieldOp, BitFieldType, BitOperation, BitOverflow, BitRange, BitUnit, MAX_BIT_OFFSET,
+};
+use expire::VolatileKeys;
+pub use expire::{now_ms, ExpireCondition};
+pub use hash::MAX_RANDOM_COUNT;
+pub use keyspace::Keyspace;
+pub use list::ListEnd;
+pub use scan::{glob_match, ScanOptions};
+pub use set::SetOperation;
+pub use string::{SetExpire, SetOptions, MAX_STRING_SIZE};
+pub use value::{Hash, Object, SortedSet, Value};
+pub use zset::{Aggregate, LexBound, ScoreBound, ZAddOptions, ZEnd, ZPopped, ZRangeBy, ZRangeSpec};
+
+use crate::cmd::CommandError;
+use bytes::Bytes;
 use dashmap::DashMap;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockWriteGuard};
 
 #[derive(Debug, Clone)]
 pub struct Backend(Arc<BackendInner>);
@@ -9,8 +37,18 @@ pub struct Backend(Arc<BackendInner>);
 #[derive(Debug)]
 pub struct BackendInner {
     // DashMap 可以在多线程之间安全的共享和 修改数据
-    pub(crate) map: DashMap<String, RespFrame>,
-    pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
+    // 所有类型的 key 都在同一个 keyspace 里，key 是任意的字节串，和 redis 一样是二进制安全的
+    // 过期时间和值保存在一起，修改值和过期时间都在同一个锁里
+    pub(crate) db: DashMap<Bytes, Object>,
+    // 和 DashMap 的分片一一对应的锁
+    // 单个 key 的操作共享 key 所在分片的锁，由 DashMap 的分片锁保证原子性
+    // 多个 key 的操作按下标从小到大独占这些 key 所在的分片，期间不会有其他命令修改这些 key
+    // 其他分片上的命令不受影响
+    locks: Box<[RwLock<()>]>,
+    // 每个分片里带过期时间的 key，active expire 从这里抽样，由 Keyspace 在修改 key 的时候维护
+    volatile: Box<[Mutex<VolatileKeys>]>,
+    // 阻塞在 key 上等待数据的客户端
+    waiters: blocking::Waiters,
 }
 
 impl Deref for Backend {
@@ -29,9 +67,15 @@ impl Default for Backend {
 
 impl Default for BackendInner {
     fn default() -> Self {
+        let db = DashMap::new();
+        let locks = db.shards().iter().map(|_| RwLock::new(())).collect();
+        let volatile = db.shards().iter().map(|_| Mutex::default()).collect();
+        let waiters = blocking::Waiters::new(db.shards().len());
         Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
+            db,
+            locks,
+            volatile,
+            waiters,
         }
     }
 }
@@ -41,30 +85,171 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map
-            .get(key)
-            // RespFrame 需要实现 Clone
-            .map(|v| v.value().clone())
+    // key 所在的分片
+    fn shard(&self, key: &[u8]) -> usize {
+        self.db.determine_map(key)
+    }
+
+    // 共享一个分片的锁，执行这个分片上单个 key 的操作
+    fn with_shard<T>(&self, shard: usize, f: impl FnOnce(Keyspace<'_>) -> T) -> T {
+        let _guard = self.locks[shard]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        f(Keyspace::new(
+            &self.db,
+            &self.volatile,
+            std::slice::from_ref(&shard),
+        ))
+    }
+
+    // 按下标从小到大独占 shards 的锁，所有多个 key 的操作都按这个顺序加锁，所以不会死锁
+    fn lock_shards(&self, shards: &[usize]) -> Vec<RwLockWriteGuard<'_, ()>> {
+        shards
+            .iter()
+            .map(|&shard| {
+                self.locks[shard]
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+            })
+            .collect()
+    }
+
+    // 独占 shards 执行 f，shards 可以有重复，也不需要排序
+    fn with_shards<T>(&self, mut shards: Vec<usize>, f: impl FnOnce(Keyspace<'_>) -> T) -> T {
+        shards.sort_unstable();
+        shards.dedup();
+
+        let _guards = self.lock_shards(&shards);
+        f(Keyspace::new(&self.db, &self.volatile, &shards))
+    }
+
+    /// 独占 keys 所在的分片执行 f，用于 MSETNX 这种涉及多个 key 的原子操作
+    /// f 里面只能通过 Keyspace 访问 keys，调用 Backend 上的方法会死锁
+    pub fn atomically<K, T>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+        f: impl FnOnce(Keyspace<'_>) -> T,
+    ) -> T
+    where
+        K: AsRef<[u8]>,
+    {
+        let shards = keys
+            .into_iter()
+            .map(|key| self.shard(key.as_ref()))
+            .collect();
+        self.with_shards(shards, f)
+    }
+
+    /// 只读访问一个 key，key 不存在时返回 None
+    pub fn read<T>(&self, key: &[u8], f: impl FnOnce(&Value) -> T) -> Option<T> {
+        self.with_shard(self.shard(key), |ks| ks.read(key, f))
+    }
+
+    /// 和 read 一样，但是可以拿到过期时间，参考 Keyspace::read_object
+    pub fn read_object<T>(&self, key: &[u8], f: impl FnOnce(&Object) -> T) -> Option<T> {
+        self.with_shard(self.shard(key), |ks| ks.read_object(key, f))
+    }
+
+    /// 修改一个 key，参考 Keyspace::write
+    pub fn write<T>(&self, key: Bytes, f: impl FnOnce(&mut Option<Value>) -> T) -> T {
+        self.with_shard(self.shard(&key), |ks| ks.write(key, f))
+    }
+
+    /// 修改一个 key 和它的过期时间，参考 Keyspace::write_object
+    pub fn write_object<T>(&self, key: Bytes, f: impl FnOnce(&mut Option<Object>) -> T) -> T {
+        self.with_shard(self.shard(&key), |ks| ks.write_object(key, f))
+    }
+
+    /// key 的类型，不存在时是 none
+    pub fn key_type(&self, key: &[u8]) -> &'static str {
+        self.read(key, Value::type_name).unwrap_or("none")
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>, CommandError> {
+        self.read(key, |value| match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(CommandError::WrongType),
+        })
+        .transpose()
     }
 
-    pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+    // SET 会覆盖任何类型的旧值，同时清除过期时间
+    pub fn set(&self, key: Bytes, value: Bytes) {
+        self.with_shard(self.shard(&key), |ks| {
+            ks.insert(key, Object::new(Value::String(value)))
+        });
     }
+}
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap.get(key).and_then(|v|
-                // 内层 dashMap的处理
-                v.get(field)
-                    .map(|v| v.value().clone()))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn test_single_keyspace() {
+        let backend = Backend::new();
+
+        backend.set("key".into(), "value".into());
+        assert_eq!(backend.key_type(b"key"), "string");
+        assert!(matches!(
+            backend.hset("key".into(), "field".into(), "value".into()),
+            Err(CommandError::WrongType)
+        ));
+
+        assert!(backend
+            .hset("hash".into(), "field".into(), "value".into())
+            .unwrap());
+        assert_eq!(backend.key_type(b"hash"), "hash");
+        assert!(matches!(backend.get(b"hash"), Err(CommandError::WrongType)));
+
+        // SET 会覆盖掉 hash
+        backend.set("hash".into(), "value".into());
+        assert_eq!(backend.key_type(b"hash"), "string");
+        assert_eq!(backend.key_type(b"nope"), "none");
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    #[test]
+    fn test_write_removes_empty_value() {
+        let backend = Backend::new();
+
+        backend
+            .hset("hash".into(), "field".into(), "value".into())
+            .unwrap();
+        backend.write("hash".into(), |slot| {
+            if let Some(Value::Hash(hash)) = slot {
+                hash.remove(b"field".as_slice());
+            }
+        });
+        assert_eq!(backend.key_type(b"hash"), "none");
+
+        // 不创建值的时候 key 不会出现
+        backend.write("missing".into(), |_| ());
+        assert!(backend.db.is_empty());
     }
 
-    pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+    #[test]
+    fn test_write_restores_value_on_panic() {
+        let backend = Backend::new();
+        backend.sadd("s".into(), vec!["a".into()]).unwrap();
+
+        let ret = catch_unwind(AssertUnwindSafe(|| {
+            backend.write("s".into(), |slot| {
+                if let Some(Value::Set(set)) = slot {
+                    set.insert("b".into());
+                }
+                panic!("boom");
+            })
+        }));
+        assert!(ret.is_err());
+
+        // panic 之前的修改还在，key 没有变成空字符串
+        assert_eq!(backend.key_type(b"s"), "set");
+        let members = backend.smembers(b"s").unwrap();
+        assert_eq!(
+            members.into_iter().collect::<HashSet<_>>(),
+            HashSet::from(["a".into(), "b".into()])
+        );
     }
 }
@@ -0,0 +1,173 @@
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// HSCAN 这类命令的 MATCH 和 COUNT 选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    pub pattern: Option<Bytes>,
+    /// 每次大约检查多少个元素，不是返回的元素个数
+    pub count: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            count: 10,
+        }
+    }
+}
+
+// 元素按照固定的哈希值排序，游标就是下一页第一个元素的哈希值
+// 这样元素的增删不会影响其他元素的位置，从头到尾一直存在的元素至少会被返回一次
+fn scan_hash(member: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    member.hash(&mut hasher);
+    // 0 表示遍历结束，不能作为游标
+    hasher.finish().max(1)
+}
+
+/// 从 cursor 开始取一页，返回下一页的游标和这一页里匹配 pattern 的元素
+pub(crate) fn scan_page<T>(
+    items: impl Iterator<Item = T>,
+    member: impl Fn(&T) -> &[u8],
+    cursor: u64,
+    options: &ScanOptions,
+) -> (u64, Vec<T>) {
+    let mut items = items
+        .map(|item| (scan_hash(member(&item)), item))
+        .filter(|(hash, _)| *hash >= cursor)
+        .collect::<Vec<_>>();
+    items.sort_unstable_by_key(|(hash, _)| *hash);
+
+    // 哈希值相同的元素必须在同一页返回，否则游标无法区分它们
+    let mut end = items.len().min(options.count.max(1));
+    while end < items.len() && end > 0 && items[end].0 == items[end - 1].0 {
+        end += 1;
+    }
+    let next = items.get(end).map_or(0, |(hash, _)| *hash);
+
+    let page = items
+        .into_iter()
+        .take(end)
+        .map(|(_, item)| item)
+        .filter(|item| match &options.pattern {
+            Some(pattern) => glob_match(pattern, member(item)),
+            None => true,
+        })
+        .collect();
+    (next, page)
+}
+
+/// 和 redis 的 stringmatchlen 一样的 glob 匹配，支持 *、?、[abc]、[^a-z] 和 \ 转义
+pub fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => {
+            // 连续的 * 和一个 * 是一样的
+            let rest = &rest[rest.iter().take_while(|&&p| p == b'*').count()..];
+            rest.is_empty() || (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+        }
+        Some((b'?', rest)) => !s.is_empty() && glob_match(rest, &s[1..]),
+        Some((b'[', rest)) => {
+            let Some((&c, tail)) = s.split_first() else {
+                return false;
+            };
+            let (matched, rest) = match_class(rest, c);
+            matched && glob_match(rest, tail)
+        }
+        Some((b'\\', rest)) if !rest.is_empty() => {
+            s.first() == Some(&rest[0]) && glob_match(&rest[1..], &s[1..])
+        }
+        Some((&p, rest)) => s.first() == Some(&p) && glob_match(rest, &s[1..]),
+    }
+}
+
+// 匹配 [...] 里的字符集合，返回是否匹配以及 ] 之后剩下的 pattern
+fn match_class(pattern: &[u8], c: u8) -> (bool, &[u8]) {
+    let (negate, mut pattern) = match pattern.split_first() {
+        Some((b'^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut matched = false;
+    loop {
+        match pattern {
+            // 没有 ] 的时候当作到 pattern 结尾
+            [] => break,
+            [b']', rest @ ..] => {
+                pattern = rest;
+                break;
+            }
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == c;
+                pattern = rest;
+            }
+            [start, b'-', end, rest @ ..] if *end != b']' => {
+                let (low, high) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= (low..=high).contains(&c);
+                pattern = rest;
+            }
+            [p, rest @ ..] => {
+                matched |= *p == c;
+                pattern = rest;
+            }
+        }
+    }
+
+    (matched != negate, pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h*llo", b"heeeello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo"));
+        assert!(glob_match(b"h\\*llo", b"h*llo"));
+        assert!(!glob_match(b"h\\*llo", b"hello"));
+        assert!(!glob_match(b"user:*", b"session:1"));
+    }
+
+    #[test]
+    fn test_scan_page_covers_all_members() {
+        let members = (0..100).map(|i| format!("m{}", i)).collect::<Vec<_>>();
+        let options = ScanOptions {
+            pattern: None,
+            count: 7,
+        };
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        loop {
+            let (next, page) = scan_page(members.iter(), |m| m.as_bytes(), cursor, &options);
+            assert!(page.len() <= 7);
+            seen.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 100);
+
+        let options = ScanOptions {
+            pattern: Some("m1*".into()),
+            count: 1000,
+        };
+        let (next, page) = scan_page(members.iter(), |m| m.as_bytes(), 0, &options);
+        assert_eq!(next, 0);
+        assert_eq!(page.len(), 11);
+    }
+}
@@ -0,0 +1,458 @@
+use super::hash::check_random_count;
+use super::scan::{scan_page, ScanOptions};
+use super::{Backend, Keyspace};
+use crate::cmd::CommandError;
+use crate::{Object, Value};
+use bytes::Bytes;
+use dashmap::mapref::one::MappedRef;
+use rand::seq::{IteratorRandom, SliceRandom};
+use std::collections::HashSet;
+
+// 在 atomically 里同时持有的多个集合的引用
+type SetRef<'a> = MappedRef<'a, Bytes, Object, HashSet<Bytes>>;
+
+/// SINTER、SUNION、SDIFF 以及它们的 STORE 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperation {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl Backend {
+    // 只读访问一个集合，key 不存在时返回 None
+    fn read_set<T>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&HashSet<Bytes>) -> T,
+    ) -> Result<Option<T>, CommandError> {
+        self.read(key, |value| match value {
+            Value::Set(set) => Ok(f(set)),
+            _ => Err(CommandError::WrongType),
+        })
+        .transpose()
+    }
+
+    // 修改一个集合，key 不存在时从空集合开始，集合被清空时 key 也会被删除
+    fn write_set<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut HashSet<Bytes>) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        self.write(key, |slot| {
+            match slot.get_or_insert_with(|| Value::Set(HashSet::new())) {
+                Value::Set(set) => f(set),
+                _ => Err(CommandError::WrongType),
+            }
+        })
+    }
+
+    /// 返回新加入的成员个数
+    pub fn sadd(&self, key: Bytes, members: Vec<Bytes>) -> Result<usize, CommandError> {
+        self.write_set(key, |set| {
+            Ok(members
+                .into_iter()
+                .filter(|member| set.insert(member.clone()))
+                .count())
+        })
+    }
+
+    /// 返回删除的成员个数
+    pub fn srem(&self, key: Bytes, members: &[Bytes]) -> Result<usize, CommandError> {
+        self.write_set(key, |set| {
+            Ok(members.iter().filter(|member| set.remove(*member)).count())
+        })
+    }
+
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool, CommandError> {
+        Ok(self
+            .read_set(key, |set| set.contains(member))?
+            .unwrap_or(false))
+    }
+
+    pub fn smismember(&self, key: &[u8], members: &[Bytes]) -> Result<Vec<bool>, CommandError> {
+        let found = self.read_set(key, |set| {
+            members.iter().map(|member| set.contains(member)).collect()
+        })?;
+        Ok(found.unwrap_or_else(|| vec![false; members.len()]))
+    }
+
+    pub fn scard(&self, key: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.read_set(key, HashSet::len)?.unwrap_or(0))
+    }
+
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Bytes>, CommandError> {
+        Ok(self
+            .read_set(key, |set| set.iter().cloned().collect())?
+            .unwrap_or_default())
+    }
+
+    /// 随机删除并返回最多 count 个成员
+    pub fn spop(&self, key: Bytes, count: usize) -> Result<Vec<Bytes>, CommandError> {
+        let mut rng = rand::thread_rng();
+        self.write_set(key, |set| {
+            let popped = if count >= set.len() {
+                set.drain().collect()
+            } else {
+                set.iter().cloned().choose_multiple(&mut rng, count)
+            };
+            for member in &popped {
+                set.remove(member);
+            }
+            Ok(popped)
+        })
+    }
+
+    /// 和 HRANDFIELD 一样，count 为正数时返回不重复的成员，最多返回整个集合，
+    /// 负数时可能重复，返回 -count 个，不能超过 MAX_RANDOM_COUNT
+    pub fn srandmember(&self, key: &[u8], count: i64) -> Result<Vec<Bytes>, CommandError> {
+        check_random_count(count)?;
+        let mut rng = rand::thread_rng();
+        Ok(self
+            .read_set(key, |set| {
+                if count >= 0 && count as usize >= set.len() {
+                    set.iter().cloned().collect()
+                } else if count >= 0 {
+                    set.iter()
+                        .cloned()
+                        .choose_multiple(&mut rng, count as usize)
+                } else {
+                    // 只遍历一次集合，之后随机取下标
+                    let members = set.iter().collect::<Vec<_>>();
+                    (0..count.unsigned_abs())
+                        .filter_map(|_| members.choose(&mut rng).map(|member| (*member).clone()))
+                        .collect()
+                }
+            })?
+            .unwrap_or_default())
+    }
+
+    /// 把 member 从 source 移动到 destination，source 中没有这个成员时返回 false
+    pub fn smove(
+        &self,
+        source: Bytes,
+        destination: Bytes,
+        member: Bytes,
+    ) -> Result<bool, CommandError> {
+        self.atomically([source.clone(), destination.clone()], |ks| {
+            let is_member = ks.read(&source, |value| match value {
+                Value::Set(set) => Ok(set.contains(&member)),
+                _ => Err(CommandError::WrongType),
+            });
+            let Some(is_member) = is_member.transpose()? else {
+                return Ok(false);
+            };
+            // 目标的类型不对时不能从 source 里删除
+            if ks.read(&destination, |value| matches!(value, Value::Set(_))) == Some(false) {
+                return Err(CommandError::WrongType);
+            }
+            if !is_member {
+                return Ok(false);
+            }
+            if source == destination {
+                return Ok(true);
+            }
+
+            ks.write(source, |slot| {
+                if let Some(Value::Set(set)) = slot {
+                    set.remove(&member);
+                }
+            });
+            ks.write(destination, |slot| {
+                if let Value::Set(set) = slot.get_or_insert_with(|| Value::Set(HashSet::new())) {
+                    set.insert(member);
+                }
+            });
+            Ok(true)
+        })
+    }
+
+    /// 计算多个集合的交集、并集或者差集，不存在的 key 当作空集合
+    pub fn sop(&self, op: SetOperation, keys: &[Bytes]) -> Result<HashSet<Bytes>, CommandError> {
+        self.atomically(keys, |ks| set_operation(ks, op, keys))
+    }
+
+    /// 和 sop 一样，但是把结果保存到 destination，覆盖原来的值，返回结果的成员个数
+    pub fn sopstore(
+        &self,
+        op: SetOperation,
+        destination: Bytes,
+        keys: &[Bytes],
+    ) -> Result<usize, CommandError> {
+        let locked = keys
+            .iter()
+            .chain([&destination])
+            .cloned()
+            .collect::<Vec<_>>();
+        self.atomically(locked, |ks| {
+            let result = set_operation(ks, op, keys)?;
+            let len = result.len();
+            if result.is_empty() {
+                ks.write(destination, |slot| *slot = None);
+            } else {
+                ks.insert(destination, Object::new(Value::Set(result)));
+            }
+            Ok(len)
+        })
+    }
+
+    /// 交集的成员个数，limit 为 0 表示不限制，否则数到 limit 就停止
+    pub fn sintercard(&self, keys: &[Bytes], limit: usize) -> Result<usize, CommandError> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        self.atomically(keys, |ks| {
+            let Some(sets) = read_sets_in(ks, keys)?
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+            else {
+                return Ok(0);
+            };
+            let Some((smallest, others)) = split_smallest(&sets) else {
+                return Ok(0);
+            };
+            Ok(smallest
+                .iter()
+                .filter(|member| others.iter().all(|set| set.contains(*member)))
+                .take(limit)
+                .count())
+        })
+    }
+
+    /// 返回下一次的游标和这一次遍历到的成员
+    pub fn sscan(
+        &self,
+        key: &[u8],
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> Result<(u64, Vec<Bytes>), CommandError> {
+        Ok(self
+            .read_set(key, |set| {
+                let (next, page) = scan_page(set.iter(), |member| member, cursor, options);
+                (next, page.into_iter().cloned().collect())
+            })?
+            .unwrap_or_default())
+    }
+}
+
+// 在 keyspace 里读取一个集合的引用，key 不存在时返回 None
+fn read_set_in<'a>(ks: Keyspace<'a>, key: &[u8]) -> Result<Option<SetRef<'a>>, CommandError> {
+    let Some(object) = ks.get(key) else {
+        return Ok(None);
+    };
+    match object.try_map(|object| match &object.value {
+        Value::Set(set) => Some(set),
+        _ => None,
+    }) {
+        Ok(set) => Ok(Some(set)),
+        Err(_) => Err(CommandError::WrongType),
+    }
+}
+
+// 先读出所有的集合，这样任何一个 key 的类型不对都会报错
+fn read_sets_in<'a>(
+    ks: Keyspace<'a>,
+    keys: &[Bytes],
+) -> Result<Vec<Option<SetRef<'a>>>, CommandError> {
+    keys.iter().map(|key| read_set_in(ks, key)).collect()
+}
+
+// 交集只需要遍历最小的集合
+fn split_smallest<'s>(
+    sets: &'s [SetRef<'_>],
+) -> Option<(&'s HashSet<Bytes>, Vec<&'s HashSet<Bytes>>)> {
+    let smallest = (0..sets.len()).min_by_key(|&i| sets[i].len())?;
+    let others = sets
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != smallest)
+        .map(|(_, set)| &**set)
+        .collect();
+    Some((&sets[smallest], others))
+}
+
+// 只复制结果里的成员，返回之前释放所有集合的引用，之后才能写入 destination
+fn set_operation(
+    ks: Keyspace<'_>,
+    op: SetOperation,
+    keys: &[Bytes],
+) -> Result<HashSet<Bytes>, CommandError> {
+    let sets = read_sets_in(ks, keys)?;
+
+    Ok(match op {
+        // 有一个 key 不存在时交集就是空的
+        SetOperation::Inter => match sets.into_iter().collect::<Option<Vec<_>>>() {
+            Some(sets) => match split_smallest(&sets) {
+                Some((smallest, others)) => smallest
+                    .iter()
+                    .filter(|member| others.iter().all(|set| set.contains(*member)))
+                    .cloned()
+                    .collect(),
+                None => HashSet::new(),
+            },
+            None => HashSet::new(),
+        },
+        SetOperation::Union => sets
+            .iter()
+            .flatten()
+            .flat_map(|set| set.iter().cloned())
+            .collect(),
+        SetOperation::Diff => {
+            let mut sets = sets.iter();
+            let Some(Some(first)) = sets.next() else {
+                return Ok(HashSet::new());
+            };
+            let others = sets.flatten().collect::<Vec<_>>();
+            first
+                .iter()
+                .filter(|member| !others.iter().any(|set| set.contains(*member)))
+                .cloned()
+                .collect()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_RANDOM_COUNT;
+
+    fn members(items: &[&'static str]) -> Vec<Bytes> {
+        items.iter().map(|item| Bytes::from(*item)).collect()
+    }
+
+    fn sorted(set: impl IntoIterator<Item = Bytes>) -> Vec<Bytes> {
+        let mut set = set.into_iter().collect::<Vec<_>>();
+        set.sort();
+        set
+    }
+
+    #[test]
+    fn test_set_members() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            backend
+                .sadd("set".into(), members(&["a", "b", "a", "c"]))
+                .unwrap(),
+            3
+        );
+        assert_eq!(backend.scard(b"set").unwrap(), 3);
+        assert!(backend.sismember(b"set", b"a").unwrap());
+        assert_eq!(
+            backend.smismember(b"set", &members(&["a", "x"])).unwrap(),
+            vec![true, false]
+        );
+        assert_eq!(
+            backend.srem("set".into(), &members(&["a", "x"])).unwrap(),
+            1
+        );
+        assert_eq!(
+            sorted(backend.smembers(b"set").unwrap()),
+            members(&["b", "c"])
+        );
+
+        assert_eq!(backend.srandmember(b"set", 5).unwrap().len(), 2);
+        // count 再大也只返回整个集合
+        assert_eq!(
+            sorted(backend.srandmember(b"set", i64::MAX / 2).unwrap()),
+            members(&["b", "c"])
+        );
+        assert_eq!(backend.srandmember(b"set", -5).unwrap().len(), 5);
+        // 负数的 count 太大时报错，不会真的生成这么多个
+        assert!(backend.srandmember(b"set", -1_000_000_000_000).is_err());
+        assert!(backend
+            .srandmember(b"set", -(MAX_RANDOM_COUNT as i64) - 1)
+            .is_err());
+        assert_eq!(backend.spop("set".into(), 1).unwrap().len(), 1);
+        assert_eq!(backend.spop("set".into(), 10).unwrap().len(), 1);
+        assert_eq!(backend.key_type(b"set"), "none");
+        assert!(backend.spop("set".into(), 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let backend = Backend::new();
+        backend
+            .sadd("a".into(), members(&["1", "2", "3", "4"]))
+            .unwrap();
+        backend.sadd("b".into(), members(&["3", "4", "5"])).unwrap();
+        let keys = members(&["a", "b"]);
+
+        let inter = backend.sop(SetOperation::Inter, &keys).unwrap();
+        assert_eq!(sorted(inter), members(&["3", "4"]));
+        let union = backend.sop(SetOperation::Union, &keys).unwrap();
+        assert_eq!(sorted(union), members(&["1", "2", "3", "4", "5"]));
+        let diff = backend.sop(SetOperation::Diff, &keys).unwrap();
+        assert_eq!(sorted(diff), members(&["1", "2"]));
+        assert!(backend
+            .sop(SetOperation::Inter, &members(&["a", "missing"]))
+            .unwrap()
+            .is_empty());
+        assert_eq!(backend.sintercard(&keys, 0).unwrap(), 2);
+        assert_eq!(backend.sintercard(&keys, 1).unwrap(), 1);
+
+        // 结果为空时 destination 被删除
+        backend.set("dest".into(), "x".into());
+        assert_eq!(
+            backend
+                .sopstore(SetOperation::Union, "dest".into(), &keys)
+                .unwrap(),
+            5
+        );
+        assert_eq!(backend.key_type(b"dest"), "set");
+        assert_eq!(
+            backend
+                .sopstore(SetOperation::Inter, "dest".into(), &members(&["a", "none"]))
+                .unwrap(),
+            0
+        );
+        assert_eq!(backend.key_type(b"dest"), "none");
+
+        // 同一个 key 出现多次，destination 也是参与计算的 key
+        let keys = members(&["a", "a", "b"]);
+        assert_eq!(backend.sintercard(&keys, 0).unwrap(), 2);
+        assert_eq!(
+            backend
+                .sopstore(
+                    SetOperation::Diff,
+                    "a".into(),
+                    &members(&["a", "b", "missing"])
+                )
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            sorted(backend.smembers(b"a").unwrap()),
+            members(&["1", "2"])
+        );
+
+        backend.set("str".into(), "x".into());
+        assert!(matches!(
+            backend.sop(SetOperation::Union, &members(&["a", "str"])),
+            Err(CommandError::WrongType)
+        ));
+    }
+
+    #[test]
+    fn test_smove() {
+        let backend = Backend::new();
+        backend.sadd("src".into(), members(&["a", "b"])).unwrap();
+        backend.set("str".into(), "x".into());
+
+        assert!(matches!(
+            backend.smove("src".into(), "str".into(), "a".into()),
+            Err(CommandError::WrongType)
+        ));
+        assert!(backend
+            .smove("src".into(), "dst".into(), "a".into())
+            .unwrap());
+        assert!(!backend
+            .smove("src".into(), "dst".into(), "a".into())
+            .unwrap());
+        assert!(backend.sismember(b"dst", b"a").unwrap());
+        assert!(backend
+            .smove("src".into(), "dst".into(), "b".into())
+            .unwrap());
+        assert_eq!(backend.key_type(b"src"), "none");
+        assert_eq!(backend.scard(b"dst").unwrap(), 2);
+    }
+}
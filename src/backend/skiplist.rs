@@ -0,0 +1,364 @@
+use bytes::Bytes;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+// 和 redis 的 zskiplist 一样的参数
+const MAX_LEVEL: usize = 32;
+const P: f64 = 0.25;
+// 头结点固定在下标 0，不保存成员
+const HEAD: usize = 0;
+
+/// 有序集合的排序索引，按照 (score, member) 排序
+/// 和 redis 一样每一层都记录跨过的结点个数，所以可以在 O(log n) 内按排名查找
+/// 结点保存在 Vec 里，用下标代替指针，删除的结点留给下一次插入复用
+#[derive(Debug, Clone)]
+pub(crate) struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    forward: Option<usize>,
+    // 从这个结点到 forward 跨过的结点个数
+    span: usize,
+}
+
+impl Node {
+    // score 相同时按 member 的字节序排序，score 不会是 NaN
+    fn cmp(&self, score: f64, member: &[u8]) -> Ordering {
+        self.score
+            .partial_cmp(&score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.member[..].cmp(member))
+    }
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![Level::default(); MAX_LEVEL],
+        };
+        Self {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+        }
+    }
+}
+
+impl SkipList {
+    fn random_level() -> usize {
+        let mut level = 1;
+        while level < MAX_LEVEL && rand::random::<f64>() < P {
+            level += 1;
+        }
+        level
+    }
+
+    fn forward(&self, node: usize, level: usize) -> Option<usize> {
+        self.nodes[node].levels[level].forward
+    }
+
+    fn span(&self, node: usize, level: usize) -> usize {
+        self.nodes[node].levels[level].span
+    }
+
+    /// 插入一个成员，调用者保证 member 不在列表里
+    pub fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.forward(x, i) {
+                if self.nodes[next].cmp(score, &member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += self.span(x, i);
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            backward: (update[0] != HEAD).then_some(update[0]),
+            levels: vec![Level::default(); level],
+        };
+        let x = match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let prev = self.nodes[update[i]].levels[i];
+            let skipped = rank[0] - rank[i];
+            self.nodes[x].levels[i] = Level {
+                forward: prev.forward,
+                span: prev.span - skipped,
+            };
+            self.nodes[update[i]].levels[i] = Level {
+                forward: Some(x),
+                span: skipped + 1,
+            };
+        }
+        for (i, &prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[prev].levels[i].span += 1;
+        }
+
+        if let Some(next) = self.forward(x, 0) {
+            self.nodes[next].backward = Some(x);
+        }
+        self.len += 1;
+    }
+
+    /// 删除一个成员，score 必须是成员当前的 score，成员不存在时返回 false
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if self.nodes[next].cmp(score, member) != Ordering::Less {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let Some(x) = self.forward(x, 0) else {
+            return false;
+        };
+        if self.nodes[x].cmp(score, member) != Ordering::Equal {
+            return false;
+        }
+
+        for (i, &prev) in update.iter().enumerate().take(self.level) {
+            if self.forward(prev, i) == Some(x) {
+                let removed = self.nodes[x].levels[i];
+                let prev = &mut self.nodes[prev].levels[i];
+                prev.span += removed.span;
+                prev.span -= 1;
+                prev.forward = removed.forward;
+            } else {
+                self.nodes[prev].levels[i].span -= 1;
+            }
+        }
+
+        if let Some(next) = self.forward(x, 0) {
+            self.nodes[next].backward = self.nodes[x].backward;
+        }
+        while self.level > 1 && self.forward(HEAD, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+
+        // 释放成员的内存，结点留给下一次插入
+        self.nodes[x].member = Bytes::new();
+        self.nodes[x].levels = Vec::new();
+        self.free.push(x);
+        self.len -= 1;
+        true
+    }
+
+    /// 成员的排名，从 0 开始
+    pub fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if self.nodes[next].cmp(score, member) == Ordering::Greater {
+                    break;
+                }
+                rank += self.span(x, i);
+                x = next;
+            }
+            // 同一个成员可能以别的 score 排在前面，要 score 和 member 都相等
+            if x != HEAD && self.nodes[x].cmp(score, member) == Ordering::Equal {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// 满足 before 的成员个数，before 必须对排在前面的成员返回 true，对排在后面的返回 false
+    /// 用来查找分数或者字典序范围的边界
+    pub fn count_before(&self, before: impl Fn(f64, &[u8]) -> bool) -> usize {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if !before(node.score, &node.member) {
+                    break;
+                }
+                rank += self.span(x, i);
+                x = next;
+            }
+        }
+        rank
+    }
+
+    // 排名为 rank 的结点，从 0 开始
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if traversed + self.span(x, i) > target {
+                    break;
+                }
+                traversed += self.span(x, i);
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// 按排名遍历 ranks 范围内的成员，rev 为 true 时从后往前
+    pub fn range(&self, ranks: Range<usize>, rev: bool) -> Iter<'_> {
+        let ranks = ranks.start..ranks.end.min(self.len);
+        let node = match rev {
+            _ if ranks.is_empty() => None,
+            false => self.node_at(ranks.start),
+            true => self.node_at(ranks.end - 1),
+        };
+        Iter {
+            list: self,
+            node,
+            remaining: ranks.len(),
+            rev,
+        }
+    }
+}
+
+pub(crate) struct Iter<'a> {
+    list: &'a SkipList,
+    node: Option<usize>,
+    remaining: usize,
+    rev: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Bytes, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.list.nodes[self.node?];
+        self.node = match self.rev {
+            false => node.levels[0].forward,
+            true => node.backward,
+        };
+        self.remaining -= 1;
+        Some((&node.member, node.score))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(list: &SkipList, ranks: Range<usize>, rev: bool) -> Vec<(String, f64)> {
+        list.range(ranks, rev)
+            .map(|(member, score)| (String::from_utf8_lossy(member).into_owned(), score))
+            .collect()
+    }
+
+    #[test]
+    fn test_skiplist_order_and_rank() {
+        let mut list = SkipList::default();
+        // 倒序插入足够多的成员，让索引有多层
+        for i in (0..1000).rev() {
+            list.insert((i / 2) as f64, Bytes::from(format!("m{:04}", i)));
+        }
+        assert_eq!(list.len, 1000);
+
+        for i in (0..1000).step_by(7) {
+            let member = format!("m{:04}", i);
+            assert_eq!(list.rank((i / 2) as f64, member.as_bytes()), Some(i));
+            assert_eq!(
+                members(&list, i..i + 1, false),
+                vec![(member, (i / 2) as f64)]
+            );
+        }
+        assert_eq!(list.rank(1.0, b"m0000"), None);
+        assert_eq!(list.rank(0.0, b"m0001"), Some(1));
+        assert_eq!(list.rank(0.5, b"m0001"), None);
+        assert_eq!(list.count_before(|score, _| score < 10.0), 20);
+
+        assert_eq!(
+            members(&list, 997..2000, true),
+            vec![
+                ("m0999".to_string(), 499.0),
+                ("m0998".to_string(), 499.0),
+                ("m0997".to_string(), 498.0)
+            ]
+        );
+        assert!(members(&list, 5..5, false).is_empty());
+    }
+
+    #[test]
+    fn test_skiplist_remove() {
+        let mut list = SkipList::default();
+        for i in 0..200 {
+            list.insert(i as f64, Bytes::from(format!("m{:03}", i)));
+        }
+        assert!(!list.remove(1.0, b"m000"));
+        for i in (0..200).filter(|i| i % 3 != 0) {
+            assert!(list.remove(i as f64, format!("m{:03}", i).as_bytes()));
+        }
+        assert_eq!(list.len, 67);
+        assert_eq!(list.rank(99.0, b"m099"), Some(33));
+        assert_eq!(
+            members(&list, 65..67, false),
+            vec![("m195".to_string(), 195.0), ("m198".to_string(), 198.0)]
+        );
+
+        // 删除的结点会被复用
+        list.insert(1.5, "x".into());
+        assert_eq!(list.rank(1.5, b"x"), Some(1));
+        assert_eq!(list.nodes.len(), 201);
+        for i in (0..200).step_by(3) {
+            assert!(list.remove(i as f64, format!("m{:03}", i).as_bytes()));
+        }
+        assert_eq!(members(&list, 0..10, true), vec![("x".to_string(), 1.5)]);
+    }
+}
@@ -0,0 +1,485 @@
+use super::Backend;
+use crate::cmd::{parse_f64, parse_i64, CommandError};
+use crate::{Object, Value};
+use bytes::{Bytes, BytesMut};
+
+/// string 的最大长度，和 redis 的 proto-max-bulk-len 默认值一样是 512MB
+pub const MAX_STRING_SIZE: usize = 512 * 1024 * 1024;
+
+/// SET 命令的选项
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SetOptions {
+    /// 只在 key 不存在时设置
+    pub nx: bool,
+    /// 只在 key 存在时设置
+    pub xx: bool,
+    /// 返回旧的值，旧值不是 string 时报 WRONGTYPE
+    pub get: bool,
+    pub expire: SetExpire,
+}
+
+/// 设置新值之后的过期时间
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpire {
+    /// 清除原来的过期时间
+    #[default]
+    Clear,
+    /// 毫秒时间戳
+    At(u64),
+    /// 保留原来的过期时间
+    KeepTtl,
+}
+
+impl Backend {
+    /// 返回是否设置成功以及 key 原来的值，原来的值不是 string 时是 None
+    pub fn set_with(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        options: SetOptions,
+    ) -> Result<(bool, Option<Bytes>), CommandError> {
+        self.write_object(key, |slot| {
+            let old = match slot.as_ref().map(|object| &object.value) {
+                Some(Value::String(s)) => Some(s.clone()),
+                Some(_) if options.get => return Err(CommandError::WrongType),
+                _ => None,
+            };
+
+            let exists = slot.is_some();
+            if (options.nx && exists) || (options.xx && !exists) {
+                return Ok((false, old));
+            }
+
+            let expires_at = match options.expire {
+                SetExpire::Clear => None,
+                SetExpire::At(at) => Some(at),
+                SetExpire::KeepTtl => slot.as_ref().and_then(|object| object.expires_at),
+            };
+            *slot = Some(Object {
+                value: Value::String(value),
+                expires_at,
+            });
+
+            Ok((true, old))
+        })
+    }
+}
+
+impl Backend {
+    /// 在同一个时间点读取多个 key，不是 string 的 key 当作不存在
+    pub fn mget(&self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        self.atomically(keys, |keyspace| {
+            keys.iter()
+                .map(|key| {
+                    keyspace
+                        .read(key, |value| match value {
+                            Value::String(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .flatten()
+                })
+                .collect()
+        })
+    }
+
+    /// 一次设置多个 key，和 SET 一样覆盖任何类型的旧值并清除过期时间
+    pub fn mset(&self, pairs: Vec<(Bytes, Bytes)>) {
+        let keys = pairs.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+        self.atomically(keys, |keyspace| {
+            for (key, value) in pairs {
+                keyspace.insert(key, Object::new(Value::String(value)));
+            }
+        })
+    }
+
+    /// 只有所有 key 都不存在时才设置，返回是否设置成功
+    /// 检查和设置在同一个独占锁里完成，其他连接看不到只设置了一部分的状态
+    pub fn msetnx(&self, pairs: Vec<(Bytes, Bytes)>) -> bool {
+        let keys = pairs.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+        self.atomically(keys, |keyspace| {
+            if pairs.iter().any(|(key, _)| keyspace.exists(key)) {
+                return false;
+            }
+            for (key, value) in pairs {
+                keyspace.insert(key, Object::new(Value::String(value)));
+            }
+            true
+        })
+    }
+}
+
+impl Backend {
+    /// 把 key 的值当作整数加上 delta，key 不存在时当作 0，过期时间保持不变
+    pub fn incr_by(&self, key: Bytes, delta: i64) -> Result<i64, CommandError> {
+        self.write(key, |slot| {
+            let current = match slot {
+                None => 0,
+                Some(Value::String(s)) => parse_i64(s)?,
+                Some(_) => return Err(CommandError::WrongType),
+            };
+
+            let value = current.checked_add(delta).ok_or_else(|| {
+                CommandError::InvalidArgument("increment or decrement would overflow".to_string())
+            })?;
+            *slot = Some(Value::String(value.to_string().into()));
+            Ok(value)
+        })
+    }
+
+    /// 把 key 的值当作浮点数加上 delta，返回的是保存下来的字符串
+    pub fn incr_by_float(&self, key: Bytes, delta: f64) -> Result<Bytes, CommandError> {
+        self.write(key, |slot| {
+            let current = match slot {
+                None => 0.0,
+                Some(Value::String(s)) => parse_f64(s)?,
+                Some(_) => return Err(CommandError::WrongType),
+            };
+
+            let value = current + delta;
+            if !value.is_finite() {
+                return Err(CommandError::InvalidArgument(
+                    "increment would produce NaN or Infinity".to_string(),
+                ));
+            }
+
+            // 和 redis 一样不用指数形式，也没有多余的 0，比如 10.5、3000、-0.25
+            let value = Bytes::from(format!("{}", value));
+            *slot = Some(Value::String(value.clone()));
+            Ok(value)
+        })
+    }
+}
+
+impl Backend {
+    /// 追加到 key 的值后面，key 不存在时创建，返回追加之后的长度
+    pub fn append(&self, key: Bytes, value: Bytes) -> Result<usize, CommandError> {
+        self.write(key, |slot| {
+            if string_len(slot)? + value.len() > MAX_STRING_SIZE {
+                return Err(string_too_long());
+            }
+
+            let mut buf = take_string_buf(slot, 0)?;
+            buf.extend_from_slice(&value);
+            let len = buf.len();
+            *slot = Some(Value::String(buf.freeze()));
+            Ok(len)
+        })
+    }
+
+    pub fn strlen(&self, key: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.get(key)?.map_or(0, |s| s.len()))
+    }
+
+    /// start 和 end 都是闭区间，负数表示从末尾开始数
+    pub fn getrange(&self, key: &[u8], start: i64, end: i64) -> Result<Bytes, CommandError> {
+        let Some(value) = self.get(key)? else {
+            return Ok(Bytes::new());
+        };
+
+        let len = value.len() as i64;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let end = if end < 0 {
+            (len + end).max(0)
+        } else {
+            end.min(len - 1)
+        };
+        if len == 0 || start > end {
+            return Ok(Bytes::new());
+        }
+
+        // 和原来的值共享同一块内存
+        Ok(value.slice(start as usize..=end as usize))
+    }
+
+    /// 从 offset 开始覆盖，长度不够时用 0 填充，返回覆盖之后的长度
+    pub fn setrange(&self, key: Bytes, offset: usize, value: Bytes) -> Result<usize, CommandError> {
+        self.write(key, |slot| {
+            let current = string_len(slot)?;
+
+            // 空的 value 不会修改，也不会创建 key
+            if value.is_empty() {
+                return Ok(current);
+            }
+            if offset + value.len() > MAX_STRING_SIZE {
+                return Err(string_too_long());
+            }
+
+            let mut buf = take_string_buf(slot, offset + value.len())?;
+            buf[offset..offset + value.len()].copy_from_slice(&value);
+            let len = buf.len();
+            *slot = Some(Value::String(buf.freeze()));
+            Ok(len)
+        })
+    }
+
+    /// 删除 key 并返回原来的值
+    pub fn getdel(&self, key: Bytes) -> Result<Option<Bytes>, CommandError> {
+        self.write(key, |slot| match slot.take() {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(other) => {
+                *slot = Some(other);
+                Err(CommandError::WrongType)
+            }
+        })
+    }
+
+    /// 返回 key 的值，同时修改过期时间，KeepTtl 表示不修改，Clear 表示去掉过期时间
+    pub fn getex(&self, key: Bytes, expire: SetExpire) -> Result<Option<Bytes>, CommandError> {
+        self.write_object(key, |slot| {
+            let Some(object) = slot else {
+                return Ok(None);
+            };
+            let Value::String(value) = &object.value else {
+                return Err(CommandError::WrongType);
+            };
+            let value = value.clone();
+
+            match expire {
+                SetExpire::KeepTtl => {}
+                SetExpire::Clear => object.expires_at = None,
+                SetExpire::At(at) => object.expires_at = Some(at),
+            }
+            Ok(Some(value))
+        })
+    }
+}
+
+fn string_too_long() -> CommandError {
+    CommandError::InvalidArgument(
+        "string exceeds maximum allowed size (proto-max-bulk-len)".to_string(),
+    )
+}
+
+// 字符串的长度，key 不存在时是 0
+fn string_len(slot: &Option<Value>) -> Result<usize, CommandError> {
+    match slot {
+        None => Ok(0),
+        Some(Value::String(s)) => Ok(s.len()),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+// 拿出 key 的值用来原地修改，长度不够时用 0 补齐到 len 个字节，改完之后由调用方放回去
+// 值没有被其他地方引用时直接复用原来的内存，只有 GET 之类还拿着这个值的时候才会复制
+pub(super) fn take_string_buf(
+    slot: &mut Option<Value>,
+    len: usize,
+) -> Result<BytesMut, CommandError> {
+    let mut buf = match slot.take() {
+        None => BytesMut::new(),
+        Some(Value::String(s)) => s.try_into_mut().unwrap_or_else(|s| BytesMut::from(&s[..])),
+        Some(other) => {
+            *slot = Some(other);
+            return Err(CommandError::WrongType);
+        }
+    };
+    if buf.len() < len {
+        buf.resize(len, 0);
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::now_ms;
+
+    #[test]
+    fn test_set_with_options() {
+        let backend = Backend::new();
+        let nx = SetOptions {
+            nx: true,
+            get: true,
+            ..Default::default()
+        };
+
+        let ret = backend.set_with("key".into(), "a".into(), nx).unwrap();
+        assert_eq!(ret, (true, None));
+        let ret = backend.set_with("key".into(), "b".into(), nx).unwrap();
+        assert_eq!(ret, (false, Some("a".into())));
+
+        let at = now_ms() + 10_000;
+        let ex = SetOptions {
+            expire: SetExpire::At(at),
+            ..Default::default()
+        };
+        backend.set_with("key".into(), "c".into(), ex).unwrap();
+        assert_eq!(backend.expire_time(b"key"), Some(Some(at)));
+
+        let keep = SetOptions {
+            xx: true,
+            expire: SetExpire::KeepTtl,
+            ..Default::default()
+        };
+        backend.set_with("key".into(), "d".into(), keep).unwrap();
+        assert_eq!(backend.expire_time(b"key"), Some(Some(at)));
+        assert_eq!(backend.get(b"key").unwrap(), Some("d".into()));
+
+        backend
+            .set_with("key".into(), "e".into(), SetOptions::default())
+            .unwrap();
+        assert_eq!(backend.expire_time(b"key"), Some(None));
+
+        // GET 遇到其他类型的旧值时报错，并且不会覆盖
+        backend
+            .hset("hash".into(), "field".into(), "value".into())
+            .unwrap();
+        let get = SetOptions {
+            get: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            backend.set_with("hash".into(), "value".into(), get),
+            Err(CommandError::WrongType)
+        ));
+        assert_eq!(backend.key_type(b"hash"), "hash");
+    }
+
+    #[test]
+    fn test_incr_by() {
+        let backend = Backend::new();
+
+        assert_eq!(backend.incr_by("counter".into(), 5).unwrap(), 5);
+        assert_eq!(backend.incr_by("counter".into(), -7).unwrap(), -2);
+        assert_eq!(backend.get(b"counter").unwrap(), Some("-2".into()));
+
+        backend.set("max".into(), i64::MAX.to_string().into());
+        assert!(backend.incr_by("max".into(), 1).is_err());
+
+        backend.set("text".into(), "abc".into());
+        assert!(backend.incr_by("text".into(), 1).is_err());
+
+        backend.set("spaces".into(), " 1".into());
+        assert!(backend.incr_by("spaces".into(), 1).is_err());
+    }
+
+    #[test]
+    fn test_incr_by_float() {
+        let backend = Backend::new();
+
+        backend.set("key".into(), "10.50".into());
+        assert_eq!(backend.incr_by_float("key".into(), 0.1).unwrap(), "10.6");
+        assert_eq!(backend.incr_by_float("key".into(), -5.0).unwrap(), "5.6");
+
+        backend.set("key".into(), "5.0e3".into());
+        assert_eq!(backend.incr_by_float("key".into(), 2.0e2).unwrap(), "5200");
+
+        assert!(backend.incr_by_float("key".into(), f64::INFINITY).is_err());
+        assert_eq!(backend.get(b"key").unwrap(), Some("5200".into()));
+    }
+
+    #[test]
+    fn test_getrange() {
+        let backend = Backend::new();
+        backend.set("key".into(), "This is a string".into());
+
+        assert_eq!(backend.getrange(b"key", 0, 3).unwrap(), "This");
+        assert_eq!(backend.getrange(b"key", -3, -1).unwrap(), "ing");
+        assert_eq!(backend.getrange(b"key", 0, -1).unwrap(), "This is a string");
+        assert_eq!(backend.getrange(b"key", 10, 100).unwrap(), "string");
+        assert_eq!(backend.getrange(b"key", 5, 3).unwrap(), "");
+        assert_eq!(backend.getrange(b"key", -100, 1).unwrap(), "Th");
+        assert_eq!(backend.getrange(b"missing", 0, -1).unwrap(), "");
+    }
+
+    #[test]
+    fn test_setrange_and_append() {
+        let backend = Backend::new();
+
+        assert_eq!(backend.setrange("key".into(), 0, "".into()).unwrap(), 0);
+        assert_eq!(backend.key_type(b"key"), "none");
+
+        assert_eq!(backend.setrange("key".into(), 3, "abc".into()).unwrap(), 6);
+        assert_eq!(backend.get(b"key").unwrap(), Some("\0\0\0abc".into()));
+
+        assert_eq!(backend.setrange("key".into(), 1, "XY".into()).unwrap(), 6);
+        assert_eq!(backend.get(b"key").unwrap(), Some("\0XYabc".into()));
+
+        assert_eq!(backend.append("key".into(), "de".into()).unwrap(), 8);
+        assert_eq!(backend.strlen(b"key").unwrap(), 8);
+        assert!(backend
+            .setrange("key".into(), MAX_STRING_SIZE, "a".into())
+            .is_err());
+    }
+
+    #[test]
+    fn test_write_in_place() {
+        let backend = Backend::new();
+        let ptr = |backend: &Backend| {
+            backend
+                .read(b"key", |value| match value {
+                    Value::String(s) => s.as_ptr(),
+                    _ => unreachable!(),
+                })
+                .unwrap()
+        };
+
+        // 第一次写入时复制一份，之后没有其他引用的时候都在同一块内存上修改
+        backend.append("key".into(), "a".repeat(64).into()).unwrap();
+        let first = ptr(&backend);
+        backend.setrange("key".into(), 1, "XY".into()).unwrap();
+        backend.setbit("key".into(), 0, true).unwrap();
+        assert_eq!(ptr(&backend), first);
+
+        // GET 拿到的值还在用时先复制，不会影响已经返回的值
+        let old = backend.get(b"key").unwrap().unwrap();
+        backend.setrange("key".into(), 1, "ZZ".into()).unwrap();
+        assert_eq!(&old[1..3], b"XY");
+        assert_ne!(ptr(&backend), first);
+        assert_eq!(&backend.getrange(b"key", 1, 2).unwrap()[..], b"ZZ");
+    }
+
+    #[test]
+    fn test_msetnx_is_atomic() {
+        let backend = Backend::new();
+        let keys: Vec<Bytes> = vec!["a".into(), "b".into(), "c".into()];
+
+        // 多个线程同时对同一组 key 执行 MSETNX，只能有一个成功，而且不会混合不同线程的值
+        let handles = (0..8)
+            .map(|i| {
+                let backend = backend.clone();
+                let pairs = keys
+                    .iter()
+                    .map(|key| (key.clone(), Bytes::from(i.to_string())))
+                    .collect::<Vec<_>>();
+                std::thread::spawn(move || backend.msetnx(pairs))
+            })
+            .collect::<Vec<_>>();
+        let succeeded = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+        assert_eq!(succeeded, 1);
+
+        let values = backend.mget(&keys);
+        assert!(values[0].is_some());
+        assert!(values.iter().all(|value| value == &values[0]));
+
+        // MSET 的同时读取，MGET 看到的总是同一次 MSET 写入的值
+        let writer = {
+            let backend = backend.clone();
+            let keys = keys.clone();
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    let value = Bytes::from(i.to_string());
+                    backend.mset(
+                        keys.iter()
+                            .map(|key| (key.clone(), value.clone()))
+                            .collect(),
+                    );
+                }
+            })
+        };
+        for _ in 0..1000 {
+            let values = backend.mget(&keys);
+            assert!(values.iter().all(|value| value == &values[0]));
+        }
+        writer.join().unwrap();
+    }
+}
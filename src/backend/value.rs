@@ -0,0 +1,273 @@
+use super::skiplist::SkipList;
+use bytes::Bytes;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+/// keyspace 中的一项，值加上可选的过期时间
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub value: Value,
+    /// 过期时间，毫秒时间戳
+    pub expires_at: Option<u64>,
+}
+
+impl Object {
+    pub fn new(value: Value) -> Self {
+        Self {
+            value,
+            expires_at: None,
+        }
+    }
+
+    /// 有没有设置过期时间，包括 hash 的 field 的过期时间，active expire 只检查这些 key
+    pub fn has_expiry(&self) -> bool {
+        self.expires_at.is_some()
+            || matches!(&self.value, Value::Hash(hash) if hash.next_expiry().is_some())
+    }
+
+    /// key 过期，或者 hash 的所有 field 都过期了
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+            || matches!(&self.value, Value::Hash(hash) if hash.all_expired(now))
+    }
+}
+
+/// keyspace 中保存的值，一个 key 只能有一种类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Bytes),
+    Hash(Hash),
+    List(VecDeque<Bytes>),
+    Set(HashSet<Bytes>),
+    ZSet(SortedSet),
+}
+
+impl Value {
+    /// TYPE 命令的回复
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Hash(_) => "hash",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+        }
+    }
+
+    // 和 redis 一样，集合类型的最后一个元素被删除之后，key 也要一起删除
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Value::String(_) => false,
+            Value::Hash(hash) => hash.is_empty(),
+            Value::List(list) => list.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::ZSet(zset) => zset.is_empty(),
+        }
+    }
+}
+
+/// hash 类型的值，每个 field 可以有自己的过期时间
+/// 这里不检查 field 是否过期，Backend 在访问之前会先删掉过期的 field
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hash {
+    fields: HashMap<Bytes, Bytes>,
+    /// field 的过期时间，毫秒时间戳，只有设置了过期时间的 field 才在这里
+    expires: HashMap<Bytes, u64>,
+    /// 和 expires 一样，但是按过期时间排序，不用遍历就能知道有没有 field 过期
+    deadlines: BTreeSet<(u64, Bytes)>,
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&Bytes> {
+        self.fields.get(field)
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.fields.contains_key(field)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        self.fields.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Bytes> {
+        self.fields.values()
+    }
+
+    /// 设置 field 的值，和 redis 的 HSET 一样会去掉原来的过期时间
+    pub fn insert(&mut self, field: Bytes, value: Bytes) -> Option<Bytes> {
+        self.clear_expire(&field);
+        self.fields.insert(field, value)
+    }
+
+    /// 修改已有 field 的值但是保留过期时间，用于 HINCRBY 这样的命令
+    pub fn insert_keep_ttl(&mut self, field: Bytes, value: Bytes) -> Option<Bytes> {
+        self.fields.insert(field, value)
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<Bytes> {
+        self.clear_expire(field);
+        self.fields.remove(field)
+    }
+
+    fn clear_expire(&mut self, field: &[u8]) {
+        if let Some((field, at)) = self.expires.remove_entry(field) {
+            self.deadlines.remove(&(at, field));
+        }
+    }
+
+    /// field 的过期时间，field 不存在或者没有过期时间时返回 None
+    pub fn expire_time(&self, field: &[u8]) -> Option<u64> {
+        self.expires.get(field).copied()
+    }
+
+    /// 设置或者去掉 field 的过期时间，field 不存在时什么都不做
+    pub fn set_expire(&mut self, field: &[u8], at: Option<u64>) {
+        let Some((field, _)) = self.fields.get_key_value(field) else {
+            return;
+        };
+        let field = field.clone();
+        self.clear_expire(&field);
+        if let Some(at) = at {
+            self.deadlines.insert((at, field.clone()));
+            self.expires.insert(field, at);
+        }
+    }
+
+    /// 最早过期的 field 的过期时间，没有 field 设置过期时间时返回 None
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.deadlines.first().map(|(at, _)| *at)
+    }
+
+    pub fn has_expired(&self, now: u64) -> bool {
+        self.next_expiry().is_some_and(|at| at <= now)
+    }
+
+    /// 删除所有过期的 field，返回删除的个数
+    pub fn remove_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while self.has_expired(now) {
+            if let Some((_, field)) = self.deadlines.pop_first() {
+                self.expires.remove(&field);
+                self.fields.remove(&field);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    // 所有 field 都过期时整个 key 当作过期
+    fn all_expired(&self, now: u64) -> bool {
+        !self.fields.is_empty()
+            && self.expires.len() == self.fields.len()
+            && self.deadlines.last().is_some_and(|(at, _)| *at <= now)
+    }
+}
+
+impl FromIterator<(Bytes, Bytes)> for Hash {
+    fn from_iter<T: IntoIterator<Item = (Bytes, Bytes)>>(iter: T) -> Self {
+        Self {
+            fields: iter.into_iter().collect(),
+            expires: HashMap::new(),
+            deadlines: BTreeSet::new(),
+        }
+    }
+}
+
+/// 有序集合的值，HashMap 用来按成员查找分数，SkipList 按照 (score, member) 排序
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    scores: HashMap<Bytes, f64>,
+    index: SkipList,
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// 加入成员或者修改已有成员的分数，返回原来的分数
+    pub fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        let old = self.scores.insert(member.clone(), score);
+        if let Some(old) = old {
+            if old == score {
+                return Some(old);
+            }
+            self.index.remove(old, &member);
+        }
+        self.index.insert(score, member);
+        old
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+        self.index.remove(score, member);
+        Some(score)
+    }
+
+    /// 成员按分数从小到大的排名，从 0 开始
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        self.index.rank(score, member)
+    }
+
+    /// 满足 before 的成员个数，也就是第一个不满足 before 的成员的排名
+    /// before 必须对排在前面的成员返回 true，对排在后面的成员返回 false
+    pub fn count_before(&self, before: impl Fn(f64, &[u8]) -> bool) -> usize {
+        self.index.count_before(before)
+    }
+
+    /// 按排名遍历，rev 为 true 时从 ranks 的末尾往前
+    pub fn range(&self, ranks: Range<usize>, rev: bool) -> impl Iterator<Item = (&Bytes, f64)> {
+        self.index.range(ranks, rev)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, f64)> {
+        self.index.range(0..self.len(), false)
+    }
+}
+
+// 索引的结构是随机的，只比较成员和分数
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.scores == other.scores
+    }
+}
+
+impl FromIterator<(Bytes, f64)> for SortedSet {
+    fn from_iter<T: IntoIterator<Item = (Bytes, f64)>>(iter: T) -> Self {
+        let mut zset = Self::new();
+        for (member, score) in iter {
+            zset.insert(member, score);
+        }
+        zset
+    }
+}
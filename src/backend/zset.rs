@@ -0,0 +1,839 @@
+use super::list::list_range;
+use super::{Backend, Keyspace};
+use crate::cmd::CommandError;
+use crate::{Object, SetOperation, SortedSet, Value};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+
+/// ZADD 的选项
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ZAddOptions {
+    /// 只加入新成员，不修改已有成员
+    pub nx: bool,
+    /// 只修改已有成员，不加入新成员
+    pub xx: bool,
+    /// 只在新分数更大时修改
+    pub gt: bool,
+    /// 只在新分数更小时修改
+    pub lt: bool,
+    /// 返回加入和修改的成员个数，而不只是加入的个数
+    pub ch: bool,
+}
+
+/// BYSCORE 的一端，exclusive 对应 `(1.5` 这样的开区间
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreBound {
+    pub value: f64,
+    pub exclusive: bool,
+}
+
+/// BYLEX 的一端，对应 `-`、`+`、`[a` 和 `(a`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    NegInf,
+    PosInf,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+/// ZRANGE 按什么取范围，BYSCORE 和 BYLEX 总是保存成 min 在前，和 REV 无关
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+/// ZRANGE 和 ZRANGESTORE 的参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRangeSpec {
+    pub by: ZRangeBy,
+    pub rev: bool,
+    /// LIMIT offset count，count 为负数表示不限制
+    pub limit: Option<(i64, i64)>,
+}
+
+/// ZMPOP 这类命令弹出的 key 和成员
+pub type ZPopped = (Bytes, Vec<(Bytes, f64)>);
+
+/// 从分数最小还是最大的一端弹出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZEnd {
+    Min,
+    Max,
+}
+
+/// ZUNIONSTORE 和 ZINTERSTORE 合并同一个成员的分数的方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl ScoreBound {
+    // 分数排在区间的下界前面
+    fn below(&self, score: f64) -> bool {
+        score < self.value || (self.exclusive && score == self.value)
+    }
+
+    // 分数没有超过区间的上界
+    fn within(&self, score: f64) -> bool {
+        score < self.value || (!self.exclusive && score == self.value)
+    }
+}
+
+impl LexBound {
+    fn below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(bound) => member < &bound[..],
+            LexBound::Exclusive(bound) => member <= &bound[..],
+        }
+    }
+
+    fn within(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(bound) => member <= &bound[..],
+            LexBound::Exclusive(bound) => member < &bound[..],
+        }
+    }
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // 和 redis 一样，inf 加 -inf 的结果当作 0
+            Aggregate::Sum => zero_if_nan(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+impl Backend {
+    // 只读访问一个有序集合，key 不存在时返回 None
+    fn read_zset<T>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&SortedSet) -> T,
+    ) -> Result<Option<T>, CommandError> {
+        self.read(key, |value| match value {
+            Value::ZSet(zset) => Ok(f(zset)),
+            _ => Err(CommandError::WrongType),
+        })
+        .transpose()
+    }
+
+    // 修改一个有序集合，参考 write_zset_in
+    fn write_zset<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut SortedSet) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        self.with_shard(self.shard(&key), |ks| write_zset_in(ks, key, f))
+    }
+
+    // 和 write_zset 一样，但是在同一个锁里把新数据交给阻塞在 key 上的客户端
+    fn add_zset<T>(
+        &self,
+        key: Bytes,
+        f: impl FnOnce(&mut SortedSet) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        let wakes = [key];
+        self.write_and_wake(&wakes, &wakes, |ks| write_zset_in(ks, wakes[0].clone(), f))
+    }
+
+    /// 返回新加入的成员个数，CH 时还包括分数被修改的成员
+    pub fn zadd(
+        &self,
+        key: Bytes,
+        options: &ZAddOptions,
+        members: Vec<(f64, Bytes)>,
+    ) -> Result<usize, CommandError> {
+        self.add_zset(key, |zset| {
+            let (mut added, mut changed) = (0, 0);
+            for (score, member) in members {
+                match zset.score(&member) {
+                    Some(old) => {
+                        if options.nx
+                            || (options.gt && score <= old)
+                            || (options.lt && score >= old)
+                            || score == old
+                        {
+                            continue;
+                        }
+                        zset.insert(member, score);
+                        changed += 1;
+                    }
+                    None if options.xx => {}
+                    None => {
+                        zset.insert(member, score);
+                        added += 1;
+                    }
+                }
+            }
+            Ok(if options.ch { added + changed } else { added })
+        })
+    }
+
+    /// ZINCRBY 和 ZADD INCR，返回新的分数，被 NX、XX、GT、LT 阻止时返回 None
+    pub fn zincrby(
+        &self,
+        key: Bytes,
+        options: &ZAddOptions,
+        increment: f64,
+        member: Bytes,
+    ) -> Result<Option<f64>, CommandError> {
+        self.add_zset(key, |zset| {
+            let old = zset.score(&member);
+            if (options.nx && old.is_some()) || (options.xx && old.is_none()) {
+                return Ok(None);
+            }
+            let score = old.unwrap_or(0.0) + increment;
+            if score.is_nan() {
+                return Err(CommandError::InvalidArgument(
+                    "resulting score is not a number (NaN)".to_string(),
+                ));
+            }
+            if let Some(old) = old {
+                if (options.gt && score <= old) || (options.lt && score >= old) {
+                    return Ok(None);
+                }
+            }
+            zset.insert(member, score);
+            Ok(Some(score))
+        })
+    }
+
+    /// 返回删除的成员个数
+    pub fn zrem(&self, key: Bytes, members: &[Bytes]) -> Result<usize, CommandError> {
+        self.write_zset(key, |zset| {
+            Ok(members
+                .iter()
+                .filter(|member| zset.remove(member).is_some())
+                .count())
+        })
+    }
+
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>, CommandError> {
+        Ok(self.read_zset(key, |zset| zset.score(member))?.flatten())
+    }
+
+    pub fn zmscore(&self, key: &[u8], members: &[Bytes]) -> Result<Vec<Option<f64>>, CommandError> {
+        let scores = self.read_zset(key, |zset| {
+            members.iter().map(|member| zset.score(member)).collect()
+        })?;
+        Ok(scores.unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    pub fn zcard(&self, key: &[u8]) -> Result<usize, CommandError> {
+        Ok(self.read_zset(key, SortedSet::len)?.unwrap_or(0))
+    }
+
+    /// 分数在 min 和 max 之间的成员个数
+    pub fn zcount(
+        &self,
+        key: &[u8],
+        min: &ScoreBound,
+        max: &ScoreBound,
+    ) -> Result<usize, CommandError> {
+        let by = ZRangeBy::Score(*min, *max);
+        Ok(self
+            .read_zset(key, |zset| range_ranks(zset, &by, false).len())?
+            .unwrap_or(0))
+    }
+
+    /// 成员的排名和分数，rev 为 true 时按分数从大到小排名
+    pub fn zrank(
+        &self,
+        key: &[u8],
+        member: &[u8],
+        rev: bool,
+    ) -> Result<Option<(usize, f64)>, CommandError> {
+        let rank = self.read_zset(key, |zset| {
+            let rank = zset.rank(member)?;
+            let rank = if rev { zset.len() - 1 - rank } else { rank };
+            Some((rank, zset.score(member)?))
+        })?;
+        Ok(rank.flatten())
+    }
+
+    /// 按照 spec 取出成员和分数，顺序就是回复的顺序
+    pub fn zrange(&self, key: &[u8], spec: &ZRangeSpec) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        Ok(self
+            .read_zset(key, |zset| zset_range(zset, spec))?
+            .unwrap_or_default())
+    }
+
+    /// 把 source 的一个范围保存到 destination，覆盖原来的值，返回保存的成员个数
+    pub fn zrangestore(
+        &self,
+        destination: Bytes,
+        source: &[u8],
+        spec: &ZRangeSpec,
+    ) -> Result<usize, CommandError> {
+        let wakes = [destination];
+        self.write_and_wake([&wakes[0][..], source], &wakes, |ks| {
+            let members = ks
+                .read(source, |value| match value {
+                    Value::ZSet(zset) => Ok(zset_range(zset, spec)),
+                    _ => Err(CommandError::WrongType),
+                })
+                .transpose()?
+                .unwrap_or_default();
+            Ok(store_zset_in(
+                ks,
+                wakes[0].clone(),
+                members.into_iter().collect(),
+            ))
+        })
+    }
+
+    /// 弹出分数最小或者最大的 count 个成员，按弹出的顺序返回
+    pub fn zpop(
+        &self,
+        key: Bytes,
+        end: ZEnd,
+        count: usize,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.write_zset(key, |zset| Ok(pop_zset(zset, end, count)))
+    }
+
+    /// ZUNIONSTORE、ZINTERSTORE，分数先乘以对应的权重再合并，返回结果的成员个数
+    /// 和 redis 一样，普通的集合也可以参与计算，成员的分数当作 1
+    pub fn zopstore(
+        &self,
+        op: SetOperation,
+        destination: Bytes,
+        keys: &[Bytes],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<usize, CommandError> {
+        let wakes = [destination];
+        self.write_and_wake(keys.iter().chain(&wakes), &wakes, |ks| {
+            let sets = keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    let weight = weights.get(i).copied().unwrap_or(1.0);
+                    Ok(read_weighted_in(ks, key, weight)?.unwrap_or_default())
+                })
+                .collect::<Result<Vec<_>, CommandError>>()?;
+            let result = zset_operation(sets, op, aggregate);
+            Ok(store_zset_in(ks, wakes[0].clone(), result))
+        })
+    }
+
+    /// 从第一个非空的有序集合弹出最多 count 个成员，返回 key 和弹出的成员，所有集合都是空的时返回 None
+    pub fn zmpop(
+        &self,
+        keys: &[Bytes],
+        end: ZEnd,
+        count: usize,
+    ) -> Result<Option<ZPopped>, CommandError> {
+        self.try_serve(keys, |ks, key| zpop_in(ks, key, end, count))
+    }
+
+    /// BZPOPMIN、BZPOPMAX、BZMPOP：和 zmpop 一样，但是所有集合都是空的时等待其他客户端写入，
+    /// 直到 until 完成，也就是超时或者客户端断开连接，这时候返回 None
+    pub async fn bzpop(
+        &self,
+        keys: Vec<Bytes>,
+        end: ZEnd,
+        count: usize,
+        until: impl Future<Output = ()>,
+    ) -> Result<Option<ZPopped>, CommandError> {
+        self.block_on(keys, vec![], until, move |ks, key| {
+            zpop_in(ks, key, end, count)
+        })
+        .await
+    }
+}
+
+// 从有序集合的一端弹出最多 count 个成员
+fn pop_zset(zset: &mut SortedSet, end: ZEnd, count: usize) -> Vec<(Bytes, f64)> {
+    let popped = match end {
+        ZEnd::Min => zset.range(0..count, false),
+        ZEnd::Max => zset.range(zset.len().saturating_sub(count)..zset.len(), true),
+    }
+    .map(|(member, score)| (member.clone(), score))
+    .collect::<Vec<_>>();
+    for (member, _) in &popped {
+        zset.remove(member);
+    }
+    popped
+}
+
+// 在 keyspace 里弹出成员，key 不存在时返回 None
+fn zpop_in(
+    ks: Keyspace<'_>,
+    key: &Bytes,
+    end: ZEnd,
+    count: usize,
+) -> Result<Option<ZPopped>, CommandError> {
+    let popped = ks.write(key.clone(), |slot| match slot {
+        None => Ok(None),
+        Some(Value::ZSet(zset)) => Ok(Some(pop_zset(zset, end, count))),
+        Some(_) => Err(CommandError::WrongType),
+    })?;
+    Ok(popped.map(|popped| (key.clone(), popped)))
+}
+
+// 按 spec 的顺序取出成员，LIMIT 的 offset 和 count 也按这个顺序计算
+fn zset_range(zset: &SortedSet, spec: &ZRangeSpec) -> Vec<(Bytes, f64)> {
+    let mut ranks = range_ranks(zset, &spec.by, spec.rev);
+    if let Some((offset, count)) = spec.limit {
+        if offset < 0 {
+            return Vec::new();
+        }
+        let offset = (offset as usize).min(ranks.len());
+        let count = usize::try_from(count).unwrap_or(usize::MAX);
+        let count = count.min(ranks.len() - offset);
+        ranks = match spec.rev {
+            false => ranks.start + offset..ranks.start + offset + count,
+            true => ranks.end - offset - count..ranks.end - offset,
+        };
+    }
+    zset.range(ranks, spec.rev)
+        .map(|(member, score)| (member.clone(), score))
+        .collect()
+}
+
+// 范围内的成员按分数从小到大的排名，rev 只影响按排名取范围时下标的含义
+fn range_ranks(zset: &SortedSet, by: &ZRangeBy, rev: bool) -> Range<usize> {
+    let len = zset.len();
+    let (start, end) = match by {
+        ZRangeBy::Rank(start, stop) => match list_range(len, *start, *stop) {
+            Some((start, stop)) if rev => (len - 1 - stop, len - start),
+            Some((start, stop)) => (start, stop + 1),
+            None => (0, 0),
+        },
+        ZRangeBy::Score(min, max) => (
+            zset.count_before(|score, _| min.below(score)),
+            zset.count_before(|score, _| max.within(score)),
+        ),
+        ZRangeBy::Lex(min, max) => (
+            zset.count_before(|_, member| min.below(member)),
+            zset.count_before(|_, member| max.within(member)),
+        ),
+    };
+    start..end.max(start)
+}
+
+// 读出一个有序集合或者集合，分数乘以 weight
+fn read_weighted_in(
+    ks: Keyspace<'_>,
+    key: &[u8],
+    weight: f64,
+) -> Result<Option<HashMap<Bytes, f64>>, CommandError> {
+    let weighted = |score: f64| zero_if_nan(score * weight);
+    ks.read(key, |value| match value {
+        Value::ZSet(zset) => Ok(zset
+            .iter()
+            .map(|(member, score)| (member.clone(), weighted(score)))
+            .collect()),
+        Value::Set(set) => Ok(set
+            .iter()
+            .map(|member| (member.clone(), weighted(1.0)))
+            .collect()),
+        _ => Err(CommandError::WrongType),
+    })
+    .transpose()
+}
+
+fn zset_operation(
+    mut sets: Vec<HashMap<Bytes, f64>>,
+    op: SetOperation,
+    aggregate: Aggregate,
+) -> HashMap<Bytes, f64> {
+    if sets.is_empty() {
+        return HashMap::new();
+    }
+    let mut result = sets.remove(0);
+    match op {
+        SetOperation::Union => {
+            for set in sets {
+                for (member, score) in set {
+                    result
+                        .entry(member)
+                        .and_modify(|old| *old = aggregate.apply(*old, score))
+                        .or_insert(score);
+                }
+            }
+        }
+        SetOperation::Inter => {
+            result.retain(|member, score| {
+                sets.iter().all(|set| match set.get(member) {
+                    Some(&other) => {
+                        *score = aggregate.apply(*score, other);
+                        true
+                    }
+                    None => false,
+                })
+            });
+        }
+        SetOperation::Diff => {
+            result.retain(|member, _| sets.iter().all(|set| !set.contains_key(member)));
+        }
+    }
+    result
+}
+
+// 在 keyspace 里修改一个有序集合，key 不存在时从空集合开始，集合被清空时 key 也会被删除
+fn write_zset_in<T>(
+    ks: Keyspace<'_>,
+    key: Bytes,
+    f: impl FnOnce(&mut SortedSet) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    ks.write(key, |slot| {
+        match slot.get_or_insert_with(|| Value::ZSet(SortedSet::new())) {
+            Value::ZSet(zset) => f(zset),
+            _ => Err(CommandError::WrongType),
+        }
+    })
+}
+
+// 把结果保存到 destination，结果为空时删除 destination，返回成员个数
+fn store_zset_in(ks: Keyspace<'_>, destination: Bytes, members: HashMap<Bytes, f64>) -> usize {
+    let len = members.len();
+    if members.is_empty() {
+        ks.write(destination, |slot| *slot = None);
+    } else {
+        let zset = members.into_iter().collect::<SortedSet>();
+        ks.insert(destination, Object::new(Value::ZSet(zset)));
+    }
+    len
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(items: &[(f64, &'static str)]) -> Vec<(f64, Bytes)> {
+        items
+            .iter()
+            .map(|(score, member)| (*score, Bytes::from(*member)))
+            .collect()
+    }
+
+    fn members(items: Vec<(Bytes, f64)>) -> Vec<(String, f64)> {
+        items
+            .into_iter()
+            .map(|(member, score)| (String::from_utf8_lossy(&member).into_owned(), score))
+            .collect()
+    }
+
+    fn spec(by: ZRangeBy, rev: bool, limit: Option<(i64, i64)>) -> ZRangeSpec {
+        ZRangeSpec { by, rev, limit }
+    }
+
+    fn bound(value: f64, exclusive: bool) -> ScoreBound {
+        ScoreBound { value, exclusive }
+    }
+
+    #[test]
+    fn test_zadd_options() {
+        let backend = Backend::new();
+        let add = |options: ZAddOptions, items: &[(f64, &'static str)]| {
+            backend.zadd("z".into(), &options, scored(items)).unwrap()
+        };
+
+        assert_eq!(add(ZAddOptions::default(), &[(1.0, "a"), (2.0, "b")]), 2);
+        assert_eq!(add(ZAddOptions::default(), &[(3.0, "a")]), 0);
+        let ch = ZAddOptions {
+            ch: true,
+            ..Default::default()
+        };
+        assert_eq!(add(ch, &[(3.0, "a"), (4.0, "b"), (5.0, "c")]), 2);
+
+        let xx_gt = ZAddOptions {
+            xx: true,
+            gt: true,
+            ch: true,
+            ..Default::default()
+        };
+        assert_eq!(add(xx_gt, &[(1.0, "a"), (10.0, "b"), (1.0, "d")]), 1);
+        assert_eq!(backend.zscore(b"z", b"a").unwrap(), Some(3.0));
+        assert_eq!(backend.zscore(b"z", b"b").unwrap(), Some(10.0));
+        assert_eq!(backend.zscore(b"z", b"d").unwrap(), None);
+
+        let nx = ZAddOptions {
+            nx: true,
+            ..Default::default()
+        };
+        assert_eq!(add(nx, &[(0.0, "a"), (0.0, "d")]), 1);
+        assert_eq!(
+            backend
+                .zmscore(b"z", &["a".into(), "d".into(), "x".into()])
+                .unwrap(),
+            vec![Some(3.0), Some(0.0), None]
+        );
+
+        assert_eq!(
+            backend
+                .zincrby("z".into(), &ZAddOptions::default(), 1.5, "a".into())
+                .unwrap(),
+            Some(4.5)
+        );
+        let lt = ZAddOptions {
+            lt: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            backend.zincrby("z".into(), &lt, 1.0, "a".into()).unwrap(),
+            None
+        );
+        backend
+            .zadd(
+                "z".into(),
+                &ZAddOptions::default(),
+                scored(&[(f64::INFINITY, "i")]),
+            )
+            .unwrap();
+        assert!(backend
+            .zincrby(
+                "z".into(),
+                &ZAddOptions::default(),
+                f64::NEG_INFINITY,
+                "i".into()
+            )
+            .is_err());
+
+        assert_eq!(
+            backend.zrem("z".into(), &["a".into(), "x".into()]).unwrap(),
+            1
+        );
+        assert_eq!(backend.zcard(b"z").unwrap(), 4);
+        backend.set("str".into(), "x".into());
+        assert!(matches!(
+            backend.zcard(b"str"),
+            Err(CommandError::WrongType)
+        ));
+    }
+
+    #[test]
+    fn test_zrange() {
+        let backend = Backend::new();
+        backend
+            .zadd(
+                "z".into(),
+                &ZAddOptions::default(),
+                scored(&[(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d"), (4.0, "e")]),
+            )
+            .unwrap();
+        let range = |spec: ZRangeSpec| members(backend.zrange(b"z", &spec).unwrap());
+
+        assert_eq!(
+            range(spec(ZRangeBy::Rank(1, 2), false, None)),
+            vec![("b".to_string(), 2.0), ("c".to_string(), 2.0)]
+        );
+        assert_eq!(
+            range(spec(ZRangeBy::Rank(0, 1), true, None)),
+            vec![("e".to_string(), 4.0), ("d".to_string(), 3.0)]
+        );
+        assert_eq!(range(spec(ZRangeBy::Rank(-1, -1), false, None)).len(), 1);
+        assert!(range(spec(ZRangeBy::Rank(3, 1), false, None)).is_empty());
+
+        let by_score = ZRangeBy::Score(bound(2.0, true), bound(4.0, false));
+        assert_eq!(
+            range(spec(by_score.clone(), false, None)),
+            vec![("d".to_string(), 3.0), ("e".to_string(), 4.0)]
+        );
+        let by_score = ZRangeBy::Score(bound(f64::NEG_INFINITY, false), bound(3.0, false));
+        assert_eq!(
+            range(spec(by_score.clone(), true, Some((1, 2)))),
+            vec![("c".to_string(), 2.0), ("b".to_string(), 2.0)]
+        );
+        assert_eq!(
+            range(spec(by_score.clone(), false, Some((3, -1)))),
+            vec![("d".to_string(), 3.0)]
+        );
+        assert!(range(spec(by_score, false, Some((-1, 1)))).is_empty());
+        assert_eq!(
+            backend
+                .zcount(b"z", &bound(2.0, false), &bound(3.0, true))
+                .unwrap(),
+            2
+        );
+
+        let by_lex = ZRangeBy::Lex(
+            LexBound::Inclusive("b".into()),
+            LexBound::Exclusive("d".into()),
+        );
+        assert_eq!(
+            range(spec(by_lex, false, None)),
+            vec![("b".to_string(), 2.0), ("c".to_string(), 2.0)]
+        );
+        let by_lex = ZRangeBy::Lex(LexBound::NegInf, LexBound::PosInf);
+        assert_eq!(range(spec(by_lex, false, None)).len(), 5);
+
+        assert_eq!(backend.zrank(b"z", b"c", false).unwrap(), Some((2, 2.0)));
+        assert_eq!(backend.zrank(b"z", b"c", true).unwrap(), Some((2, 2.0)));
+        assert_eq!(backend.zrank(b"z", b"e", true).unwrap(), Some((0, 4.0)));
+        assert_eq!(backend.zrank(b"z", b"x", true).unwrap(), None);
+
+        let stored = backend
+            .zrangestore("dst".into(), b"z", &spec(ZRangeBy::Rank(0, 1), false, None))
+            .unwrap();
+        assert_eq!(stored, 2);
+        assert_eq!(backend.zcard(b"dst").unwrap(), 2);
+        backend
+            .zrangestore(
+                "dst".into(),
+                b"z",
+                &spec(ZRangeBy::Rank(10, 11), false, None),
+            )
+            .unwrap();
+        assert_eq!(backend.key_type(b"dst"), "none");
+
+        assert_eq!(
+            members(backend.zpop("z".into(), ZEnd::Max, 2).unwrap()),
+            vec![("e".to_string(), 4.0), ("d".to_string(), 3.0)]
+        );
+        assert_eq!(
+            members(backend.zpop("z".into(), ZEnd::Min, 10).unwrap()).len(),
+            3
+        );
+        assert_eq!(backend.key_type(b"z"), "none");
+    }
+
+    #[test]
+    fn test_zopstore() {
+        let backend = Backend::new();
+        backend
+            .zadd(
+                "a".into(),
+                &ZAddOptions::default(),
+                scored(&[(1.0, "x"), (2.0, "y")]),
+            )
+            .unwrap();
+        backend
+            .zadd(
+                "b".into(),
+                &ZAddOptions::default(),
+                scored(&[(10.0, "y"), (20.0, "z")]),
+            )
+            .unwrap();
+        backend.sadd("s".into(), vec!["y".into()]).unwrap();
+        let keys = ["a".into(), "b".into()];
+
+        let len = backend
+            .zopstore(
+                SetOperation::Union,
+                "u".into(),
+                &keys,
+                &[2.0, 1.0],
+                Aggregate::Sum,
+            )
+            .unwrap();
+        assert_eq!(len, 3);
+        assert_eq!(
+            members(
+                backend
+                    .zrange(b"u", &spec(ZRangeBy::Rank(0, -1), false, None))
+                    .unwrap()
+            ),
+            vec![
+                ("x".to_string(), 2.0),
+                ("y".to_string(), 14.0),
+                ("z".to_string(), 20.0)
+            ]
+        );
+
+        let keys = ["a".into(), "b".into(), "s".into()];
+        let len = backend
+            .zopstore(SetOperation::Inter, "i".into(), &keys, &[], Aggregate::Max)
+            .unwrap();
+        assert_eq!(len, 1);
+        assert_eq!(backend.zscore(b"i", b"y").unwrap(), Some(10.0));
+
+        // 结果为空时删除 destination
+        let len = backend
+            .zopstore(
+                SetOperation::Inter,
+                "i".into(),
+                &["a".into(), "none".into()],
+                &[],
+                Aggregate::Sum,
+            )
+            .unwrap();
+        assert_eq!(len, 0);
+        assert_eq!(backend.key_type(b"i"), "none");
+    }
+
+    #[tokio::test]
+    async fn test_bzpop_wrong_type() {
+        let backend = Backend::new();
+        backend.set("s".into(), "x".into());
+
+        // 类型不对的 key 直接回复错误，不会排队等待
+        for keys in [vec!["s".into()], vec!["none".into(), "s".into()]] {
+            let popped = backend
+                .bzpop(keys, ZEnd::Min, 1, std::future::pending())
+                .await;
+            assert!(matches!(popped, Err(CommandError::WrongType)));
+            assert_eq!(backend.blocked_clients(), 0);
+        }
+
+        // 前面的 key 有数据时不会检查后面的 key
+        backend
+            .zadd("z".into(), &ZAddOptions::default(), scored(&[(1.0, "a")]))
+            .unwrap();
+        let popped = backend
+            .bzpop(
+                vec!["z".into(), "s".into()],
+                ZEnd::Min,
+                1,
+                std::future::pending(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(popped, Some(("z".into(), vec![("a".into(), 1.0)])));
+    }
+
+    #[tokio::test]
+    async fn test_bzpop_served_while_timing_out() {
+        let backend = Backend::new();
+
+        // 客户端排队之后，超时的同时 ZADD 为它取走了成员，超时之后仍然要返回这个成员
+        let until = async {
+            backend
+                .zadd("q".into(), &ZAddOptions::default(), scored(&[(1.0, "a")]))
+                .unwrap();
+        };
+        let popped = backend
+            .bzpop(vec!["q".into()], ZEnd::Min, 1, until)
+            .await
+            .unwrap();
+        assert_eq!(popped, Some(("q".into(), vec![("a".into(), 1.0)])));
+        assert_eq!(backend.zcard(b"q").unwrap(), 0);
+        assert_eq!(backend.blocked_clients(), 0);
+
+        // 超时之后写入的成员留在集合里
+        let until = tokio::time::sleep(std::time::Duration::from_millis(10));
+        let popped = backend
+            .bzpop(vec!["q".into()], ZEnd::Max, 1, until)
+            .await
+            .unwrap();
+        assert_eq!(popped, None);
+        backend
+            .zadd("q".into(), &ZAddOptions::default(), scored(&[(2.0, "b")]))
+            .unwrap();
+        assert_eq!(backend.zcard(b"q").unwrap(), 1);
+    }
+}
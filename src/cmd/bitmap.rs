@@ -0,0 +1,466 @@
+use crate::cmd::{
+    bytes_arg, command_name, extract_args, parse_i64, syntax_error, validate_command, wrong_args,
+    BitCount, BitField, BitOp, BitPos, CommandError, CommandExecutor, GetBit, SetBit,
+};
+use crate::{
+    Backend, BitFieldOp, BitFieldType, BitOperation, BitOverflow, BitRange, BitUnit, RespArray,
+    RespFrame, RespNull, MAX_BIT_OFFSET,
+};
+
+impl CommandExecutor for SetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.setbit(self.key, self.offset, self.on) {
+            Ok(bit) => (bit as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.getbit(&self.key, self.offset) {
+            Ok(bit) => (bit as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitcount(&self.key, self.range) {
+            Ok(count) => (count as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitpos(&self.key, self.bit, self.range) {
+            Ok(pos) => pos.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitOp {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitop(self.op, self.dest, &self.keys) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitField {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitfield(self.key, &self.ops) {
+            Ok(values) => {
+                let values = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(value) => value.into(),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<_>>();
+                RespArray::new(values).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// SETBIT key offset value
+impl TryFrom<RespArray> for SetBit {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setbit"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(offset), Some(bit)) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args("setbit"));
+        };
+
+        let on = match bytes_arg(bit)?.as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "bit is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+
+        Ok(SetBit {
+            key: bytes_arg(key)?,
+            offset: parse_offset(&bytes_arg(offset)?, None)?,
+            on,
+        })
+    }
+}
+
+// GETBIT key offset
+impl TryFrom<RespArray> for GetBit {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getbit"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(offset)) => Ok(GetBit {
+                key: bytes_arg(key)?,
+                offset: parse_offset(&bytes_arg(offset)?, None)?,
+            }),
+            _ => Err(wrong_args("getbit")),
+        }
+    }
+}
+
+// BITCOUNT key [start end [BYTE | BIT]]
+impl TryFrom<RespArray> for BitCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("bitcount"))?)?;
+
+        let range = match (args.next(), args.next(), args.next(), args.next()) {
+            (None, ..) => None,
+            (Some(start), Some(end), unit, None) => Some(BitRange {
+                start: parse_i64(&bytes_arg(start)?)?,
+                end: Some(parse_i64(&bytes_arg(end)?)?),
+                unit: parse_unit(unit)?,
+            }),
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(BitCount { key, range })
+    }
+}
+
+// BITPOS key bit [start [end [BYTE | BIT]]]
+impl TryFrom<RespArray> for BitPos {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(bit)) = (args.next(), args.next()) else {
+            return Err(wrong_args("bitpos"));
+        };
+
+        let bit = match bytes_arg(bit)?.as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "The bit argument must be 1 or 0.".to_string(),
+                ))
+            }
+        };
+
+        let range = match (args.next(), args.next(), args.next(), args.next()) {
+            (None, ..) => None,
+            (Some(start), end, unit, None) => Some(BitRange {
+                start: parse_i64(&bytes_arg(start)?)?,
+                end: end.map(|end| parse_i64(&bytes_arg(end)?)).transpose()?,
+                unit: parse_unit(unit)?,
+            }),
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(BitPos {
+            key: bytes_arg(key)?,
+            bit,
+            range,
+        })
+    }
+}
+
+// BITOP <AND | OR | XOR | NOT> destkey key [key ...]
+impl TryFrom<RespArray> for BitOp {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(op), Some(dest)) = (args.next(), args.next()) else {
+            return Err(wrong_args("bitop"));
+        };
+
+        let op = match bytes_arg(op)?.to_ascii_lowercase().as_slice() {
+            b"and" => BitOperation::And,
+            b"or" => BitOperation::Or,
+            b"xor" => BitOperation::Xor,
+            b"not" => BitOperation::Not,
+            _ => return Err(syntax_error()),
+        };
+        let keys = args.map(bytes_arg).collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(wrong_args("bitop"));
+        }
+        if op == BitOperation::Not && keys.len() != 1 {
+            return Err(CommandError::InvalidArgument(
+                "BITOP NOT must be called with a single source key.".to_string(),
+            ));
+        }
+
+        Ok(BitOp {
+            op,
+            dest: bytes_arg(dest)?,
+            keys,
+        })
+    }
+}
+
+// BITFIELD key [GET encoding offset | [OVERFLOW <WRAP | SAT | FAIL>]
+//   <SET encoding offset value | INCRBY encoding offset increment> ...]
+// BITFIELD_RO key [GET encoding offset ...]
+impl TryFrom<RespArray> for BitField {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args(&name))?)?;
+
+        // OVERFLOW 只影响它后面的 SET 和 INCRBY
+        let mut overflow = BitOverflow::default();
+        let mut ops = Vec::new();
+        while let Some(sub) = args.next() {
+            let sub = bytes_arg(sub)?.to_ascii_lowercase();
+            if sub == b"overflow" {
+                let mode = bytes_arg(args.next().ok_or_else(syntax_error)?)?;
+                overflow = match mode.to_ascii_lowercase().as_slice() {
+                    b"wrap" => BitOverflow::Wrap,
+                    b"sat" => BitOverflow::Sat,
+                    b"fail" => BitOverflow::Fail,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid OVERFLOW type specified".to_string(),
+                        ))
+                    }
+                };
+                continue;
+            }
+
+            let (Some(ty), Some(offset)) = (args.next(), args.next()) else {
+                return Err(syntax_error());
+            };
+            let ty = parse_field_type(&bytes_arg(ty)?)?;
+            let offset = parse_offset(&bytes_arg(offset)?, Some(ty))?;
+
+            let op = match sub.as_slice() {
+                b"get" => BitFieldOp::Get { ty, offset },
+                b"set" | b"incrby" if name == "bitfield_ro" => {
+                    return Err(CommandError::InvalidArgument(
+                        "BITFIELD_RO only supports the GET subcommand".to_string(),
+                    ))
+                }
+                b"set" => BitFieldOp::Set {
+                    ty,
+                    offset,
+                    value: parse_i64(&bytes_arg(args.next().ok_or_else(syntax_error)?)?)?,
+                    overflow,
+                },
+                b"incrby" => BitFieldOp::IncrBy {
+                    ty,
+                    offset,
+                    delta: parse_i64(&bytes_arg(args.next().ok_or_else(syntax_error)?)?)?,
+                    overflow,
+                },
+                _ => return Err(syntax_error()),
+            };
+            ops.push(op);
+        }
+
+        Ok(BitField { key, ops })
+    }
+}
+
+fn parse_unit(unit: Option<RespFrame>) -> Result<BitUnit, CommandError> {
+    let Some(unit) = unit else {
+        return Ok(BitUnit::Byte);
+    };
+    match bytes_arg(unit)?.to_ascii_lowercase().as_slice() {
+        b"byte" => Ok(BitUnit::Byte),
+        b"bit" => Ok(BitUnit::Bit),
+        _ => Err(syntax_error()),
+    }
+}
+
+// i1 到 i64，u1 到 u63
+fn parse_field_type(arg: &[u8]) -> Result<BitFieldType, CommandError> {
+    let err = || {
+        CommandError::InvalidArgument(
+            "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                .to_string(),
+        )
+    };
+
+    let (signed, bits) = match arg {
+        [b'i' | b'I', bits @ ..] => (true, bits),
+        [b'u' | b'U', bits @ ..] => (false, bits),
+        _ => return Err(err()),
+    };
+    let bits = parse_i64(bits).map_err(|_| err())?;
+    let max = if signed { 64 } else { 63 };
+    if !(1..=max).contains(&bits) {
+        return Err(err());
+    }
+
+    Ok(BitFieldType {
+        signed,
+        bits: bits as u32,
+    })
+}
+
+// BITFIELD 的 offset 可以写成 #N，表示第 N 个这种类型的字段
+fn parse_offset(arg: &[u8], ty: Option<BitFieldType>) -> Result<u64, CommandError> {
+    let err = || {
+        CommandError::InvalidArgument("bit offset is not an integer or out of range".to_string())
+    };
+
+    let offset = match (arg.strip_prefix(b"#"), ty) {
+        (Some(index), Some(ty)) => parse_i64(index)
+            .map_err(|_| err())?
+            .checked_mul(ty.bits as i64),
+        _ => parse_i64(arg).ok(),
+    };
+    match offset {
+        Some(offset) if (0..MAX_BIT_OFFSET as i64).contains(&offset) => Ok(offset as u64),
+        _ => Err(err()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::exec;
+    use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, SimpleError, SimpleString};
+
+    #[test]
+    fn test_bit_commands() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["setbit", "key", "7", "1"]), 0.into());
+        assert_eq!(exec(&backend, &["setbit", "key", "7", "0"]), 1.into());
+        assert_eq!(exec(&backend, &["getbit", "key", "7"]), 0.into());
+        assert_eq!(
+            exec(&backend, &["setbit", "key", "7", "2"]),
+            SimpleError::new("ERR bit is not an integer or out of range").into()
+        );
+        assert_eq!(
+            exec(&backend, &["setbit", "key", "4294967296", "1"]),
+            SimpleError::new("ERR bit offset is not an integer or out of range").into()
+        );
+
+        exec(&backend, &["set", "key", "foobar"]);
+        assert_eq!(exec(&backend, &["bitcount", "key"]), 26.into());
+        assert_eq!(exec(&backend, &["bitcount", "key", "1", "1"]), 6.into());
+        assert_eq!(
+            exec(&backend, &["bitcount", "key", "5", "30", "bit"]),
+            17.into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitcount", "key", "1"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+
+        exec(&backend, &["set", "pos", "\u{0}\u{7f}"]);
+        assert_eq!(exec(&backend, &["bitpos", "pos", "1"]), 9.into());
+        assert_eq!(exec(&backend, &["bitpos", "pos", "0", "1"]), 8.into());
+        assert_eq!(
+            exec(&backend, &["bitpos", "pos", "1", "10", "-1", "bit"]),
+            10.into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitpos", "pos", "2"]),
+            SimpleError::new("ERR The bit argument must be 1 or 0.").into()
+        );
+
+        exec(&backend, &["hset", "hash", "f", "v"]);
+        assert_eq!(
+            exec(&backend, &["bitcount", "hash"]),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+    }
+
+    #[test]
+    fn test_bitop_command() {
+        let backend = Backend::new();
+        exec(&backend, &["set", "a", "abc"]);
+        exec(&backend, &["set", "b", "a"]);
+
+        assert_eq!(
+            exec(&backend, &["bitop", "xor", "dest", "a", "b"]),
+            3.into()
+        );
+        assert_eq!(
+            exec(&backend, &["get", "dest"]),
+            BulkString::new("\u{0}bc").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitop", "not", "dest", "a", "b"]),
+            SimpleError::new("ERR BITOP NOT must be called with a single source key.").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitop", "nand", "dest", "a"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+    }
+
+    #[test]
+    fn test_bitfield_command() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            exec(
+                &backend,
+                &[
+                    "bitfield", "key", "incrby", "u2", "100", "1", "overflow", "sat", "incrby",
+                    "i8", "#1", "-200", "get", "u4", "0"
+                ]
+            ),
+            RespArray::new([1.into(), (-128).into(), 0.into()]).into()
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["bitfield", "key", "overflow", "fail", "set", "u2", "100", "4"]
+            ),
+            RespArray::new([RespFrame::Null(RespNull)]).into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitfield_ro", "key", "get", "i8", "8"]),
+            RespArray::new([(-128).into()]).into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["bitfield_ro", "key", "set", "i8", "8", "1"]),
+            SimpleError::new("ERR BITFIELD_RO only supports the GET subcommand").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitfield", "key", "get", "u64", "0"]),
+            SimpleError::new(
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+            )
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitfield", "key", "overflow", "nope"]),
+            SimpleError::new("ERR Invalid OVERFLOW type specified").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bitfield", "key", "get", "u8"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+
+        // 只有 GET 的时候不创建 key
+        exec(&backend, &["bitfield", "missing", "get", "u8", "0"]);
+        assert_eq!(
+            exec(&backend, &["type", "missing"]),
+            SimpleString::new("none").into()
+        );
+    }
+}
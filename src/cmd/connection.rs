@@ -0,0 +1,221 @@
+use crate::cmd::{extract_args, CommandError, CommandExecutor, Hello, Ping};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap, RespProtocol, SimpleString};
+
+const SERVER_NAME: &str = "redis";
+const SERVER_VERSION: &str = "7.4.0";
+
+/// 每个连接自己的状态，由网络层持有
+#[derive(Debug, Default)]
+pub struct Session {
+    pub id: u64,
+    pub protocol: RespProtocol,
+    pub name: Option<String>,
+}
+
+impl Session {
+    pub fn new(id: u64) -> Self {
+        Session {
+            id,
+            ..Default::default()
+        }
+    }
+}
+
+impl Hello {
+    /// HELLO 会修改连接的状态，所以由网络层把 session 传进来执行
+    pub fn apply(self, session: &mut Session) -> RespFrame {
+        if let Some(protocol) = self.protocol {
+            session.protocol = protocol;
+        }
+        if let Some(name) = self.setname {
+            session.name = Some(name);
+        }
+
+        let proto = match session.protocol {
+            RespProtocol::Resp2 => 2,
+            RespProtocol::Resp3 => 3,
+        };
+
+        let mut map = RespMap::new();
+        map.insert("server".to_string(), BulkString::new(SERVER_NAME).into());
+        map.insert(
+            "version".to_string(),
+            BulkString::new(SERVER_VERSION).into(),
+        );
+        map.insert("proto".to_string(), proto.into());
+        map.insert("id".to_string(), (session.id as i64).into());
+        map.insert("mode".to_string(), BulkString::new("standalone").into());
+        map.insert("role".to_string(), BulkString::new("master").into());
+        map.insert("modules".to_string(), RespArray::new([]).into());
+        map.into()
+    }
+}
+
+impl CommandExecutor for Hello {
+    // 没有连接上下文的时候只回复服务器信息
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        self.apply(&mut Session::default())
+    }
+}
+
+impl CommandExecutor for Ping {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.message {
+            Some(message) => message.into(),
+            None => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+// PING [message]
+impl TryFrom<RespArray> for Ping {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+
+        match (args.next(), args.next()) {
+            (None, None) => Ok(Ping { message: None }),
+            (Some(RespFrame::BulkString(message)), None) => Ok(Ping {
+                message: Some(message),
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'ping' command".to_string(),
+            )),
+        }
+    }
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().map(|arg| match arg {
+            RespFrame::BulkString(s) => Ok(String::from_utf8_lossy(&s).to_string()),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid argument for HELLO".to_string(),
+            )),
+        });
+
+        let mut hello = Hello {
+            protocol: None,
+            setname: None,
+        };
+
+        let Some(protover) = args.next().transpose()? else {
+            return Ok(hello);
+        };
+
+        hello.protocol = match protover.parse::<i64>() {
+            Ok(2) => Some(RespProtocol::Resp2),
+            Ok(3) => Some(RespProtocol::Resp3),
+            Ok(_) => return Err(CommandError::NoProto),
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "Protocol version is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+
+        while let Some(opt) = args.next().transpose()? {
+            match opt.to_ascii_lowercase().as_str() {
+                "auth" => {
+                    let (Some(user), Some(_pass)) = (args.next(), args.next()) else {
+                        return Err(CommandError::InvalidArgument(
+                            "Syntax error in HELLO option 'auth'".to_string(),
+                        ));
+                    };
+                    // 没有开启认证，只有 default 用户可以登录
+                    if user? != "default" {
+                        return Err(CommandError::WrongPass);
+                    }
+                }
+                "setname" => {
+                    let Some(name) = args.next().transpose()? else {
+                        return Err(CommandError::InvalidArgument(
+                            "Syntax error in HELLO option 'setname'".to_string(),
+                        ));
+                    };
+                    if name.chars().any(|c| c <= ' ' || c > '~') {
+                        return Err(CommandError::InvalidArgument(
+                            "Client names cannot contain spaces, newlines or special characters."
+                                .to_string(),
+                        ));
+                    }
+                    hello.setname = Some(name);
+                }
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_ping() -> Result<()> {
+        let backend = Backend::new();
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*1\r\n$4\r\nPING\r\n*2\r\n$4\r\nping\r\n$2\r\nhi\r\n");
+
+        let ping: Ping = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(ping.execute(&backend), SimpleString::new("PONG").into());
+
+        let ping: Ping = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(ping.execute(&backend), BulkString::new("hi").into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_switch_protocol() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$7\r\nsetname\r\n$3\r\napp\r\n");
+
+        let hello: Hello = RespArray::decode(&mut buf)?.try_into()?;
+        let mut session = Session::new(7);
+
+        let RespFrame::Map(map) = hello.apply(&mut session) else {
+            panic!("expect a map");
+        };
+
+        assert_eq!(session.protocol, RespProtocol::Resp3);
+        assert_eq!(session.name.as_deref(), Some("app"));
+        assert_eq!(map.get("proto"), Some(&3.into()));
+        assert_eq!(map.get("id"), Some(&7.into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_errors() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$5\r\nhello\r\n$1\r\n4\r\n");
+
+        let ret = Hello::try_from(RespArray::decode(&mut buf)?);
+        assert!(matches!(ret, Err(CommandError::NoProto)));
+
+        buf.extend_from_slice(
+            b"*5\r\n$5\r\nhello\r\n$1\r\n3\r\n$4\r\nauth\r\n$3\r\nbob\r\n$1\r\nx\r\n",
+        );
+
+        let ret = Hello::try_from(RespArray::decode(&mut buf)?);
+        assert!(matches!(ret, Err(CommandError::WrongPass)));
+
+        Ok(())
+    }
+}
@@ -1,42 +1,261 @@
 use crate::cmd::{
-    extract_args, validate_command, CommandError, CommandExecutor, HGet, HGetAll, HSet, RESP_OK,
+    bulk_array, bytes_arg, command_name, extract_args, int_array, key_and_values, parse_expire_at,
+    parse_f64, parse_i64, parse_scan_args, syntax_error, validate_command, wrong_args,
+    CommandError, CommandExecutor, HDel, HExists, HExpire, HGet, HGetAll, HGetEx, HIncrBy,
+    HIncrByFloat, HKeys, HLen, HMGet, HPersist, HRandField, HScan, HSet, HSetEx, HSetNx, HStrLen,
+    HTtl, TimeUnit, RESP_OK,
 };
-use crate::{Backend, RespArray, RespFrame, RespMap};
+use crate::{
+    now_ms, Backend, BulkString, ExpireCondition, RespArray, RespFrame, RespMap, RespNull,
+    SetExpire, SetOptions,
+};
+use bytes::Bytes;
 
 impl CommandExecutor for HGet {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend
-            .hget(&self.key, &self.field)
-            .unwrap_or_else(|| RespFrame::Null(crate::RespNull))
+        match backend.hget(&self.key, &self.field) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
     }
 }
 
 impl CommandExecutor for HGetAll {
     fn execute(self, backend: &Backend) -> RespFrame {
-        let hmap = backend.hmap.get(&self.key);
-
-        match hmap {
-            None => RespArray::new([]).into(),
-            Some(hmap) => {
+        match backend.hgetall(&self.key) {
+            Ok(None) => RespArray::new([]).into(),
+            Ok(Some(hash)) => {
+                // RespMap 按 field 的字节序保存，所以回复总是按 field 排好序的，
+                // 和 hash 内部的顺序无关，测试里的快照可以保持稳定
                 let mut map = RespMap::new();
-
-                for item in hmap.iter() {
-                    let key = item.key().to_owned();
-                    map.insert(key, item.value().to_owned());
+                for (field, value) in hash {
+                    map.insert(field, BulkString::from(value).into());
                 }
                 map.into()
             }
+            Err(e) => e.into(),
         }
     }
 }
 
 impl CommandExecutor for HSet {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.hset(self.key, self.field, self.value);
-        RESP_OK.clone()
+        match backend.hmset(self.key, self.fields) {
+            Ok(_) if self.hmset => RESP_OK.clone(),
+            Ok(added) => (added as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HSetNx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hsetnx(self.key, self.field, self.value) {
+            Ok(set) => (set as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hdel(self.key, &self.fields) {
+            Ok(removed) => (removed as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HExists {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hexists(&self.key, &self.field) {
+            Ok(exists) => (exists as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hlen(&self.key) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HKeys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = if self.values {
+            backend.hvals(&self.key)
+        } else {
+            backend.hkeys(&self.key)
+        };
+        match ret {
+            Ok(items) => bulk_array(items),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HMGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hmget(&self.key, &self.fields) {
+            Ok(values) => {
+                let values = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(value) => BulkString::from(value).into(),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<_>>();
+                RespArray::new(values).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hincr_by(self.key, self.field, self.delta) {
+            Ok(value) => value.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HIncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hincr_by_float(self.key, self.field, self.delta) {
+            Ok(value) => BulkString::from(value).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HStrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hstrlen(&self.key, &self.field) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 没有 count 时回复一个 field，hash 不存在时是 nil
+        let Some(count) = self.count else {
+            return match backend.hrandfield(&self.key, 1) {
+                Ok(fields) => match fields.into_iter().next() {
+                    Some((field, _)) => BulkString::from(field).into(),
+                    None => RespFrame::Null(RespNull),
+                },
+                Err(e) => e.into(),
+            };
+        };
+
+        match backend.hrandfield(&self.key, count) {
+            Ok(fields) if self.with_values => bulk_array(flatten(fields)),
+            Ok(fields) => bulk_array(fields.into_iter().map(|(field, _)| field)),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hscan(&self.key, self.cursor, &self.options) {
+            Ok((cursor, fields)) => {
+                let items = if self.no_values {
+                    bulk_array(fields.into_iter().map(|(field, _)| field))
+                } else {
+                    bulk_array(flatten(fields))
+                };
+                RespArray::new([BulkString::new(cursor.to_string()).into(), items]).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hexpire_at(self.key, &self.fields, self.when, self.condition) {
+            Ok(codes) => int_array(codes),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let times = match backend.hexpire_time(&self.key, &self.fields) {
+            Ok(times) => times,
+            Err(e) => return e.into(),
+        };
+
+        let now = now_ms();
+        let ttls = times.into_iter().map(|time| {
+            let ttl = match time {
+                None => return -2,
+                Some(None) => return -1,
+                Some(Some(at)) if self.absolute => at as i64,
+                Some(Some(at)) => at.saturating_sub(now) as i64,
+            };
+            match self.unit {
+                TimeUnit::Milliseconds => ttl,
+                // 和 TTL 一样四舍五入到秒
+                TimeUnit::Seconds => (ttl + 500) / 1000,
+            }
+        });
+        int_array(ttls)
     }
 }
 
+impl CommandExecutor for HPersist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hpersist(self.key, &self.fields) {
+            Ok(codes) => int_array(codes),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HGetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hgetex(self.key, &self.fields, self.expire) {
+            Ok(values) => {
+                let values = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(value) => BulkString::from(value).into(),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<_>>();
+                RespArray::new(values).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HSetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hsetex(self.key, self.fields, self.options) {
+            Ok(set) => (set as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// field 和 value 交替排列
+fn flatten(fields: Vec<(Bytes, Bytes)>) -> impl Iterator<Item = Bytes> {
+    fields.into_iter().flat_map(|(field, value)| [field, value])
+}
+
 impl TryFrom<RespArray> for HGet {
     type Error = CommandError;
 
@@ -47,8 +266,8 @@ impl TryFrom<RespArray> for HGet {
 
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: key.into_bytes(),
+                field: field.into_bytes(),
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -67,45 +286,474 @@ impl TryFrom<RespArray> for HGetAll {
 
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
-                sort: false,
+                key: key.into_bytes(),
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
 
+// HSET key field value [field value ...], HMSET key field value [field value ...]
 impl TryFrom<RespArray> for HSet {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hset"], 3)?;
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args(&name))?)?;
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(wrong_args(&name));
+        }
+
+        let mut fields = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            fields.push((bytes_arg(field)?, bytes_arg(value)?));
+        }
+
+        Ok(HSet {
+            key,
+            fields,
+            hmset: name == "hmset",
+        })
+    }
+}
+
+// HSETNX key field value
+impl TryFrom<RespArray> for HSetNx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hsetnx"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(key), Some(field), Some(value)) => Ok(HSetNx {
+                key: bytes_arg(key)?,
+                field: bytes_arg(field)?,
+                value: bytes_arg(value)?,
+            }),
+            _ => Err(wrong_args("hsetnx")),
+        }
+    }
+}
+
+// HDEL key field [field ...]
+impl TryFrom<RespArray> for HDel {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = key_and_values(value, "hdel")?;
+        Ok(HDel { key, fields })
+    }
+}
+
+// HEXISTS key field
+impl TryFrom<RespArray> for HExists {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hexists"], 2)?;
 
         let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(field)) => Ok(HExists {
+                key: bytes_arg(key)?,
+                field: bytes_arg(field)?,
+            }),
+            _ => Err(wrong_args("hexists")),
+        }
+    }
+}
+
+// HLEN key
+impl TryFrom<RespArray> for HLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hlen"], 1)?;
 
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(HLen {
+                key: bytes_arg(key)?,
+            }),
+            _ => Err(wrong_args("hlen")),
+        }
+    }
+}
+
+// HKEYS key, HVALS key
+impl TryFrom<RespArray> for HKeys {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), None) => Ok(HKeys {
+                key: bytes_arg(key)?,
+                values: name == "hvals",
+            }),
+            _ => Err(wrong_args(&name)),
+        }
+    }
+}
+
+// HMGET key field [field ...]
+impl TryFrom<RespArray> for HMGet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = key_and_values(value, "hmget")?;
+        Ok(HMGet { key, fields })
+    }
+}
+
+// HINCRBY key field increment
+impl TryFrom<RespArray> for HIncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hincrby"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
-                Ok(HSet {
-                    key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
-                    value,
-                })
+            (Some(key), Some(field), Some(delta)) => Ok(HIncrBy {
+                key: bytes_arg(key)?,
+                field: bytes_arg(field)?,
+                delta: parse_i64(&bytes_arg(delta)?)?,
+            }),
+            _ => Err(wrong_args("hincrby")),
+        }
+    }
+}
+
+// HINCRBYFLOAT key field increment
+impl TryFrom<RespArray> for HIncrByFloat {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hincrbyfloat"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(key), Some(field), Some(delta)) => Ok(HIncrByFloat {
+                key: bytes_arg(key)?,
+                field: bytes_arg(field)?,
+                delta: parse_f64(&bytes_arg(delta)?)?,
+            }),
+            _ => Err(wrong_args("hincrbyfloat")),
+        }
+    }
+}
+
+// HSTRLEN key field
+impl TryFrom<RespArray> for HStrLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hstrlen"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(field)) => Ok(HStrLen {
+                key: bytes_arg(key)?,
+                field: bytes_arg(field)?,
+            }),
+            _ => Err(wrong_args("hstrlen")),
+        }
+    }
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+impl TryFrom<RespArray> for HRandField {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("hrandfield"))?)?;
+
+        let (count, with_values) = match (args.next(), args.next(), args.next()) {
+            (None, ..) => (None, false),
+            (Some(count), None, None) => (Some(parse_i64(&bytes_arg(count)?)?), false),
+            (Some(count), Some(opt), None) => {
+                if !bytes_arg(opt)?.eq_ignore_ascii_case(b"withvalues") {
+                    return Err(syntax_error());
+                }
+                (Some(parse_i64(&bytes_arg(count)?)?), true)
             }
+            _ => return Err(syntax_error()),
+        };
 
-            _ => Err(CommandError::InvalidArgument(
-                "Invalid key, field or value".to_string(),
-            )),
+        // 和 redis 一样限制 count 的范围，负数时返回的个数由 backend 限制
+        if count.is_some_and(|count| !(-i64::MAX / 2..=i64::MAX / 2).contains(&count)) {
+            return Err(CommandError::InvalidArgument(
+                "value is out of range".to_string(),
+            ));
+        }
+
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+impl TryFrom<RespArray> for HScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("hscan"))?)?;
+
+        let mut no_values = false;
+        let (cursor, options) = parse_scan_args(args, "hscan", |flag| {
+            no_values |= flag == b"novalues";
+            flag == b"novalues"
+        })?;
+
+        Ok(HScan {
+            key,
+            cursor,
+            options,
+            no_values,
+        })
+    }
+}
+
+// HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+// HPEXPIRE key milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+// HEXPIREAT key unix-time-seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+// HPEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+impl TryFrom<RespArray> for HExpire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+
+        let (Some(key), Some(time)) = (args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let key = bytes_arg(key)?;
+        let time = parse_i64(&bytes_arg(time)?)?;
+        if !(0..=MAX_FIELD_EXPIRE).contains(&time) {
+            return Err(CommandError::InvalidArgument(
+                "invalid expire time, must be >= 0 && <= 2^48".to_string(),
+            ));
+        }
+
+        // FIELDS 前面最多有一个条件
+        let mut condition = ExpireCondition::default();
+        if let Some(RespFrame::BulkString(opt)) = args.peek() {
+            match opt.to_ascii_lowercase().as_slice() {
+                b"nx" => condition.nx = true,
+                b"xx" => condition.xx = true,
+                b"gt" => condition.gt = true,
+                b"lt" => condition.lt = true,
+                _ => {}
+            }
+            if condition != ExpireCondition::default() {
+                args.next();
+            }
+        }
+        let fields = parse_fields(&mut args, 1)?;
+
+        // 统一换算成毫秒时间戳
+        let when = match name.as_str() {
+            "hpexpire" => time.checked_add(now_ms() as i64),
+            "hexpireat" => time.checked_mul(1000),
+            "hpexpireat" => Some(time),
+            _ => time
+                .checked_mul(1000)
+                .and_then(|ms| ms.checked_add(now_ms() as i64)),
+        };
+        let when = when
+            .filter(|when| *when <= MAX_FIELD_EXPIRE)
+            .ok_or_else(|| {
+                CommandError::InvalidArgument(format!("invalid expire time in '{}' command", name))
+            })?;
+
+        Ok(HExpire {
+            key,
+            when,
+            condition,
+            fields,
+        })
+    }
+}
+
+// HTTL key FIELDS numfields field [field ...]
+// HPTTL、HEXPIRETIME、HPEXPIRETIME 的参数一样
+impl TryFrom<RespArray> for HTtl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let (unit, absolute) = match name.as_str() {
+            "hpttl" => (TimeUnit::Milliseconds, false),
+            "hexpiretime" => (TimeUnit::Seconds, true),
+            "hpexpiretime" => (TimeUnit::Milliseconds, true),
+            _ => (TimeUnit::Seconds, false),
+        };
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args(&name))?)?;
+        let fields = parse_fields(&mut args, 1)?;
+
+        Ok(HTtl {
+            key,
+            unit,
+            absolute,
+            fields,
+        })
+    }
+}
+
+// HPERSIST key FIELDS numfields field [field ...]
+impl TryFrom<RespArray> for HPersist {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("hpersist"))?)?;
+        let fields = parse_fields(&mut args, 1)?;
+
+        Ok(HPersist { key, fields })
+    }
+}
+
+// HGETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | PERSIST] FIELDS numfields field [field ...]
+impl TryFrom<RespArray> for HGetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("hgetex"))?)?;
+
+        let mut expire = SetExpire::KeepTtl;
+        while let Some(RespFrame::BulkString(opt)) = args.peek() {
+            let opt = opt.to_ascii_lowercase();
+            if opt == b"fields" {
+                break;
+            }
+            args.next();
+
+            // 只能有一个选项
+            if expire != SetExpire::KeepTtl {
+                return Err(syntax_error());
+            }
+            expire = match opt.as_slice() {
+                b"persist" => SetExpire::Clear,
+                b"ex" | b"px" | b"exat" | b"pxat" => {
+                    SetExpire::At(parse_expire_at(&opt, args.next(), "hgetex")?)
+                }
+                _ => return Err(syntax_error()),
+            };
         }
+        let fields = parse_fields(&mut args, 1)?;
+
+        Ok(HGetEx {
+            key,
+            expire,
+            fields,
+        })
     }
 }
 
+// HSETEX key [FNX | FXX] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | KEEPTTL] FIELDS numfields field value [field value ...]
+impl TryFrom<RespArray> for HSetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("hsetex"))?)?;
+
+        let mut options = SetOptions::default();
+        let mut has_expire = false;
+        while let Some(RespFrame::BulkString(opt)) = args.peek() {
+            let opt = opt.to_ascii_lowercase();
+            if opt == b"fields" {
+                break;
+            }
+            args.next();
+
+            match opt.as_slice() {
+                b"fnx" if !options.xx => options.nx = true,
+                b"fxx" if !options.nx => options.xx = true,
+                b"keepttl" if !has_expire => {
+                    options.expire = SetExpire::KeepTtl;
+                    has_expire = true;
+                }
+                b"ex" | b"px" | b"exat" | b"pxat" if !has_expire => {
+                    options.expire = SetExpire::At(parse_expire_at(&opt, args.next(), "hsetex")?);
+                    has_expire = true;
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        let mut args = parse_fields(&mut args, 2)?.into_iter();
+        let mut fields = Vec::new();
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            fields.push((field, value));
+        }
+
+        Ok(HSetEx {
+            key,
+            options,
+            fields,
+        })
+    }
+}
+
+// field 的过期时间最多是 2^48 毫秒，和 redis 一样
+const MAX_FIELD_EXPIRE: i64 = 1 << 48;
+
+// FIELDS numfields field [field ...]，每个 field 后面跟着 width - 1 个参数，比如 HSETEX 的 value
+// 返回 FIELDS 后面所有的参数，数量必须和 numfields 一致
+fn parse_fields(
+    args: &mut impl Iterator<Item = RespFrame>,
+    width: usize,
+) -> Result<Vec<Bytes>, CommandError> {
+    let keyword = args.next().map(bytes_arg).transpose()?;
+    if !keyword.is_some_and(|keyword| keyword.eq_ignore_ascii_case(b"fields")) {
+        return Err(CommandError::InvalidArgument(
+            "Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        ));
+    }
+
+    let numfields = parse_i64(&bytes_arg(args.next().ok_or_else(syntax_error)?)?)?;
+    if numfields <= 0 {
+        return Err(CommandError::InvalidArgument(
+            "Parameter `numFields` should be greater than 0".to_string(),
+        ));
+    }
+
+    let fields = args.map(bytes_arg).collect::<Result<Vec<_>, _>>()?;
+    if fields.len() as u64 != numfields as u64 * width as u64 {
+        return Err(CommandError::InvalidArgument(
+            "The `numfields` parameter must match the number of arguments".to_string(),
+        ));
+    }
+    Ok(fields)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{Backend, RespDecode, RespMap};
     use crate::{RespArray, RespFrame};
     use bytes::BytesMut;
 
-    use crate::cmd::{CommandExecutor, HGet, HGetAll, HSet, RESP_OK};
+    use crate::cmd::{exec, CommandExecutor, HGet, HGetAll, HSet, RESP_OK};
+    use crate::{BulkString, RespNull, SimpleError, SimpleString};
     use anyhow::Result;
 
     #[test]
@@ -150,8 +798,8 @@ mod tests {
         let hset_cmd: HSet = resp_array.try_into()?;
 
         assert_eq!(hset_cmd.key, "map");
-        assert_eq!(hset_cmd.field, "hello");
-        assert_eq!(hset_cmd.value, RespFrame::BulkString(b"world".into()));
+        assert_eq!(hset_cmd.fields, vec![("hello".into(), "world".into())]);
+        assert!(!hset_cmd.hmset);
 
         Ok(())
     }
@@ -162,17 +810,18 @@ mod tests {
 
         let cmd = HSet {
             key: "map".into(),
-            field: "hello".into(),
-            value: RespFrame::BulkString(b"world".into()),
+            fields: vec![("hello".into(), "world".into())],
+            hmset: false,
         };
 
+        // HSET 回复新加入的 field 个数
         let result = cmd.execute(&backend);
-        assert_eq!(result, RESP_OK.clone());
+        assert_eq!(result, 1.into());
 
         let cmd = HSet {
             key: "map".into(),
-            field: "hello1".into(),
-            value: RespFrame::BulkString(b"world1".into()),
+            fields: vec![("hello1".into(), "world1".into())],
+            hmset: false,
         };
 
         cmd.execute(&backend);
@@ -185,10 +834,7 @@ mod tests {
         let result = cmd.execute(&backend);
         assert_eq!(result, RespFrame::BulkString(b"world1".into()));
 
-        let cmd = HGetAll {
-            key: "map".into(),
-            sort: false,
-        };
+        let cmd = HGetAll { key: "map".into() };
 
         let result = cmd.execute(&backend);
 
@@ -202,4 +848,268 @@ mod tests {
         assert_eq!(result, expected.into());
         Ok(())
     }
+
+    #[test]
+    fn test_hset_hgetall_binary_field() -> Result<()> {
+        let backend = Backend::new();
+
+        let cmd = HSet {
+            key: (&b"\x00\x01"[..]).into(),
+            fields: vec![((&b"\xfe\r\n"[..]).into(), "world".into())],
+            hmset: false,
+        };
+        cmd.execute(&backend);
+
+        let cmd = HGet {
+            key: (&b"\x00\x01"[..]).into(),
+            field: (&b"\xfe\r\n"[..]).into(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::BulkString(b"world".into())
+        );
+
+        let cmd = HGetAll {
+            key: (&b"\x00\x01"[..]).into(),
+        };
+        let RespFrame::Map(map) = cmd.execute(&backend) else {
+            panic!("expect a map");
+        };
+        assert_eq!(
+            map.get(b"\xfe\r\n"),
+            Some(&RespFrame::BulkString(b"world".into()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_commands() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["hset", "h", "a", "1", "b", "2"]), 2.into());
+        assert_eq!(exec(&backend, &["hset", "h", "a", "3"]), 0.into());
+        assert_eq!(exec(&backend, &["hmset", "h", "c", "4"]), RESP_OK.clone());
+        assert_eq!(
+            exec(&backend, &["hset", "h", "a"]),
+            SimpleError::new("ERR wrong number of arguments for 'hset' command").into()
+        );
+        assert_eq!(exec(&backend, &["hsetnx", "h", "a", "x"]), 0.into());
+        assert_eq!(exec(&backend, &["hlen", "h"]), 3.into());
+        assert_eq!(exec(&backend, &["hexists", "h", "b"]), 1.into());
+        assert_eq!(exec(&backend, &["hstrlen", "h", "c"]), 1.into());
+        assert_eq!(
+            exec(&backend, &["hmget", "h", "a", "nope"]),
+            RespArray::new([BulkString::new("3").into(), RespFrame::Null(RespNull)]).into()
+        );
+
+        assert_eq!(exec(&backend, &["hincrby", "h", "a", "10"]), 13.into());
+        assert_eq!(
+            exec(&backend, &["hincrbyfloat", "h", "a", "0.5"]),
+            BulkString::new("13.5").into()
+        );
+        assert_eq!(
+            exec(&backend, &["hincrby", "h", "a", "1"]),
+            SimpleError::new("ERR hash value is not an integer").into()
+        );
+
+        assert_eq!(exec(&backend, &["hdel", "h", "a", "b", "nope"]), 2.into());
+        assert_eq!(
+            exec(&backend, &["hkeys", "h"]),
+            RespArray::new([BulkString::new("c").into()]).into()
+        );
+        assert_eq!(
+            exec(&backend, &["hvals", "h"]),
+            RespArray::new([BulkString::new("4").into()]).into()
+        );
+        assert_eq!(exec(&backend, &["hdel", "h", "c"]), 1.into());
+        assert_eq!(
+            exec(&backend, &["type", "h"]),
+            SimpleString::new("none").into()
+        );
+        assert_eq!(exec(&backend, &["hkeys", "h"]), RespArray::new([]).into());
+    }
+
+    #[test]
+    fn test_hrandfield_hscan_commands() {
+        let backend = Backend::new();
+        assert_eq!(
+            exec(&backend, &["hrandfield", "h"]),
+            RespFrame::Null(RespNull)
+        );
+
+        exec(&backend, &["hset", "h", "a", "1"]);
+        assert_eq!(
+            exec(&backend, &["hrandfield", "h"]),
+            BulkString::new("a").into()
+        );
+        assert_eq!(
+            exec(&backend, &["hrandfield", "h", "-2", "withvalues"]),
+            RespArray::new([
+                BulkString::new("a").into(),
+                BulkString::new("1").into(),
+                BulkString::new("a").into(),
+                BulkString::new("1").into(),
+            ])
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["hrandfield", "h", "1", "values"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(
+            exec(&backend, &["hrandfield", "h", "-1000000000000"]),
+            SimpleError::new(
+                "ERR count is too large, at most 1048576 random elements can be returned"
+            )
+            .into()
+        );
+
+        exec(&backend, &["hset", "h", "b", "2", "ab", "3"]);
+        let RespFrame::Array(reply) = exec(&backend, &["hscan", "h", "0", "match", "a*"]) else {
+            panic!("expect an array");
+        };
+        assert_eq!(reply[0], BulkString::new("0").into());
+        let RespFrame::Array(items) = &reply[1] else {
+            panic!("expect an array");
+        };
+        assert_eq!(items.len(), 4);
+
+        let RespFrame::Array(reply) = exec(&backend, &["hscan", "h", "0", "novalues"]) else {
+            panic!("expect an array");
+        };
+        let RespFrame::Array(items) = &reply[1] else {
+            panic!("expect an array");
+        };
+        assert_eq!(items.len(), 3);
+
+        assert_eq!(
+            exec(&backend, &["hscan", "h", "x"]),
+            SimpleError::new("ERR invalid cursor").into()
+        );
+        assert_eq!(
+            exec(&backend, &["hscan", "h", "0", "count", "0"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+    }
+
+    #[test]
+    fn test_hgetall_ordered_by_field() {
+        let backend = Backend::new();
+        exec(
+            &backend,
+            &["hset", "h", "b", "2", "c", "3", "a", "1", "aa", "4"],
+        );
+
+        // 不管写入的顺序，RESP2 下展开成的数组总是按 field 的字节序排列
+        let reply = exec(&backend, &["hgetall", "h"]).into_resp2();
+        let expected = ["a", "1", "aa", "4", "b", "2", "c", "3"]
+            .into_iter()
+            .map(|s| BulkString::new(s).into())
+            .collect::<Vec<RespFrame>>();
+        assert_eq!(reply, RespArray::new(expected).into());
+    }
+
+    #[test]
+    fn test_field_expire_commands() {
+        let backend = Backend::new();
+        exec(&backend, &["hset", "h", "a", "1", "b", "2"]);
+        let ints = |items: &[i64]| {
+            RespFrame::from(RespArray::new(
+                items.iter().map(|&i| i.into()).collect::<Vec<RespFrame>>(),
+            ))
+        };
+
+        assert_eq!(
+            exec(&backend, &["hexpire", "h", "100", "fields", "2", "a", "c"]),
+            ints(&[1, -2])
+        );
+        // GT 要求新的过期时间更大，没有过期时间的 field 当作永不过期
+        assert_eq!(
+            exec(
+                &backend,
+                &["hpexpire", "h", "50000", "gt", "fields", "2", "a", "b"]
+            ),
+            ints(&[0, 0])
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["hexpire", "h", "200", "nx", "fields", "2", "a", "b"]
+            ),
+            ints(&[0, 1])
+        );
+        assert_eq!(
+            exec(&backend, &["httl", "h", "fields", "3", "a", "b", "c"]),
+            ints(&[100, 200, -2])
+        );
+        let RespFrame::Array(reply) = exec(&backend, &["hpttl", "h", "fields", "1", "a"]) else {
+            panic!("expected array");
+        };
+        assert!(matches!(reply[0], RespFrame::Integer(ms) if ms > 99_000 && ms <= 100_000));
+
+        assert_eq!(
+            exec(&backend, &["hpersist", "h", "fields", "2", "a", "c"]),
+            ints(&[1, -2])
+        );
+        assert_eq!(
+            exec(&backend, &["hexpiretime", "h", "fields", "1", "a"]),
+            ints(&[-1])
+        );
+
+        // HGETEX 返回旧的值，过去的时间会删除 field
+        assert_eq!(
+            exec(
+                &backend,
+                &["hgetex", "h", "exat", "1", "fields", "2", "a", "c"]
+            ),
+            RespArray::new(vec![BulkString::new("1").into(), RespFrame::Null(RespNull)]).into()
+        );
+        assert_eq!(exec(&backend, &["hlen", "h"]), 1.into());
+
+        // FNX 要求所有 field 都不存在
+        assert_eq!(
+            exec(
+                &backend,
+                &["hsetex", "h", "fnx", "fields", "2", "b", "x", "c", "y"]
+            ),
+            0.into()
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["hsetex", "h", "fxx", "keepttl", "fields", "1", "b", "x"]
+            ),
+            1.into()
+        );
+        assert_eq!(
+            exec(&backend, &["httl", "h", "fields", "1", "b"]),
+            ints(&[200])
+        );
+        assert_eq!(
+            exec(&backend, &["hget", "h", "b"]),
+            BulkString::new("x").into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["httl", "h", "a", "1", "b"]),
+            SimpleError::new(
+                "ERR Mandatory argument FIELDS is missing or not at the right position"
+            )
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["hpersist", "h", "fields", "0", "a"]),
+            SimpleError::new("ERR Parameter `numFields` should be greater than 0").into()
+        );
+        assert_eq!(
+            exec(&backend, &["hpersist", "h", "fields", "2", "a"]),
+            SimpleError::new("ERR The `numfields` parameter must match the number of arguments")
+                .into()
+        );
+        assert_eq!(
+            exec(&backend, &["hexpire", "h", "-1", "fields", "1", "a"]),
+            SimpleError::new("ERR invalid expire time, must be >= 0 && <= 2^48").into()
+        );
+    }
 }
@@ -0,0 +1,304 @@
+use crate::cmd::{
+    bytes_arg, command_name, extract_args, parse_i64, validate_command, wrong_args, CommandError,
+    CommandExecutor, Expire, Persist, TimeUnit, Ttl, Type,
+};
+use crate::{now_ms, Backend, ExpireCondition, RespArray, RespFrame, SimpleString};
+
+impl CommandExecutor for Type {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        SimpleString::new(backend.key_type(&self.key)).into()
+    }
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        (backend.expire_at(self.key, self.when, self.condition) as i64).into()
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ttl = match backend.expire_time(&self.key) {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(at)) if self.absolute => at as i64,
+            Some(Some(at)) => at.saturating_sub(now_ms()) as i64,
+        };
+
+        match self.unit {
+            _ if ttl < 0 => ttl.into(),
+            TimeUnit::Milliseconds => ttl.into(),
+            // 和 redis 一样四舍五入到秒
+            TimeUnit::Seconds => ((ttl + 500) / 1000).into(),
+        }
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        (backend.persist(self.key) as i64).into()
+    }
+}
+
+// TYPE key
+impl TryFrom<RespArray> for Type {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["type"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Type {
+                key: key.into_bytes(),
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+// EXPIRE key seconds [NX | XX | GT | LT]
+// PEXPIRE key milliseconds [NX | XX | GT | LT]
+// EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+// PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter();
+
+        let (Some(key), Some(time)) = (args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let key = bytes_arg(key)?;
+        let time = parse_i64(&bytes_arg(time)?)?;
+
+        let mut condition = ExpireCondition::default();
+        for opt in args {
+            let opt = bytes_arg(opt)?;
+            match opt.to_ascii_lowercase().as_slice() {
+                b"nx" => condition.nx = true,
+                b"xx" => condition.xx = true,
+                b"gt" => condition.gt = true,
+                b"lt" => condition.lt = true,
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unsupported option {}",
+                        String::from_utf8_lossy(&opt)
+                    )))
+                }
+            }
+        }
+
+        if condition.nx && (condition.xx || condition.gt || condition.lt) {
+            return Err(CommandError::InvalidArgument(
+                "NX and XX, GT or LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        if condition.gt && condition.lt {
+            return Err(CommandError::InvalidArgument(
+                "GT and LT options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        // 统一换算成毫秒时间戳，溢出时报错
+        let (unit, absolute) = match name.as_str() {
+            "pexpire" => (TimeUnit::Milliseconds, false),
+            "expireat" => (TimeUnit::Seconds, true),
+            "pexpireat" => (TimeUnit::Milliseconds, true),
+            _ => (TimeUnit::Seconds, false),
+        };
+        let when = match unit {
+            TimeUnit::Seconds => time.checked_mul(1000),
+            TimeUnit::Milliseconds => Some(time),
+        };
+        let when = if absolute {
+            when
+        } else {
+            when.and_then(|when| when.checked_add(now_ms() as i64))
+        };
+        let Some(when) = when else {
+            return Err(CommandError::InvalidArgument(format!(
+                "invalid expire time in '{}' command",
+                name
+            )));
+        };
+
+        Ok(Expire {
+            key,
+            when,
+            condition,
+        })
+    }
+}
+
+// TTL key, PTTL key, EXPIRETIME key, PEXPIRETIME key
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        if value.len() != 2 {
+            return Err(wrong_args(&name));
+        }
+
+        let (unit, absolute) = match name.as_str() {
+            "pttl" => (TimeUnit::Milliseconds, false),
+            "expiretime" => (TimeUnit::Seconds, true),
+            "pexpiretime" => (TimeUnit::Milliseconds, true),
+            _ => (TimeUnit::Seconds, false),
+        };
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(Ttl {
+                key: bytes_arg(key)?,
+                unit,
+                absolute,
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+// PERSIST key
+impl TryFrom<RespArray> for Persist {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["persist"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(Persist {
+                key: bytes_arg(key)?,
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{exec, Command};
+    use crate::{RespDecode, SimpleError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &Backend, buf: &mut BytesMut) -> Result<RespFrame> {
+        let cmd: Command = RespArray::decode(buf)?.try_into()?;
+        Ok(cmd.execute(backend))
+    }
+
+    #[test]
+    fn test_type_and_wrong_type() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+
+        buf.extend_from_slice(b"*2\r\n$4\r\nTYPE\r\n$3\r\nkey\r\n");
+        assert_eq!(run(&backend, &mut buf)?, SimpleString::new("none").into());
+
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+        run(&backend, &mut buf)?;
+
+        buf.extend_from_slice(b"*2\r\n$4\r\ntype\r\n$3\r\nkey\r\n");
+        assert_eq!(run(&backend, &mut buf)?, SimpleString::new("string").into());
+
+        // 同一个 key 不能同时是 string 和 hash
+        buf.extend_from_slice(b"*3\r\n$4\r\nhget\r\n$3\r\nkey\r\n$1\r\nf\r\n");
+        assert_eq!(
+            run(&backend, &mut buf)?,
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_rejects_non_bulk_value() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n*1\r\n$1\r\na\r\n");
+
+        let ret = Command::try_from(RespArray::decode(&mut buf)?);
+        assert!(matches!(ret, Err(CommandError::InvalidArgument(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_ttl_persist() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["expire", "key", "100"]), 0.into());
+        assert_eq!(exec(&backend, &["ttl", "key"]), (-2).into());
+
+        exec(&backend, &["set", "key", "value"]);
+        assert_eq!(exec(&backend, &["ttl", "key"]), (-1).into());
+        assert_eq!(exec(&backend, &["expiretime", "key"]), (-1).into());
+
+        assert_eq!(exec(&backend, &["expire", "key", "100"]), 1.into());
+        assert_eq!(exec(&backend, &["ttl", "key"]), 100.into());
+        let RespFrame::Integer(pttl) = exec(&backend, &["pttl", "key"]) else {
+            panic!("expect an integer");
+        };
+        assert!((99_000..=100_000).contains(&pttl));
+
+        // GT 要求新的过期时间更大，NX 要求没有过期时间
+        assert_eq!(exec(&backend, &["expire", "key", "10", "gt"]), 0.into());
+        assert_eq!(exec(&backend, &["expire", "key", "10", "NX"]), 0.into());
+        assert_eq!(
+            exec(&backend, &["expire", "key", "10", "xx", "lt"]),
+            1.into()
+        );
+        assert_eq!(exec(&backend, &["ttl", "key"]), 10.into());
+
+        assert_eq!(
+            exec(&backend, &["pexpireat", "key", "4102444800000"]),
+            1.into()
+        );
+        assert_eq!(exec(&backend, &["expiretime", "key"]), 4_102_444_800.into());
+
+        assert_eq!(exec(&backend, &["persist", "key"]), 1.into());
+        assert_eq!(exec(&backend, &["persist", "key"]), 0.into());
+        assert_eq!(exec(&backend, &["ttl", "key"]), (-1).into());
+
+        // 过期时间已经过去，key 直接被删除
+        assert_eq!(exec(&backend, &["pexpire", "key", "-1"]), 1.into());
+        assert_eq!(
+            exec(&backend, &["type", "key"]),
+            SimpleString::new("none").into()
+        );
+    }
+
+    #[test]
+    fn test_expire_errors() {
+        let backend = Backend::new();
+        exec(&backend, &["set", "key", "value"]);
+
+        assert_eq!(
+            exec(&backend, &["expire", "key", "10", "nx", "xx"]),
+            SimpleError::new("ERR NX and XX, GT or LT options at the same time are not compatible")
+                .into()
+        );
+        assert_eq!(
+            exec(&backend, &["expire", "key", "10", "gt", "lt"]),
+            SimpleError::new("ERR GT and LT options at the same time are not compatible").into()
+        );
+        assert_eq!(
+            exec(&backend, &["expire", "key", "10", "foo"]),
+            SimpleError::new("ERR Unsupported option foo").into()
+        );
+        assert_eq!(
+            exec(&backend, &["expire", "key", "1.5"]),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+        assert_eq!(
+            exec(&backend, &["expire", "key", "9223372036854775807"]),
+            SimpleError::new("ERR invalid expire time in 'expire' command").into()
+        );
+    }
+}
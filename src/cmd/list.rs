@@ -0,0 +1,847 @@
+use crate::cmd::{
+    bulk_array, bytes_arg, command_name, extract_args, int_array, key_and_values, parse_count,
+    parse_i64, parse_timeout, syntax_error, validate_command, wait_until, wrong_args, BLMove, BPop,
+    CommandError, CommandExecutor, LIndex, LInsert, LLen, LMove, LPos, LRange, LRem, LSet, LTrim,
+    Pop, Push, RESP_OK,
+};
+use crate::{Backend, BulkString, ListEnd, RespArray, RespFrame, RespNull, RespNullArray};
+use bytes::Bytes;
+use std::future::Future;
+
+impl CommandExecutor for Push {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let len = if self.only_existing {
+            backend.pushx(self.key, self.end, self.values)
+        } else {
+            backend.push(self.key, self.end, self.values)
+        };
+        match len {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Pop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let values = match backend.pop(self.key, self.end, self.count.unwrap_or(1)) {
+            Ok(values) => values,
+            Err(e) => return e.into(),
+        };
+
+        // 有 COUNT 时 key 不存在回复 null array，没有 COUNT 时回复 null
+        match (values, self.count) {
+            (Some(values), Some(_)) => bulk_array(values),
+            (Some(values), None) => values
+                .into_iter()
+                .next()
+                .map_or(RespFrame::Null(RespNull), |value| {
+                    BulkString::from(value).into()
+                }),
+            (None, Some(_)) => RespNullArray.into(),
+            (None, None) => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for LLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.llen(&self.key) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lrange(&self.key, self.start, self.stop) {
+            Ok(values) => bulk_array(values),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LIndex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lindex(&self.key, self.index) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lset(self.key, self.index, self.value) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LInsert {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.linsert(self.key, self.before, &self.pivot, self.value) {
+            Ok(len) => len.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lrem(self.key, self.count, &self.value) {
+            Ok(removed) => (removed as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.ltrim(self.key, self.start, self.stop) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        let positions = match backend.lpos(&self.key, &self.element, self.rank, count, self.maxlen)
+        {
+            Ok(positions) => positions.into_iter().map(|pos| pos as i64),
+            Err(e) => return e.into(),
+        };
+
+        match self.count {
+            Some(_) => int_array(positions),
+            None => positions
+                .into_iter()
+                .next()
+                .map_or(RespFrame::Null(RespNull), RespFrame::from),
+        }
+    }
+}
+
+impl CommandExecutor for LMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lmove(self.source, self.destination, self.from, self.to) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let popped = backend.mpop(&self.keys, self.end, self.count.unwrap_or(1));
+        bpop_reply(popped, self.count.is_some())
+    }
+}
+
+impl BPop {
+    pub(crate) async fn wait(
+        self,
+        backend: &Backend,
+        closed: impl Future<Output = ()>,
+    ) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        let until = wait_until(self.timeout, closed);
+        let popped = backend.bpop(self.keys, self.end, count, until).await;
+        bpop_reply(popped, self.count.is_some())
+    }
+}
+
+impl CommandExecutor for BLMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let moved = backend.lmove(self.source, self.destination, self.from, self.to);
+        blmove_reply(moved)
+    }
+}
+
+impl BLMove {
+    pub(crate) async fn wait(
+        self,
+        backend: &Backend,
+        closed: impl Future<Output = ()>,
+    ) -> RespFrame {
+        let moved = backend
+            .blmove(
+                self.source,
+                self.destination,
+                self.from,
+                self.to,
+                wait_until(self.timeout, closed),
+            )
+            .await;
+        blmove_reply(moved)
+    }
+}
+
+// BLPOP 回复 [key, element]，BLMPOP 回复 [key, [element ...]]，超时回复 null array
+fn bpop_reply(popped: Result<Option<(Bytes, Vec<Bytes>)>, CommandError>, multi: bool) -> RespFrame {
+    match popped {
+        Ok(Some((key, values))) => {
+            let values = if multi {
+                bulk_array(values)
+            } else {
+                values
+                    .into_iter()
+                    .next()
+                    .map_or(RespFrame::Null(RespNull), |value| {
+                        BulkString::from(value).into()
+                    })
+            };
+            RespArray::new(vec![BulkString::from(key).into(), values]).into()
+        }
+        Ok(None) => RespNullArray.into(),
+        Err(e) => e.into(),
+    }
+}
+
+// 和 redis 一样，BLMOVE 超时也回复 null array
+fn blmove_reply(moved: Result<Option<Bytes>, CommandError>) -> RespFrame {
+    match moved {
+        Ok(Some(value)) => BulkString::from(value).into(),
+        Ok(None) => RespNullArray.into(),
+        Err(e) => e.into(),
+    }
+}
+
+// LPUSH key element [element ...]
+// RPUSH、LPUSHX、RPUSHX 的参数一样
+impl TryFrom<RespArray> for Push {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let end = if name.starts_with('l') {
+            ListEnd::Left
+        } else {
+            ListEnd::Right
+        };
+        let only_existing = name.ends_with('x');
+
+        let (key, values) = key_and_values(value, &name)?;
+
+        Ok(Push {
+            key,
+            end,
+            values,
+            only_existing,
+        })
+    }
+}
+
+// LPOP key [count]
+// RPOP key [count]
+impl TryFrom<RespArray> for Pop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let end = if name == "lpop" {
+            ListEnd::Left
+        } else {
+            ListEnd::Right
+        };
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), count, None) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let count = count
+            .map(|count| parse_count(&bytes_arg(count)?))
+            .transpose()?;
+
+        Ok(Pop {
+            key: bytes_arg(key)?,
+            end,
+            count,
+        })
+    }
+}
+
+// LLEN key
+impl TryFrom<RespArray> for LLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["llen"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("llen"))?)?;
+        Ok(LLen { key })
+    }
+}
+
+// LRANGE key start stop
+impl TryFrom<RespArray> for LRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lrange"], 3)?;
+
+        let (key, start, stop) = key_and_range(value, "lrange")?;
+        Ok(LRange { key, start, stop })
+    }
+}
+
+// LINDEX key index
+impl TryFrom<RespArray> for LIndex {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lindex"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(index)) = (args.next(), args.next()) else {
+            return Err(wrong_args("lindex"));
+        };
+
+        Ok(LIndex {
+            key: bytes_arg(key)?,
+            index: parse_i64(&bytes_arg(index)?)?,
+        })
+    }
+}
+
+// LSET key index element
+impl TryFrom<RespArray> for LSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lset"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(index), Some(element)) = (args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("lset"));
+        };
+
+        Ok(LSet {
+            key: bytes_arg(key)?,
+            index: parse_i64(&bytes_arg(index)?)?,
+            value: bytes_arg(element)?,
+        })
+    }
+}
+
+// LINSERT key <BEFORE | AFTER> pivot element
+impl TryFrom<RespArray> for LInsert {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["linsert"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(position), Some(pivot), Some(element)) =
+            (args.next(), args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("linsert"));
+        };
+
+        let before = match bytes_arg(position)?.to_ascii_lowercase().as_slice() {
+            b"before" => true,
+            b"after" => false,
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(LInsert {
+            key: bytes_arg(key)?,
+            before,
+            pivot: bytes_arg(pivot)?,
+            value: bytes_arg(element)?,
+        })
+    }
+}
+
+// LREM key count element
+impl TryFrom<RespArray> for LRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lrem"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(count), Some(element)) = (args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("lrem"));
+        };
+
+        Ok(LRem {
+            key: bytes_arg(key)?,
+            count: parse_i64(&bytes_arg(count)?)?,
+            value: bytes_arg(element)?,
+        })
+    }
+}
+
+// LTRIM key start stop
+impl TryFrom<RespArray> for LTrim {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["ltrim"], 3)?;
+
+        let (key, start, stop) = key_and_range(value, "ltrim")?;
+        Ok(LTrim { key, start, stop })
+    }
+}
+
+// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+impl TryFrom<RespArray> for LPos {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(element)) = (args.next(), args.next()) else {
+            return Err(wrong_args("lpos"));
+        };
+
+        let mut pos = LPos {
+            key: bytes_arg(key)?,
+            element: bytes_arg(element)?,
+            rank: 1,
+            count: None,
+            maxlen: 0,
+        };
+        while let Some(opt) = args.next() {
+            let opt = bytes_arg(opt)?.to_ascii_lowercase();
+            let arg = parse_i64(&bytes_arg(args.next().ok_or_else(syntax_error)?)?)?;
+
+            match opt.as_slice() {
+                b"rank" if arg == 0 => {
+                    return Err(CommandError::InvalidArgument(
+                        "RANK can't be zero: use 1 to start from the first match, 2 from the \
+                         second ... or use negative to start from the last match"
+                            .to_string(),
+                    ))
+                }
+                // 和 redis 一样，rank 取反之后不能溢出
+                b"rank" if arg == i64::MIN => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "value is out of range, value must between {} and {}",
+                        -i64::MAX,
+                        i64::MAX
+                    )))
+                }
+                b"rank" => pos.rank = arg,
+                b"count" => {
+                    let count = usize::try_from(arg).map_err(|_| {
+                        CommandError::InvalidArgument("COUNT can't be negative".to_string())
+                    })?;
+                    pos.count = Some(count);
+                }
+                b"maxlen" => {
+                    pos.maxlen = usize::try_from(arg).map_err(|_| {
+                        CommandError::InvalidArgument("MAXLEN can't be negative".to_string())
+                    })?;
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        Ok(pos)
+    }
+}
+
+// LMOVE source destination <LEFT | RIGHT> <LEFT | RIGHT>
+impl TryFrom<RespArray> for LMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lmove"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(source), Some(destination), Some(from), Some(to)) =
+            (args.next(), args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("lmove"));
+        };
+
+        Ok(LMove {
+            source: bytes_arg(source)?,
+            destination: bytes_arg(destination)?,
+            from: parse_end(&bytes_arg(from)?)?,
+            to: parse_end(&bytes_arg(to)?)?,
+        })
+    }
+}
+
+// BLPOP key [key ...] timeout
+// BRPOP key [key ...] timeout
+// BLMPOP timeout numkeys key [key ...] <LEFT | RIGHT> [COUNT count]
+impl TryFrom<RespArray> for BPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if name != "blmpop" {
+            let timeout = args.pop().ok_or_else(|| wrong_args(&name))?;
+            if args.is_empty() {
+                return Err(wrong_args(&name));
+            }
+            return Ok(BPop {
+                keys: args,
+                end: if name == "blpop" {
+                    ListEnd::Left
+                } else {
+                    ListEnd::Right
+                },
+                count: None,
+                timeout: parse_timeout(&timeout)?,
+            });
+        }
+
+        let mut args = args.into_iter();
+        let (Some(timeout), Some(numkeys)) = (args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let timeout = parse_timeout(&timeout)?;
+        let numkeys = parse_i64(&numkeys)?;
+        if numkeys <= 0 {
+            return Err(CommandError::InvalidArgument(
+                "numkeys should be greater than 0".to_string(),
+            ));
+        }
+        let keys = args.by_ref().take(numkeys as usize).collect::<Vec<_>>();
+        let end = match args.next() {
+            Some(end) if keys.len() == numkeys as usize => parse_end(&end)?,
+            _ => return Err(syntax_error()),
+        };
+
+        let count = match (args.next(), args.next(), args.next()) {
+            (None, None, None) => 1,
+            (Some(opt), Some(count), None) if opt.eq_ignore_ascii_case(b"count") => {
+                match parse_i64(&count)? {
+                    count if count > 0 => count as usize,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "count should be greater than 0".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(BPop {
+            keys,
+            end,
+            count: Some(count),
+            timeout,
+        })
+    }
+}
+
+// BLMOVE source destination <LEFT | RIGHT> <LEFT | RIGHT> timeout
+impl TryFrom<RespArray> for BLMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["blmove"], 5)?;
+
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+        let (Some(source), Some(destination), Some(from), Some(to), Some(timeout)) = (
+            args.next(),
+            args.next(),
+            args.next(),
+            args.next(),
+            args.next(),
+        ) else {
+            return Err(wrong_args("blmove"));
+        };
+
+        Ok(BLMove {
+            source,
+            destination,
+            from: parse_end(&from)?,
+            to: parse_end(&to)?,
+            timeout: parse_timeout(&timeout)?,
+        })
+    }
+}
+
+// LEFT 或者 RIGHT，不区分大小写
+fn parse_end(arg: &[u8]) -> Result<ListEnd, CommandError> {
+    match arg.to_ascii_lowercase().as_slice() {
+        b"left" => Ok(ListEnd::Left),
+        b"right" => Ok(ListEnd::Right),
+        _ => Err(syntax_error()),
+    }
+}
+
+// key start stop
+fn key_and_range(value: RespArray, name: &str) -> Result<(Bytes, i64, i64), CommandError> {
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (Some(key), Some(start), Some(stop)) = (args.next(), args.next(), args.next()) else {
+        return Err(wrong_args(name));
+    };
+
+    Ok((
+        bytes_arg(key)?,
+        parse_i64(&bytes_arg(start)?)?,
+        parse_i64(&bytes_arg(stop)?)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{exec, Command};
+    use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, RespNullArray, SimpleError};
+    use anyhow::Result;
+    use std::future::pending;
+
+    fn bulks(items: &[&str]) -> RespFrame {
+        let items = items
+            .iter()
+            .map(|item| BulkString::new(*item).into())
+            .collect::<Vec<RespFrame>>();
+        RespArray::new(items).into()
+    }
+
+    #[test]
+    fn test_push_pop_commands() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["rpushx", "list", "a"]), 0.into());
+        assert_eq!(exec(&backend, &["lpush", "list", "b", "a"]), 2.into());
+        assert_eq!(exec(&backend, &["rpush", "list", "c", "d"]), 4.into());
+        assert_eq!(exec(&backend, &["lpushx", "list", "z"]), 5.into());
+        assert_eq!(
+            exec(&backend, &["lrange", "list", "0", "-1"]),
+            bulks(&["z", "a", "b", "c", "d"])
+        );
+        assert_eq!(exec(&backend, &["llen", "list"]), 5.into());
+
+        assert_eq!(
+            exec(&backend, &["lpop", "list"]),
+            BulkString::new("z").into()
+        );
+        assert_eq!(exec(&backend, &["rpop", "list", "2"]), bulks(&["d", "c"]));
+        assert_eq!(exec(&backend, &["lpop", "list", "0"]), bulks(&[]));
+        assert_eq!(exec(&backend, &["lpop", "list", "5"]), bulks(&["a", "b"]));
+        assert_eq!(exec(&backend, &["lpop", "list"]), RespFrame::Null(RespNull));
+        assert_eq!(exec(&backend, &["lpop", "list", "1"]), RespNullArray.into());
+        assert_eq!(
+            exec(&backend, &["lpop", "list", "-1"]),
+            SimpleError::new("ERR value is out of range, must be positive").into()
+        );
+
+        exec(&backend, &["set", "str", "x"]);
+        assert_eq!(
+            exec(&backend, &["lpush", "str", "a"]),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+    }
+
+    #[test]
+    fn test_list_edit_commands() {
+        let backend = Backend::new();
+        exec(&backend, &["rpush", "list", "a", "b", "c", "b"]);
+
+        assert_eq!(
+            exec(&backend, &["lindex", "list", "-1"]),
+            BulkString::new("b").into()
+        );
+        assert_eq!(
+            exec(&backend, &["lindex", "list", "9"]),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            exec(&backend, &["lset", "list", "0", "x"]),
+            crate::cmd::RESP_OK.clone()
+        );
+        assert_eq!(
+            exec(&backend, &["lset", "list", "9", "x"]),
+            SimpleError::new("ERR index out of range").into()
+        );
+        assert_eq!(
+            exec(&backend, &["lset", "nope", "0", "x"]),
+            SimpleError::new("ERR no such key").into()
+        );
+        assert_eq!(
+            exec(&backend, &["linsert", "list", "AFTER", "c", "d"]),
+            5.into()
+        );
+        assert_eq!(
+            exec(&backend, &["linsert", "list", "before", "q", "d"]),
+            (-1).into()
+        );
+        assert_eq!(
+            exec(&backend, &["linsert", "nope", "before", "q", "d"]),
+            0.into()
+        );
+        assert_eq!(
+            exec(&backend, &["linsert", "list", "middle", "c", "d"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(exec(&backend, &["lrem", "list", "0", "b"]), 2.into());
+        assert_eq!(
+            exec(&backend, &["lrange", "list", "0", "-1"]),
+            bulks(&["x", "c", "d"])
+        );
+        assert_eq!(
+            exec(&backend, &["ltrim", "list", "1", "1"]),
+            crate::cmd::RESP_OK.clone()
+        );
+        assert_eq!(
+            exec(&backend, &["lrange", "list", "0", "-1"]),
+            bulks(&["c"])
+        );
+    }
+
+    #[test]
+    fn test_lpos_lmove_commands() {
+        let backend = Backend::new();
+        exec(&backend, &["rpush", "list", "a", "b", "c", "b"]);
+
+        assert_eq!(exec(&backend, &["lpos", "list", "b"]), 1.into());
+        assert_eq!(
+            exec(&backend, &["lpos", "list", "b", "rank", "-1"]),
+            3.into()
+        );
+        assert_eq!(
+            exec(&backend, &["lpos", "list", "z"]),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            exec(&backend, &["lpos", "list", "b", "count", "0"]),
+            RespArray::new(vec![1.into(), 3.into()]).into()
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["lpos", "list", "b", "count", "0", "maxlen", "2"]
+            ),
+            RespArray::new(vec![1.into()]).into()
+        );
+        assert!(matches!(
+            exec(&backend, &["lpos", "list", "b", "rank", "0"]),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            exec(&backend, &["lpos", "list", "b", "count", "-1"]),
+            SimpleError::new("ERR COUNT can't be negative").into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["lmove", "list", "other", "left", "RIGHT"]),
+            BulkString::new("a").into()
+        );
+        assert_eq!(
+            exec(&backend, &["lrange", "other", "0", "-1"]),
+            bulks(&["a"])
+        );
+        assert_eq!(
+            exec(&backend, &["lmove", "nope", "other", "left", "right"]),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            exec(&backend, &["lmove", "list", "other", "up", "right"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+    }
+
+    #[test]
+    fn test_blocking_commands_without_waiting() {
+        let backend = Backend::new();
+        exec(&backend, &["rpush", "b", "1", "2", "3"]);
+
+        // 直接执行的时候不会等待
+        assert_eq!(
+            exec(&backend, &["blpop", "a", "b", "0"]),
+            bulks(&["b", "1"])
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["blmpop", "0", "2", "a", "b", "right", "count", "5"]
+            ),
+            RespArray::new(vec![BulkString::new("b").into(), bulks(&["3", "2"])]).into()
+        );
+        assert_eq!(
+            exec(&backend, &["brpop", "a", "b", "0"]),
+            RespNullArray.into()
+        );
+        assert_eq!(
+            exec(&backend, &["blmove", "a", "b", "left", "left", "0"]),
+            RespNullArray.into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["blpop", "a", "-1"]),
+            SimpleError::new("ERR timeout is negative").into()
+        );
+        assert_eq!(
+            exec(&backend, &["blpop", "a", "soon"]),
+            SimpleError::new("ERR timeout is not a float or out of range").into()
+        );
+        assert_eq!(
+            exec(&backend, &["blmpop", "0", "0", "a", "left"]),
+            SimpleError::new("ERR numkeys should be greater than 0").into()
+        );
+        assert_eq!(
+            exec(&backend, &["blmpop", "0", "1", "a", "left", "count", "0"]),
+            SimpleError::new("ERR count should be greater than 0").into()
+        );
+        assert_eq!(
+            exec(&backend, &["blmpop", "0", "2", "a", "left"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocking_commands_wait() -> Result<()> {
+        let backend = Backend::new();
+        let command = |args: &[&str]| {
+            let frames = args
+                .iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>();
+            Command::try_from(RespArray::new(frames))
+        };
+
+        let cmd = command(&["blpop", "q", "0.01"])?;
+        assert_eq!(
+            cmd.execute_async(&backend, pending()).await,
+            RespNullArray.into()
+        );
+
+        let waiter = {
+            let (backend, cmd) = (
+                backend.clone(),
+                command(&["blmove", "q", "done", "left", "right", "0"])?,
+            );
+            tokio::spawn(async move { cmd.execute_async(&backend, pending()).await })
+        };
+        while backend.blocked_clients() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(exec(&backend, &["rpush", "q", "job"]), 1.into());
+        assert_eq!(waiter.await?, BulkString::new("job").into());
+        assert_eq!(exec(&backend, &["llen", "q"]), 0.into());
+        assert_eq!(
+            exec(&backend, &["lrange", "done", "0", "-1"]),
+            bulks(&["job"])
+        );
+        Ok(())
+    }
+}
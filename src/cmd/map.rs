@@ -1,20 +1,146 @@
 use crate::cmd::{
-    extract_args, validate_command, CommandError, CommandExecutor, Get, Set, RESP_OK,
+    bytes_arg, command_name, extract_args, parse_expire_at, parse_f64, parse_i64, syntax_error,
+    validate_command, wrong_args, Append, CommandError, CommandExecutor, Get, GetDel, GetEx,
+    GetRange, GetSet, IncrBy, IncrByFloat, MGet, MSet, Set, SetRange, StrLen, RESP_OK,
 };
-use crate::{Backend, RespArray, RespFrame, RespNull};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, SetExpire, SetOptions};
 
 impl CommandExecutor for Get {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend
-            .get(&self.key)
-            .unwrap_or_else(|| RespFrame::Null(RespNull))
+        match backend.get(&self.key) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
     }
 }
 
 impl CommandExecutor for Set {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.set(self.key, self.value);
-        RESP_OK.clone()
+        match backend.set_with(self.key, self.value, self.options) {
+            // 带 GET 选项时回复旧的值，不管有没有设置成功
+            Ok((_, old)) if self.options.get => match old {
+                Some(old) => BulkString::from(old).into(),
+                None => RespFrame::Null(RespNull),
+            },
+            Ok((true, _)) => RESP_OK.clone(),
+            Ok((false, _)) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for IncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.incr_by(self.key, self.delta) {
+            Ok(value) => value.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for IncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.incr_by_float(self.key, self.delta) {
+            Ok(value) => BulkString::from(value).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.append(self.key, self.value) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for StrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.strlen(&self.key) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.getrange(&self.key, self.start, self.end) {
+            Ok(value) => BulkString::from(value).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.setrange(self.key, self.offset, self.value) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GetDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.getdel(self.key) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.getex(self.key, self.expire) {
+            Ok(Some(value)) => BulkString::from(value).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// GETSET 等价于 SET key value GET
+impl CommandExecutor for GetSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let options = SetOptions {
+            get: true,
+            ..Default::default()
+        };
+        match backend.set_with(self.key, self.value, options) {
+            Ok((_, Some(old))) => BulkString::from(old).into(),
+            Ok((_, None)) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let values = backend
+            .mget(&self.keys)
+            .into_iter()
+            .map(|value| match value {
+                Some(value) => BulkString::from(value).into(),
+                None => RespFrame::Null(RespNull),
+            })
+            .collect::<Vec<_>>();
+        RespArray::new(values).into()
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.nx {
+            (backend.msetnx(self.pairs) as i64).into()
+        } else {
+            backend.mset(self.pairs);
+            RESP_OK.clone()
+        }
     }
 }
 
@@ -31,37 +157,320 @@ impl TryFrom<RespArray> for Get {
 
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
+                key: key.into_bytes(),
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
 
+// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds |
+//   EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
 impl TryFrom<RespArray> for Set {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+
+        let (Some(key), Some(value)) = (args.next(), args.next()) else {
+            return Err(wrong_args("set"));
+        };
+        let (key, value) = (bytes_arg(key)?, bytes_arg(value)?);
+
+        let mut options = SetOptions::default();
+        let mut expire_opt = None;
+        while let Some(opt) = args.next() {
+            let opt = bytes_arg(opt)?.to_ascii_lowercase();
+            if let Some(expire) = parse_expire_option(
+                &opt,
+                &mut args,
+                &mut expire_opt,
+                (b"keepttl", SetExpire::KeepTtl),
+                "set",
+            )? {
+                options.expire = expire;
+                continue;
+            }
+
+            match opt.as_slice() {
+                b"nx" if !options.xx => options.nx = true,
+                b"xx" if !options.nx => options.xx = true,
+                b"get" => options.get = true,
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        Ok(Set {
+            key,
+            value,
+            options,
+        })
+    }
+}
+
+// SET 和 GETEX 共用的过期选项解析，opt 已经转成小写，不是过期选项时返回 None
+// 同一个过期选项可以重复出现，以最后一个为准，不同的过期选项不能同时出现
+// flag 是不带参数的那个选项，SET 是 KEEPTTL，GETEX 是 PERSIST
+fn parse_expire_option(
+    opt: &[u8],
+    args: &mut impl Iterator<Item = RespFrame>,
+    seen: &mut Option<Vec<u8>>,
+    (flag, flag_expire): (&[u8], SetExpire),
+    name: &str,
+) -> Result<Option<SetExpire>, CommandError> {
+    let is_expire = matches!(opt, b"ex" | b"px" | b"exat" | b"pxat") || opt == flag;
+    if !is_expire {
+        return Ok(None);
+    }
+    if seen.as_deref().is_some_and(|prev| prev != opt) {
+        return Err(syntax_error());
+    }
+    *seen = Some(opt.to_vec());
+
+    if opt == flag {
+        Ok(Some(flag_expire))
+    } else {
+        Ok(Some(SetExpire::At(parse_expire_at(
+            opt,
+            args.next(),
+            name,
+        )?)))
+    }
+}
+
+// INCR key, DECR key, INCRBY key increment, DECRBY key decrement
+impl TryFrom<RespArray> for IncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?.into_iter();
+
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args(&name))?)?;
+        let delta = match (name.as_str(), args.next(), args.next()) {
+            ("incr", None, None) => 1,
+            ("decr", None, None) => -1,
+            ("incrby", Some(delta), None) => parse_i64(&bytes_arg(delta)?)?,
+            ("decrby", Some(delta), None) => parse_i64(&bytes_arg(delta)?)?
+                .checked_neg()
+                .ok_or_else(|| {
+                    CommandError::InvalidArgument("decrement would overflow".to_string())
+                })?,
+            _ => return Err(wrong_args(&name)),
+        };
+
+        Ok(IncrBy { key, delta })
+    }
+}
+
+// INCRBYFLOAT key increment
+impl TryFrom<RespArray> for IncrByFloat {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrbyfloat"], 2)?;
 
         let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(delta)) => Ok(IncrByFloat {
+                key: bytes_arg(key)?,
+                delta: parse_f64(&bytes_arg(delta)?)?,
+            }),
+            _ => Err(wrong_args("incrbyfloat")),
+        }
+    }
+}
 
+// APPEND key value
+impl TryFrom<RespArray> for Append {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["append"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
-                key: String::from_utf8(key.0)?,
-                value,
+            (Some(key), Some(value)) => Ok(Append {
+                key: bytes_arg(key)?,
+                value: bytes_arg(value)?,
+            }),
+            _ => Err(wrong_args("append")),
+        }
+    }
+}
+
+// STRLEN key
+impl TryFrom<RespArray> for StrLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["strlen"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(StrLen {
+                key: bytes_arg(key)?,
             }),
-            _ => Err(CommandError::InvalidArgument(
-                "Invalid key or value".to_string(),
-            )),
+            _ => Err(wrong_args("strlen")),
         }
     }
 }
 
+// GETRANGE key start end
+impl TryFrom<RespArray> for GetRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getrange"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(key), Some(start), Some(end)) => Ok(GetRange {
+                key: bytes_arg(key)?,
+                start: parse_i64(&bytes_arg(start)?)?,
+                end: parse_i64(&bytes_arg(end)?)?,
+            }),
+            _ => Err(wrong_args("getrange")),
+        }
+    }
+}
+
+// SETRANGE key offset value
+impl TryFrom<RespArray> for SetRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setrange"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(offset), Some(value)) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args("setrange"));
+        };
+
+        let offset = parse_i64(&bytes_arg(offset)?)?;
+        if offset < 0 {
+            return Err(CommandError::InvalidArgument(
+                "offset is out of range".to_string(),
+            ));
+        }
+
+        Ok(SetRange {
+            key: bytes_arg(key)?,
+            offset: offset as usize,
+            value: bytes_arg(value)?,
+        })
+    }
+}
+
+// GETDEL key
+impl TryFrom<RespArray> for GetDel {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getdel"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(GetDel {
+                key: bytes_arg(key)?,
+            }),
+            _ => Err(wrong_args("getdel")),
+        }
+    }
+}
+
+// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | PERSIST]
+impl TryFrom<RespArray> for GetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("getex"))?)?;
+
+        let mut expire = SetExpire::KeepTtl;
+        let mut expire_opt = None;
+        while let Some(opt) = args.next() {
+            let opt = bytes_arg(opt)?.to_ascii_lowercase();
+            expire = parse_expire_option(
+                &opt,
+                &mut args,
+                &mut expire_opt,
+                (b"persist", SetExpire::Clear),
+                "getex",
+            )?
+            .ok_or_else(syntax_error)?;
+        }
+
+        Ok(GetEx { key, expire })
+    }
+}
+
+// GETSET key value
+impl TryFrom<RespArray> for GetSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getset"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(value)) => Ok(GetSet {
+                key: bytes_arg(key)?,
+                value: bytes_arg(value)?,
+            }),
+            _ => Err(wrong_args("getset")),
+        }
+    }
+}
+
+// MGET key [key ...]
+impl TryFrom<RespArray> for MGet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(wrong_args("mget"));
+        }
+
+        Ok(MGet { keys })
+    }
+}
+
+// MSET key value [key value ...], MSETNX key value [key value ...]
+impl TryFrom<RespArray> for MSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let args = extract_args(value, 1)?;
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(wrong_args(&name));
+        }
+
+        let mut pairs = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let (Some(key), Some(value)) = (args.next(), args.next()) {
+            pairs.push((bytes_arg(key)?, bytes_arg(value)?));
+        }
+
+        Ok(MSet {
+            pairs,
+            nx: name == "msetnx",
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::cmd::{CommandExecutor, Get, Set, RESP_OK};
-    use crate::{Backend, RespArray, RespDecode, RespFrame};
+    use crate::cmd::{exec, CommandExecutor, Get, Set, RESP_OK};
+    use crate::{
+        Backend, BulkString, RespArray, RespDecode, RespFrame, RespNull, SimpleError, SimpleString,
+    };
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -92,7 +501,7 @@ mod tests {
 
         assert_eq!(result.key, "hello");
         // into 方法在 From<&[u8]> 中实现
-        assert_eq!(result.value, RespFrame::BulkString(b"world".into()));
+        assert_eq!(result.value, "world");
 
         Ok(())
     }
@@ -102,8 +511,9 @@ mod tests {
         let backend = Backend::new();
 
         let cmd = Set {
-            key: "hello".to_string(),
-            value: RespFrame::BulkString(b"world".into()),
+            key: "hello".into(),
+            value: "world".into(),
+            options: Default::default(),
         };
 
         let result = cmd.execute(&backend);
@@ -111,7 +521,7 @@ mod tests {
         assert_eq!(result, RESP_OK.clone());
 
         let cmd = Get {
-            key: "hello".to_string(),
+            key: "hello".into(),
         };
 
         let result = cmd.execute(&backend);
@@ -119,4 +529,315 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_binary_key() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$4\r\n\xff\x00\r\n\r\n$5\r\nworld\r\n");
+        buf.extend_from_slice(b"*2\r\n$3\r\nget\r\n$4\r\n\xff\x00\r\n\r\n");
+
+        let backend = Backend::new();
+
+        let cmd: Set = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.key, &b"\xff\x00\r\n"[..]);
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        let cmd: Get = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::BulkString(b"world".into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_options() {
+        let backend = Backend::new();
+        let null = RespFrame::Null(RespNull);
+
+        assert_eq!(exec(&backend, &["set", "lock", "a", "XX"]), null);
+        assert_eq!(
+            exec(&backend, &["set", "lock", "a", "NX", "PX", "30000"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(exec(&backend, &["set", "lock", "b", "nx"]), null);
+        let RespFrame::Integer(pttl) = exec(&backend, &["pttl", "lock"]) else {
+            panic!("expect an integer");
+        };
+        assert!((29_000..=30_000).contains(&pttl));
+
+        // GET 回复旧的值，KEEPTTL 保留过期时间
+        assert_eq!(
+            exec(&backend, &["set", "lock", "c", "get", "keepttl"]),
+            BulkString::new("a").into()
+        );
+        assert!(exec(&backend, &["pttl", "lock"]) != (-1).into());
+        assert_eq!(
+            exec(&backend, &["set", "lock", "d", "nx", "get"]),
+            BulkString::new("c").into()
+        );
+        assert_eq!(exec(&backend, &["set", "new", "d", "get"]), null);
+
+        assert_eq!(
+            exec(&backend, &["set", "lock", "e", "exat", "4102444800"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            exec(&backend, &["expiretime", "lock"]),
+            4_102_444_800.into()
+        );
+        exec(&backend, &["set", "lock", "f"]);
+        assert_eq!(exec(&backend, &["ttl", "lock"]), (-1).into());
+    }
+
+    #[test]
+    fn test_set_option_errors() {
+        let backend = Backend::new();
+        let syntax_error: RespFrame = SimpleError::new("ERR syntax error").into();
+
+        assert_eq!(exec(&backend, &["set", "k", "v", "nx", "xx"]), syntax_error);
+        assert_eq!(
+            exec(&backend, &["set", "k", "v", "ex", "10", "px", "10"]),
+            syntax_error
+        );
+        assert_eq!(
+            exec(&backend, &["set", "k", "v", "keepttl", "ex", "10"]),
+            syntax_error
+        );
+        assert_eq!(exec(&backend, &["set", "k", "v", "ex"]), syntax_error);
+        assert_eq!(exec(&backend, &["set", "k", "v", "foo"]), syntax_error);
+        assert_eq!(
+            exec(&backend, &["set", "k", "v", "ex", "0"]),
+            SimpleError::new("ERR invalid expire time in 'set' command").into()
+        );
+        assert_eq!(
+            exec(&backend, &["set", "k", "v", "ex", "abc"]),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+        // 同一个过期选项重复出现时以最后一个为准
+        assert_eq!(
+            exec(&backend, &["set", "k", "v", "ex", "10", "ex", "20"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(exec(&backend, &["ttl", "k"]), 20.into());
+
+        exec(&backend, &["hset", "hash", "f", "v"]);
+        assert_eq!(
+            exec(&backend, &["set", "hash", "v", "get"]),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+    }
+
+    #[test]
+    fn test_incr_decr() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["incr", "counter"]), 1.into());
+        assert_eq!(exec(&backend, &["incrby", "counter", "10"]), 11.into());
+        assert_eq!(exec(&backend, &["decr", "counter"]), 10.into());
+        assert_eq!(exec(&backend, &["decrby", "counter", "-5"]), 15.into());
+        assert_eq!(
+            exec(&backend, &["get", "counter"]),
+            BulkString::new("15").into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["decrby", "counter", "-9223372036854775808"]),
+            SimpleError::new("ERR decrement would overflow").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrby", "counter", "9223372036854775807"]),
+            SimpleError::new("ERR increment or decrement would overflow").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrby", "counter", "1.5"]),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+
+        exec(&backend, &["set", "text", "hello"]);
+        assert_eq!(
+            exec(&backend, &["incr", "text"]),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+
+        // 过期时间保持不变
+        exec(&backend, &["expire", "counter", "100"]);
+        exec(&backend, &["incr", "counter"]);
+        assert_eq!(exec(&backend, &["ttl", "counter"]), 100.into());
+    }
+
+    #[test]
+    fn test_incr_by_float() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            exec(&backend, &["incrbyfloat", "key", "10.5"]),
+            BulkString::new("10.5").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrbyfloat", "key", "0.1"]),
+            BulkString::new("10.6").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrbyfloat", "key", "-10.6"]),
+            BulkString::new("0").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrbyfloat", "key", "abc"]),
+            SimpleError::new("ERR value is not a valid float").into()
+        );
+        assert_eq!(
+            exec(&backend, &["incrbyfloat", "key", "inf"]),
+            SimpleError::new("ERR increment would produce NaN or Infinity").into()
+        );
+    }
+
+    #[test]
+    fn test_string_commands() {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["append", "key", "Hello"]), 5.into());
+        assert_eq!(exec(&backend, &["append", "key", " World"]), 11.into());
+        assert_eq!(exec(&backend, &["strlen", "key"]), 11.into());
+        assert_eq!(exec(&backend, &["strlen", "missing"]), 0.into());
+
+        assert_eq!(
+            exec(&backend, &["getrange", "key", "-5", "-1"]),
+            BulkString::new("World").into()
+        );
+        assert_eq!(
+            exec(&backend, &["setrange", "key", "6", "Redis"]),
+            11.into()
+        );
+        assert_eq!(
+            exec(&backend, &["get", "key"]),
+            BulkString::new("Hello Redis").into()
+        );
+        assert_eq!(exec(&backend, &["setrange", "pad", "2", "x"]), 3.into());
+        assert_eq!(
+            exec(&backend, &["get", "pad"]),
+            BulkString::new("\0\0x").into()
+        );
+        assert_eq!(
+            exec(&backend, &["setrange", "pad", "-1", "x"]),
+            SimpleError::new("ERR offset is out of range").into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["getset", "key", "new"]),
+            BulkString::new("Hello Redis").into()
+        );
+        assert_eq!(
+            exec(&backend, &["getdel", "key"]),
+            BulkString::new("new").into()
+        );
+        assert_eq!(
+            exec(&backend, &["getdel", "key"]),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            exec(&backend, &["getset", "key", "v"]),
+            RespFrame::Null(RespNull)
+        );
+
+        exec(&backend, &["hset", "hash", "f", "v"]);
+        assert_eq!(
+            exec(&backend, &["getdel", "hash"]),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+        assert_eq!(
+            exec(&backend, &["type", "hash"]),
+            SimpleString::new("hash").into()
+        );
+    }
+
+    #[test]
+    fn test_getex() {
+        let backend = Backend::new();
+        exec(&backend, &["set", "key", "value"]);
+
+        assert_eq!(
+            exec(&backend, &["getex", "key", "ex", "100"]),
+            BulkString::new("value").into()
+        );
+        assert_eq!(exec(&backend, &["ttl", "key"]), 100.into());
+
+        exec(&backend, &["getex", "key"]);
+        assert_eq!(exec(&backend, &["ttl", "key"]), 100.into());
+
+        exec(&backend, &["getex", "key", "persist"]);
+        assert_eq!(exec(&backend, &["ttl", "key"]), (-1).into());
+
+        assert_eq!(
+            exec(&backend, &["getex", "key", "ex", "10", "persist"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+
+        // 和 SET 一样，重复的同一个选项以最后一个为准
+        exec(&backend, &["getex", "key", "ex", "10", "ex", "20"]);
+        assert_eq!(exec(&backend, &["ttl", "key"]), 20.into());
+        exec(&backend, &["getex", "key", "persist", "persist"]);
+        assert_eq!(exec(&backend, &["ttl", "key"]), (-1).into());
+        assert_eq!(
+            exec(&backend, &["getex", "key", "ex", "10", "px", "20"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(
+            exec(&backend, &["getex", "key", "px", "-1"]),
+            SimpleError::new("ERR invalid expire time in 'getex' command").into()
+        );
+        assert_eq!(
+            exec(&backend, &["getex", "missing"]),
+            RespFrame::Null(RespNull)
+        );
+    }
+
+    #[test]
+    fn test_mget_mset() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            exec(&backend, &["mset", "a", "1", "b", "2"]),
+            RESP_OK.clone()
+        );
+        exec(&backend, &["hset", "hash", "f", "v"]);
+        assert_eq!(
+            exec(&backend, &["mget", "a", "missing", "hash", "b"]),
+            RespArray::new([
+                BulkString::new("1").into(),
+                RespFrame::Null(RespNull),
+                RespFrame::Null(RespNull),
+                BulkString::new("2").into(),
+            ])
+            .into()
+        );
+
+        // 有一个 key 存在就什么都不设置
+        assert_eq!(exec(&backend, &["msetnx", "c", "3", "a", "x"]), 0.into());
+        assert_eq!(exec(&backend, &["get", "c"]), RespFrame::Null(RespNull));
+        assert_eq!(exec(&backend, &["get", "a"]), BulkString::new("1").into());
+        assert_eq!(exec(&backend, &["msetnx", "c", "3", "d", "4"]), 1.into());
+        assert_eq!(exec(&backend, &["get", "d"]), BulkString::new("4").into());
+
+        // MSET 覆盖其他类型并清除过期时间
+        exec(&backend, &["expire", "a", "100"]);
+        exec(&backend, &["mset", "a", "x", "hash", "y"]);
+        assert_eq!(exec(&backend, &["ttl", "a"]), (-1).into());
+        assert_eq!(
+            exec(&backend, &["get", "hash"]),
+            BulkString::new("y").into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["mset", "a", "1", "b"]),
+            SimpleError::new("ERR wrong number of arguments for 'mset' command").into()
+        );
+        assert_eq!(
+            exec(&backend, &["msetnx", "a"]),
+            SimpleError::new("ERR wrong number of arguments for 'msetnx' command").into()
+        );
+    }
 }
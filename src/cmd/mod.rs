@@ -1,10 +1,28 @@
-use crate::{Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    now_ms, Aggregate, Backend, BitFieldOp, BitOperation, BitRange, BulkString, ExpireCondition,
+    ListEnd, RespArray, RespError, RespFrame, RespProtocol, ScanOptions, ScoreBound, SetExpire,
+    SetOperation, SetOptions, SimpleError, SimpleString, ZAddOptions, ZEnd, ZRangeSpec,
+};
+use bytes::Bytes;
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
+use std::future::Future;
+use std::time::Duration;
 use thiserror::Error;
 
+mod bitmap;
+mod connection;
 mod hmap;
+mod keyspace;
+mod list;
 mod map;
+mod registry;
+mod server;
+mod set;
+mod zset;
+
+pub use connection::Session;
+pub use registry::{lookup_command, CommandSpec};
 
 // 宏的作用是定义一个静态变量，并且保证这个变量在第一次被使用的时候才会被初始化
 // 可以确保在多线程环境下，变量只会被初始化一次，从而避免了竞态条件的发生
@@ -15,12 +33,24 @@ lazy_static! {
 
 #[derive(Error, Debug)]
 pub enum CommandError {
-    #[error("Invalid command: {0}")]
+    #[error("{0}")]
     InvalidCommand(String),
 
-    #[error("Invalid argument: {0}")]
+    #[error("{0}")]
     InvalidArgument(String),
 
+    #[error("Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    #[error("No matching script. Please use EVAL.")]
+    NoScript,
+
+    #[error("unsupported protocol version")]
+    NoProto,
+
+    #[error("invalid username-password pair or user is disabled.")]
+    WrongPass,
+
     #[error("{0}")]
     RespError(#[from] RespError),
 
@@ -31,11 +61,34 @@ pub enum CommandError {
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+impl CommandError {
+    /// 错误回复的前缀，客户端库会根据前缀区分错误类型，比如 -WRONGTYPE、-NOSCRIPT
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::WrongType => "WRONGTYPE",
+            CommandError::NoScript => "NOSCRIPT",
+            CommandError::NoProto => "NOPROTO",
+            CommandError::WrongPass => "WRONGPASS",
+            _ => "ERR",
+        }
+    }
+}
+
+// 命令出错时不断开连接，而是回复一个 "-ERR ..." 这样的 SimpleError
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        // simple error 中不能出现 \r\n，否则会破坏协议
+        let msg = e.to_string().replace(['\r', '\n'], " ");
+        SimpleError::new(format!("{} {}", e.code(), msg)).into()
+    }
+}
+
 #[enum_dispatch]
 pub trait CommandExecutor {
     fn execute(self, backend: &Backend) -> RespFrame;
 }
 
+// 阻塞命令的 execute 不会等待，没有数据时和超时一样回复，等待只能通过 Command::execute_async
 #[derive(Debug)]
 #[enum_dispatch(CommandExecutor)]
 pub enum Command {
@@ -48,36 +101,771 @@ pub enum Command {
     HSet(HSet),
 
     HGetAll(HGetAll),
+
+    CommandInfo(CommandInfo),
+
+    Hello(Hello),
+
+    Ping(Ping),
+
+    Type(Type),
+
+    Expire(Expire),
+
+    Ttl(Ttl),
+
+    Persist(Persist),
+
+    IncrBy(IncrBy),
+
+    IncrByFloat(IncrByFloat),
+
+    Append(Append),
+
+    StrLen(StrLen),
+
+    GetRange(GetRange),
+
+    SetRange(SetRange),
+
+    GetDel(GetDel),
+
+    GetEx(GetEx),
+
+    GetSet(GetSet),
+
+    MGet(MGet),
+
+    MSet(MSet),
+
+    SetBit(SetBit),
+
+    GetBit(GetBit),
+
+    BitCount(BitCount),
+
+    BitPos(BitPos),
+
+    BitOp(BitOp),
+
+    BitField(BitField),
+
+    HSetNx(HSetNx),
+
+    HDel(HDel),
+
+    HExists(HExists),
+
+    HLen(HLen),
+
+    HKeys(HKeys),
+
+    HMGet(HMGet),
+
+    HIncrBy(HIncrBy),
+
+    HIncrByFloat(HIncrByFloat),
+
+    HStrLen(HStrLen),
+
+    HRandField(HRandField),
+
+    HScan(HScan),
+
+    HExpire(HExpire),
+
+    HTtl(HTtl),
+
+    HPersist(HPersist),
+
+    HGetEx(HGetEx),
+
+    HSetEx(HSetEx),
+
+    Push(Push),
+
+    Pop(Pop),
+
+    LLen(LLen),
+
+    LRange(LRange),
+
+    LIndex(LIndex),
+
+    LSet(LSet),
+
+    LInsert(LInsert),
+
+    LRem(LRem),
+
+    LTrim(LTrim),
+
+    LPos(LPos),
+
+    LMove(LMove),
+
+    BPop(BPop),
+
+    BLMove(BLMove),
+
+    SAdd(SAdd),
+
+    SRem(SRem),
+
+    SIsMember(SIsMember),
+
+    SMIsMember(SMIsMember),
+
+    SCard(SCard),
+
+    SMembers(SMembers),
+
+    SPop(SPop),
+
+    SRandMember(SRandMember),
+
+    SMove(SMove),
+
+    SOp(SOp),
+
+    SInterCard(SInterCard),
+
+    SScan(SScan),
+
+    ZAdd(ZAdd),
+
+    ZIncrBy(ZIncrBy),
+
+    ZRem(ZRem),
+
+    ZScore(ZScore),
+
+    ZMScore(ZMScore),
+
+    ZCard(ZCard),
+
+    ZCount(ZCount),
+
+    ZRank(ZRank),
+
+    ZRange(ZRange),
+
+    ZRangeStore(ZRangeStore),
+
+    ZPop(ZPop),
+
+    ZOpStore(ZOpStore),
+
+    BZPop(BZPop),
+}
+
+impl Command {
+    /// 和 execute 一样，但是阻塞命令会等待其他客户端写入数据，直到超时或者 closed 完成
+    /// closed 表示客户端断开了连接，这时候和超时一样回复，已经为客户端取走的数据仍然会回复
+    pub async fn execute_async(
+        self,
+        backend: &Backend,
+        closed: impl Future<Output = ()>,
+    ) -> RespFrame {
+        match self {
+            Command::BPop(cmd) => cmd.wait(backend, closed).await,
+            Command::BLMove(cmd) => cmd.wait(backend, closed).await,
+            Command::BZPop(cmd) => cmd.wait(backend, closed).await,
+            cmd => cmd.execute(backend),
+        }
+    }
+}
+
+// 阻塞命令等到超时或者客户端断开连接为止，timeout 为 None 时一直等到断开连接
+async fn wait_until(timeout: Option<Duration>, closed: impl Future<Output = ()>) {
+    match timeout {
+        Some(timeout) => {
+            tokio::select! {
+                _ = tokio::time::sleep(timeout) => {}
+                _ = closed => {}
+            }
+        }
+        None => closed.await,
+    }
 }
 
 #[derive(Debug)]
 pub struct Get {
-    key: String,
+    key: Bytes,
 }
 
 #[derive(Debug)]
 pub struct Set {
-    key: String,
-    value: RespFrame,
+    key: Bytes,
+    value: Bytes,
+    options: SetOptions,
+}
+
+// INCR、DECR、INCRBY、DECRBY 解析之后都是加上一个 delta
+#[derive(Debug)]
+pub struct IncrBy {
+    key: Bytes,
+    delta: i64,
+}
+
+#[derive(Debug)]
+pub struct IncrByFloat {
+    key: Bytes,
+    delta: f64,
+}
+
+#[derive(Debug)]
+pub struct Append {
+    key: Bytes,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct StrLen {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetRange {
+    key: Bytes,
+    start: i64,
+    end: i64,
+}
+
+#[derive(Debug)]
+pub struct SetRange {
+    key: Bytes,
+    offset: usize,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetDel {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetEx {
+    key: Bytes,
+    expire: SetExpire,
+}
+
+#[derive(Debug)]
+pub struct GetSet {
+    key: Bytes,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct MGet {
+    keys: Vec<Bytes>,
+}
+
+// MSET 和 MSETNX 的区别只在于是否要求所有 key 都不存在
+#[derive(Debug)]
+pub struct MSet {
+    pairs: Vec<(Bytes, Bytes)>,
+    nx: bool,
+}
+
+#[derive(Debug)]
+pub struct SetBit {
+    key: Bytes,
+    offset: u64,
+    on: bool,
+}
+
+#[derive(Debug)]
+pub struct GetBit {
+    key: Bytes,
+    offset: u64,
+}
+
+#[derive(Debug)]
+pub struct BitCount {
+    key: Bytes,
+    range: Option<BitRange>,
+}
+
+#[derive(Debug)]
+pub struct BitPos {
+    key: Bytes,
+    bit: bool,
+    range: Option<BitRange>,
+}
+
+#[derive(Debug)]
+pub struct BitOp {
+    op: BitOperation,
+    dest: Bytes,
+    keys: Vec<Bytes>,
+}
+
+// BITFIELD 和 BITFIELD_RO，只读的版本解析时就拒绝 GET 以外的子命令
+#[derive(Debug)]
+pub struct BitField {
+    key: Bytes,
+    ops: Vec<BitFieldOp>,
 }
 
 #[derive(Debug)]
 pub struct HGet {
-    key: String,
-    field: String,
+    key: Bytes,
+    field: Bytes,
 }
 
+// HMSET 和 HSET 一样，只是回复 OK 而不是新加入的 field 个数
 #[derive(Debug)]
 pub struct HSet {
-    key: String,
-    field: String,
-    value: RespFrame,
+    key: Bytes,
+    fields: Vec<(Bytes, Bytes)>,
+    hmset: bool,
+}
+
+#[derive(Debug)]
+pub struct HSetNx {
+    key: Bytes,
+    field: Bytes,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HDel {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct HExists {
+    key: Bytes,
+    field: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HLen {
+    key: Bytes,
+}
+
+// HKEYS 和 HVALS
+#[derive(Debug)]
+pub struct HKeys {
+    key: Bytes,
+    values: bool,
+}
+
+#[derive(Debug)]
+pub struct HMGet {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct HIncrBy {
+    key: Bytes,
+    field: Bytes,
+    delta: i64,
+}
+
+#[derive(Debug)]
+pub struct HIncrByFloat {
+    key: Bytes,
+    field: Bytes,
+    delta: f64,
+}
+
+#[derive(Debug)]
+pub struct HStrLen {
+    key: Bytes,
+    field: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HRandField {
+    key: Bytes,
+    count: Option<i64>,
+    with_values: bool,
+}
+
+// HEXPIRE、HPEXPIRE、HEXPIREAT、HPEXPIREAT 解析之后都是一个毫秒时间戳
+#[derive(Debug)]
+pub struct HExpire {
+    key: Bytes,
+    when: i64,
+    condition: ExpireCondition,
+    fields: Vec<Bytes>,
+}
+
+// HTTL、HPTTL、HEXPIRETIME、HPEXPIRETIME
+#[derive(Debug)]
+pub struct HTtl {
+    key: Bytes,
+    unit: TimeUnit,
+    absolute: bool,
+    fields: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct HPersist {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct HGetEx {
+    key: Bytes,
+    expire: SetExpire,
+    fields: Vec<Bytes>,
+}
+
+// FNX、FXX 对应 SetOptions 的 nx 和 xx
+#[derive(Debug)]
+pub struct HSetEx {
+    key: Bytes,
+    options: SetOptions,
+    fields: Vec<(Bytes, Bytes)>,
+}
+
+#[derive(Debug)]
+pub struct HScan {
+    key: Bytes,
+    cursor: u64,
+    options: ScanOptions,
+    no_values: bool,
 }
 
 #[derive(Debug)]
 pub struct HGetAll {
-    key: String,
-    sort: bool,
+    key: Bytes,
+}
+
+// LPUSH、RPUSH、LPUSHX、RPUSHX
+#[derive(Debug)]
+pub struct Push {
+    key: Bytes,
+    end: ListEnd,
+    values: Vec<Bytes>,
+    only_existing: bool,
+}
+
+// LPOP、RPOP，没有 COUNT 时回复一个元素，有 COUNT 时回复数组
+#[derive(Debug)]
+pub struct Pop {
+    key: Bytes,
+    end: ListEnd,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct LLen {
+    key: Bytes,
+}
+
+// LRANGE 和 LTRIM 的范围都是闭区间
+#[derive(Debug)]
+pub struct LRange {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug)]
+pub struct LIndex {
+    key: Bytes,
+    index: i64,
+}
+
+#[derive(Debug)]
+pub struct LSet {
+    key: Bytes,
+    index: i64,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct LInsert {
+    key: Bytes,
+    before: bool,
+    pivot: Bytes,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct LRem {
+    key: Bytes,
+    count: i64,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct LTrim {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+}
+
+// count 为 None 时回复第一个匹配的下标，否则回复数组
+#[derive(Debug)]
+pub struct LPos {
+    key: Bytes,
+    element: Bytes,
+    rank: i64,
+    count: Option<usize>,
+    maxlen: usize,
+}
+
+#[derive(Debug)]
+pub struct LMove {
+    source: Bytes,
+    destination: Bytes,
+    from: ListEnd,
+    to: ListEnd,
+}
+
+// BLPOP、BRPOP、BLMPOP，count 为 None 时是 BLPOP、BRPOP 的回复格式
+// timeout 为 None 表示一直等待
+#[derive(Debug)]
+pub struct BPop {
+    keys: Vec<Bytes>,
+    end: ListEnd,
+    count: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct BLMove {
+    source: Bytes,
+    destination: Bytes,
+    from: ListEnd,
+    to: ListEnd,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct SAdd {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SIsMember {
+    key: Bytes,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SMIsMember {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SCard {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SMembers {
+    key: Bytes,
+}
+
+// 没有 count 时回复一个成员，有 count 时回复集合
+#[derive(Debug)]
+pub struct SPop {
+    key: Bytes,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct SRandMember {
+    key: Bytes,
+    count: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct SMove {
+    source: Bytes,
+    destination: Bytes,
+    member: Bytes,
+}
+
+// SINTER、SUNION、SDIFF，destination 不为 None 时是对应的 STORE 命令
+#[derive(Debug)]
+pub struct SOp {
+    op: SetOperation,
+    destination: Option<Bytes>,
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SInterCard {
+    keys: Vec<Bytes>,
+    limit: usize,
+}
+
+#[derive(Debug)]
+pub struct SScan {
+    key: Bytes,
+    cursor: u64,
+    options: ScanOptions,
+}
+
+// ZADD，INCR 时只有一个成员，回复新的分数
+#[derive(Debug)]
+pub struct ZAdd {
+    key: Bytes,
+    options: ZAddOptions,
+    incr: bool,
+    members: Vec<(f64, Bytes)>,
+}
+
+#[derive(Debug)]
+pub struct ZIncrBy {
+    key: Bytes,
+    increment: f64,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct ZScore {
+    key: Bytes,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZMScore {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct ZCard {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZCount {
+    key: Bytes,
+    min: ScoreBound,
+    max: ScoreBound,
+}
+
+// ZRANK、ZREVRANK
+#[derive(Debug)]
+pub struct ZRank {
+    key: Bytes,
+    member: Bytes,
+    rev: bool,
+    with_score: bool,
+}
+
+#[derive(Debug)]
+pub struct ZRange {
+    key: Bytes,
+    spec: ZRangeSpec,
+    with_scores: bool,
+}
+
+#[derive(Debug)]
+pub struct ZRangeStore {
+    destination: Bytes,
+    source: Bytes,
+    spec: ZRangeSpec,
+}
+
+// ZPOPMIN、ZPOPMAX，和 LPOP 不同，没有 count 时也回复数组
+#[derive(Debug)]
+pub struct ZPop {
+    key: Bytes,
+    end: ZEnd,
+    count: Option<usize>,
+}
+
+// ZUNIONSTORE、ZINTERSTORE
+#[derive(Debug)]
+pub struct ZOpStore {
+    op: SetOperation,
+    destination: Bytes,
+    keys: Vec<Bytes>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+// BZPOPMIN、BZPOPMAX、BZMPOP，count 为 None 时是 BZPOPMIN、BZPOPMAX 的回复格式
+// timeout 为 None 表示一直等待
+#[derive(Debug)]
+pub struct BZPop {
+    keys: Vec<Bytes>,
+    end: ZEnd,
+    count: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct CommandInfo {
+    kind: CommandInfoKind,
+}
+
+#[derive(Debug)]
+pub enum CommandInfoKind {
+    List,
+    Count,
+    Info(Vec<String>),
+    Docs(Vec<String>),
+}
+
+#[derive(Debug)]
+pub struct Hello {
+    protocol: Option<RespProtocol>,
+    setname: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Ping {
+    message: Option<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct Type {
+    key: Bytes,
+}
+
+// EXPIRE、PEXPIRE、EXPIREAT、PEXPIREAT 解析之后都是一个毫秒时间戳
+#[derive(Debug)]
+pub struct Expire {
+    key: Bytes,
+    when: i64,
+    condition: ExpireCondition,
+}
+
+// TTL、PTTL、EXPIRETIME、PEXPIRETIME
+#[derive(Debug)]
+pub struct Ttl {
+    key: Bytes,
+    unit: TimeUnit,
+    absolute: bool,
+}
+
+#[derive(Debug)]
+pub struct Persist {
+    key: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
 }
 
 #[derive(Debug)]
@@ -90,7 +878,7 @@ impl TryFrom<RespFrame> for Command {
         match value {
             RespFrame::Array(array) => array.try_into(),
             _ => Err(CommandError::InvalidCommand(
-                "Command mut be an array".to_string(),
+                "Protocol error: command must be an array of bulk strings".to_string(),
             )),
         }
     }
@@ -101,31 +889,8 @@ impl TryFrom<RespArray> for Command {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        // "get" "hello"
-        // let x = b"get";
-        // let x1 = "get".as_bytes().as_ref();
-        // match x1 {
-        //     b"get" => println!("hello"),
-        //     _ => println!("world"),
-        // };
-
-        match value.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                b"get" => Ok(Get::try_from(value)?.into()),
-                b"set" => Ok(Set::try_from(value)?.into()),
-                b"hget" => Ok(HGet::try_from(value)?.into()),
-                b"hset" => Ok(HSet::try_from(value)?.into()),
-                b"hgetall" => Ok(HGetAll::try_from(value)?.into()),
-                _ => Err(CommandError::InvalidCommand(format!(
-                    "Invalid command: {}",
-                    String::from_utf8_lossy(cmd.as_ref())
-                ))),
-            },
-
-            _ => Err(CommandError::InvalidCommand(
-                "Command mut have a BulkString as the first argument".to_string(),
-            )),
-        }
+        // 根据命令表分发，命令名大小写不敏感，参数个数也由命令表检查
+        registry::dispatch(value)
     }
 }
 
@@ -135,6 +900,23 @@ impl CommandExecutor for Unrecognized {
     }
 }
 
+// 和 redis 一样的提示: unknown command 'foo', with args beginning with: 'a' 'b'
+fn unknown_command(name: &[u8], args: &[RespFrame]) -> CommandError {
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(s) => format!("'{}' ", String::from_utf8_lossy(s.as_ref())),
+            _ => String::new(),
+        })
+        .collect::<String>();
+
+    CommandError::InvalidCommand(format!(
+        "unknown command '{}', with args beginning with: {}",
+        String::from_utf8_lossy(name),
+        args
+    ))
+}
+
 // "get" "hello"
 fn validate_command(
     value: &RespArray,
@@ -143,9 +925,8 @@ fn validate_command(
 ) -> Result<(), CommandError> {
     if value.len() != n_args + names.len() {
         return Err(CommandError::InvalidArgument(format!(
-            "{} command must have exactly {} argument",
-            names.join(" "),
-            n_args
+            "wrong number of arguments for '{}' command",
+            names.join("|")
         )));
     }
 
@@ -162,7 +943,7 @@ fn validate_command(
             }
             _ => {
                 return Err(CommandError::InvalidCommand(
-                    "Command must have a BulkString as the first argument".to_string(),
+                    "Protocol error: command name must be a bulk string".to_string(),
                 ))
             }
         }
@@ -175,10 +956,184 @@ fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, Comman
     Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 }
 
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+fn wrong_args(name: &str) -> CommandError {
+    CommandError::InvalidArgument(format!("wrong number of arguments for '{}' command", name))
+}
+
+// 命令的参数都应该是 bulk string
+fn bytes_arg(arg: RespFrame) -> Result<Bytes, CommandError> {
+    match arg {
+        RespFrame::BulkString(s) => Ok(s.into_bytes()),
+        _ => Err(CommandError::InvalidArgument(
+            "Protocol error: argument must be a bulk string".to_string(),
+        )),
+    }
+}
+
+// 命令名，用来区分共用一个解析函数的命令，比如 EXPIRE 和 PEXPIRE
+fn command_name(value: &RespArray) -> String {
+    match value.first() {
+        Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_ascii_lowercase(),
+        _ => String::new(),
+    }
+}
+
+// key 后面跟着至少一个参数，比如 HDEL 的 field、SADD 的 member
+fn key_and_values(value: RespArray, name: &str) -> Result<(Bytes, Vec<Bytes>), CommandError> {
+    let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+    let key = args.next().ok_or_else(|| wrong_args(name))??;
+    let values = args.collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() {
+        return Err(wrong_args(name));
+    }
+    Ok((key, values))
+}
+
+// 多个 bulk string 组成的数组，LRANGE、HKEYS 这类命令的回复
+fn bulk_array(items: impl IntoIterator<Item = Bytes>) -> RespFrame {
+    let items = items
+        .into_iter()
+        .map(|item| BulkString::from(item).into())
+        .collect::<Vec<_>>();
+    RespArray::new(items).into()
+}
+
+fn int_array(items: impl IntoIterator<Item = i64>) -> RespFrame {
+    let items = items.into_iter().map(RespFrame::from).collect::<Vec<_>>();
+    RespArray::new(items).into()
+}
+
+// SCAN 类命令共用的参数: cursor [MATCH pattern] [COUNT count]
+// 其他的选项交给 flag 处理，flag 不认识时返回 false
+fn parse_scan_args(
+    mut args: impl Iterator<Item = RespFrame>,
+    name: &str,
+    mut flag: impl FnMut(&[u8]) -> bool,
+) -> Result<(u64, ScanOptions), CommandError> {
+    let cursor = bytes_arg(args.next().ok_or_else(|| wrong_args(name))?)?;
+    let cursor = std::str::from_utf8(&cursor)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or_else(|| CommandError::InvalidArgument("invalid cursor".to_string()))?;
+
+    let mut options = ScanOptions::default();
+    while let Some(opt) = args.next() {
+        let opt = bytes_arg(opt)?.to_ascii_lowercase();
+        match opt.as_slice() {
+            b"match" => {
+                options.pattern = Some(bytes_arg(args.next().ok_or_else(syntax_error)?)?);
+            }
+            b"count" => {
+                let count = parse_i64(&bytes_arg(args.next().ok_or_else(syntax_error)?)?)?;
+                if count < 1 {
+                    return Err(syntax_error());
+                }
+                options.count = count as usize;
+            }
+            _ if flag(&opt) => {}
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok((cursor, options))
+}
+
+// EX、PX、EXAT、PXAT 的参数换算成毫秒时间戳，时间必须是正数
+fn parse_expire_at(opt: &[u8], time: Option<RespFrame>, name: &str) -> Result<u64, CommandError> {
+    let invalid_expire =
+        || CommandError::InvalidArgument(format!("invalid expire time in '{}' command", name));
+
+    let time = parse_i64(&bytes_arg(time.ok_or_else(syntax_error)?)?)?;
+    if time <= 0 {
+        return Err(invalid_expire());
+    }
+
+    let at = match opt {
+        b"ex" => time
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(now_ms() as i64)),
+        b"px" => time.checked_add(now_ms() as i64),
+        b"exat" => time.checked_mul(1000),
+        _ => Some(time),
+    };
+    at.map(|at| at as u64).ok_or_else(invalid_expire)
+}
+
+// LPOP、SPOP 这类命令的 count 不能是负数
+fn parse_count(arg: &[u8]) -> Result<usize, CommandError> {
+    usize::try_from(parse_i64(arg)?).map_err(|_| {
+        CommandError::InvalidArgument("value is out of range, must be positive".to_string())
+    })
+}
+
+// 阻塞命令的超时时间，单位是秒，可以是小数，0 表示一直等待
+fn parse_timeout(arg: &[u8]) -> Result<Option<Duration>, CommandError> {
+    let timeout = parse_f64(arg).map_err(|_| {
+        CommandError::InvalidArgument("timeout is not a float or out of range".to_string())
+    })?;
+    if timeout < 0.0 {
+        return Err(CommandError::InvalidArgument(
+            "timeout is negative".to_string(),
+        ));
+    }
+    // 和 redis 一样，换算成毫秒之后不能溢出
+    if timeout * 1000.0 >= i64::MAX as f64 {
+        return Err(CommandError::InvalidArgument(
+            "timeout is out of range".to_string(),
+        ));
+    }
+    Ok((timeout > 0.0).then(|| Duration::from_secs_f64(timeout)))
+}
+
+// 和 redis 的 string2ll 一样严格: 不允许前导的 +、空格和多余的 0
+pub(crate) fn parse_i64(arg: &[u8]) -> Result<i64, CommandError> {
+    let err = || CommandError::InvalidArgument("value is not an integer or out of range".into());
+
+    let digits = arg.strip_prefix(b"-").unwrap_or(arg);
+    match digits {
+        [] | [b'0', _, ..] => return Err(err()),
+        [b'0'] if digits.len() != arg.len() => return Err(err()),
+        _ if !digits.iter().all(u8::is_ascii_digit) => return Err(err()),
+        _ => {}
+    }
+
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(err)
+}
+
+// 不允许空格和 nan，inf 是合法的
+pub(crate) fn parse_f64(arg: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|s| !s.starts_with(char::is_whitespace))
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| !f.is_nan())
+        .ok_or_else(|| CommandError::InvalidArgument("value is not a valid float".to_string()))
+}
+
+// 测试用: 执行一条命令，解析失败时和网络层一样回复错误
+#[cfg(test)]
+fn exec(backend: &Backend, args: &[&str]) -> RespFrame {
+    let frames = args
+        .iter()
+        .map(|arg| BulkString::new(*arg).into())
+        .collect::<Vec<RespFrame>>();
+    match Command::try_from(RespArray::new(frames)) {
+        Ok(cmd) => cmd.execute(backend),
+        Err(e) => e.into(),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::cmd::{Command, CommandExecutor};
-    use crate::{Backend, RespArray, RespDecode, RespFrame, RespNull};
+    use crate::cmd::{parse_f64, parse_i64, Command, CommandError, CommandExecutor};
+    use crate::{Backend, RespArray, RespDecode, RespFrame, RespNull, SimpleError};
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -207,4 +1162,69 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_i64() {
+        assert_eq!(parse_i64(b"0").unwrap(), 0);
+        assert_eq!(parse_i64(b"-42").unwrap(), -42);
+        assert_eq!(parse_i64(b"-9223372036854775808").unwrap(), i64::MIN);
+
+        for s in [
+            &b""[..],
+            b"+1",
+            b"01",
+            b"-0",
+            b" 1",
+            b"1.0",
+            b"9223372036854775808",
+        ] {
+            assert!(parse_i64(s).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_f64() {
+        assert_eq!(parse_f64(b"1.5").unwrap(), 1.5);
+        assert_eq!(parse_f64(b"3.0e3").unwrap(), 3000.0);
+        assert_eq!(parse_f64(b"-inf").unwrap(), f64::NEG_INFINITY);
+
+        for s in [&b""[..], b"nan", b" 1", b"1 ", b"abc"] {
+            assert!(parse_f64(s).is_err());
+        }
+    }
+
+    #[test]
+    fn test_command_error_to_frame() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$3\r\nfoo\r\n$5\r\nhello\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let err = Command::try_from(frame).unwrap_err();
+
+        let frame: RespFrame = err.into();
+        assert_eq!(
+            frame,
+            SimpleError::new("ERR unknown command 'foo', with args beginning with: 'hello' ")
+                .into()
+        );
+
+        buf.extend_from_slice(b"*1\r\n$3\r\nget\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let err = Command::try_from(frame).unwrap_err();
+
+        let frame: RespFrame = err.into();
+        assert_eq!(
+            frame,
+            SimpleError::new("ERR wrong number of arguments for 'get' command").into()
+        );
+
+        let frame: RespFrame = CommandError::WrongType.into();
+        assert_eq!(
+            frame,
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,1546 @@
+use crate::cmd::{
+    unknown_command, Append, BLMove, BPop, BZPop, BitCount, BitField, BitOp, BitPos, Command,
+    CommandError, CommandInfo, Expire, Get, GetBit, GetDel, GetEx, GetRange, GetSet, HDel, HExists,
+    HExpire, HGet, HGetAll, HGetEx, HIncrBy, HIncrByFloat, HKeys, HLen, HMGet, HPersist,
+    HRandField, HScan, HSet, HSetEx, HSetNx, HStrLen, HTtl, Hello, IncrBy, IncrByFloat, LIndex,
+    LInsert, LLen, LMove, LPos, LRange, LRem, LSet, LTrim, MGet, MSet, Persist, Ping, Pop, Push,
+    SAdd, SCard, SInterCard, SIsMember, SMIsMember, SMembers, SMove, SOp, SPop, SRandMember, SRem,
+    SScan, Set, SetBit, SetRange, StrLen, Ttl, Type, ZAdd, ZCard, ZCount, ZIncrBy, ZMScore,
+    ZOpStore, ZPop, ZRange, ZRangeStore, ZRank, ZRem, ZScore,
+};
+use crate::{RespArray, RespFrame};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// 命令表中的一项，描述一个命令的名字、参数个数、标志位以及 key 所在的位置
+/// 解析、分发以及 COMMAND 命令的回复都由这张表驱动
+#[derive(Debug)]
+pub struct CommandSpec {
+    /// 小写的命令名
+    pub name: &'static str,
+    /// 参数个数（包括命令名本身），负数表示至少 -arity 个参数
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub group: &'static str,
+    pub since: &'static str,
+    pub summary: &'static str,
+    parser: fn(RespArray) -> Result<Command, CommandError>,
+}
+
+// 用泛型函数作为 parser，这样命令表可以是一个 const 数组
+fn parse<T>(value: RespArray) -> Result<Command, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError> + Into<Command>,
+{
+    Ok(T::try_from(value)?.into())
+}
+
+pub(crate) const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns the string value of a key.",
+        parser: parse::<Get>,
+    },
+    CommandSpec {
+        name: "set",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Sets the string value of a key.",
+        parser: parse::<Set>,
+    },
+    CommandSpec {
+        name: "incr",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        parser: parse::<IncrBy>,
+    },
+    CommandSpec {
+        name: "decr",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        parser: parse::<IncrBy>,
+    },
+    CommandSpec {
+        name: "incrby",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        parser: parse::<IncrBy>,
+    },
+    CommandSpec {
+        name: "decrby",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist.",
+        parser: parse::<IncrBy>,
+    },
+    CommandSpec {
+        name: "incrbyfloat",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "2.6.0",
+        summary: "Increment the floating point value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        parser: parse::<IncrByFloat>,
+    },
+    CommandSpec {
+        name: "append",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "2.0.0",
+        summary: "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+        parser: parse::<Append>,
+    },
+    CommandSpec {
+        name: "strlen",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "2.2.0",
+        summary: "Returns the length of a string value.",
+        parser: parse::<StrLen>,
+    },
+    CommandSpec {
+        name: "getrange",
+        arity: 4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "2.4.0",
+        summary: "Returns a substring of the string stored at a key.",
+        parser: parse::<GetRange>,
+    },
+    CommandSpec {
+        name: "setrange",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "2.2.0",
+        summary: "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist.",
+        parser: parse::<SetRange>,
+    },
+    CommandSpec {
+        name: "getdel",
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "6.2.0",
+        summary: "Returns the string value of a key after deleting the key.",
+        parser: parse::<GetDel>,
+    },
+    CommandSpec {
+        name: "getex",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "6.2.0",
+        summary: "Returns the string value of a key after setting its expiration time.",
+        parser: parse::<GetEx>,
+    },
+    CommandSpec {
+        name: "getset",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns the previous string value of a key after setting it to a new value.",
+        parser: parse::<GetSet>,
+    },
+    CommandSpec {
+        name: "mget",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "string",
+        since: "1.0.0",
+        summary: "Atomically returns the string values of one or more keys.",
+        parser: parse::<MGet>,
+    },
+    CommandSpec {
+        name: "mset",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+        group: "string",
+        since: "1.0.1",
+        summary: "Atomically creates or modifies the string values of one or more keys.",
+        parser: parse::<MSet>,
+    },
+    CommandSpec {
+        name: "msetnx",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+        group: "string",
+        since: "1.0.1",
+        summary: "Atomically modifies the string values of one or more keys only when all keys don't exist.",
+        parser: parse::<MSet>,
+    },
+    CommandSpec {
+        name: "setbit",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "2.2.0",
+        summary: "Sets or clears the bit at offset of the string value. Creates the key if it doesn't exist.",
+        parser: parse::<SetBit>,
+    },
+    CommandSpec {
+        name: "getbit",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "2.2.0",
+        summary: "Returns a bit value by offset.",
+        parser: parse::<GetBit>,
+    },
+    CommandSpec {
+        name: "bitcount",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "2.6.0",
+        summary: "Counts the number of set bits (population counting) in a string.",
+        parser: parse::<BitCount>,
+    },
+    CommandSpec {
+        name: "bitpos",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "2.8.7",
+        summary: "Finds the first set (1) or clear (0) bit in a string.",
+        parser: parse::<BitPos>,
+    },
+    CommandSpec {
+        name: "bitop",
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 2,
+        last_key: -1,
+        step: 1,
+        group: "bitmap",
+        since: "2.6.0",
+        summary: "Performs bitwise operations on multiple strings, and stores the result.",
+        parser: parse::<BitOp>,
+    },
+    CommandSpec {
+        name: "bitfield",
+        arity: -2,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "3.2.0",
+        summary: "Performs arbitrary bitfield integer operations on strings.",
+        parser: parse::<BitField>,
+    },
+    CommandSpec {
+        name: "bitfield_ro",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        since: "6.0.0",
+        summary: "Performs arbitrary read-only bitfield integer operations on strings.",
+        parser: parse::<BitField>,
+    },
+    CommandSpec {
+        name: "hget",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the value of a field in a hash.",
+        parser: parse::<HGet>,
+    },
+    CommandSpec {
+        name: "hset",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Creates or modifies the value of a field in a hash.",
+        parser: parse::<HSet>,
+    },
+    CommandSpec {
+        name: "hgetall",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns all fields and values in a hash.",
+        parser: parse::<HGetAll>,
+    },
+    CommandSpec {
+        name: "hsetnx",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Sets the value of a field in a hash only when the field doesn't exist.",
+        parser: parse::<HSetNx>,
+    },
+    CommandSpec {
+        name: "hmset",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Sets the values of multiple fields.",
+        parser: parse::<HSet>,
+    },
+    CommandSpec {
+        name: "hdel",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Deletes one or more fields and their values from a hash. Deletes the hash if no fields remain.",
+        parser: parse::<HDel>,
+    },
+    CommandSpec {
+        name: "hexists",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Determines whether a field exists in a hash.",
+        parser: parse::<HExists>,
+    },
+    CommandSpec {
+        name: "hlen",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the number of fields in a hash.",
+        parser: parse::<HLen>,
+    },
+    CommandSpec {
+        name: "hkeys",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns all fields in a hash.",
+        parser: parse::<HKeys>,
+    },
+    CommandSpec {
+        name: "hvals",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns all values in a hash.",
+        parser: parse::<HKeys>,
+    },
+    CommandSpec {
+        name: "hmget",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the values of all fields in a hash.",
+        parser: parse::<HMGet>,
+    },
+    CommandSpec {
+        name: "hincrby",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Increments the integer value of a field in a hash by a number. Uses 0 as initial value if the field doesn't exist.",
+        parser: parse::<HIncrBy>,
+    },
+    CommandSpec {
+        name: "hincrbyfloat",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.6.0",
+        summary: "Increments the floating point value of a field by a number. Uses 0 as initial value if the field doesn't exist.",
+        parser: parse::<HIncrByFloat>,
+    },
+    CommandSpec {
+        name: "hstrlen",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "3.2.0",
+        summary: "Returns the length of the value of a field.",
+        parser: parse::<HStrLen>,
+    },
+    CommandSpec {
+        name: "hrandfield",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "6.2.0",
+        summary: "Returns one or more random fields from a hash.",
+        parser: parse::<HRandField>,
+    },
+    CommandSpec {
+        name: "hscan",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "2.8.0",
+        summary: "Iterates over fields and values of a hash.",
+        parser: parse::<HScan>,
+    },
+    CommandSpec {
+        name: "hexpire",
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Set expiry for hash field using relative time to expire (seconds)",
+        parser: parse::<HExpire>,
+    },
+    CommandSpec {
+        name: "hpexpire",
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Set expiry for hash field using relative time to expire (milliseconds)",
+        parser: parse::<HExpire>,
+    },
+    CommandSpec {
+        name: "hexpireat",
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Set expiry for hash field using an absolute Unix timestamp (seconds)",
+        parser: parse::<HExpire>,
+    },
+    CommandSpec {
+        name: "hpexpireat",
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Set expiry for hash field using an absolute Unix timestamp (milliseconds)",
+        parser: parse::<HExpire>,
+    },
+    CommandSpec {
+        name: "httl",
+        arity: -5,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Returns the TTL in seconds of a hash field.",
+        parser: parse::<HTtl>,
+    },
+    CommandSpec {
+        name: "hpttl",
+        arity: -5,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Returns the TTL in milliseconds of a hash field.",
+        parser: parse::<HTtl>,
+    },
+    CommandSpec {
+        name: "hexpiretime",
+        arity: -5,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Returns the expiration time of a hash field as a Unix timestamp, in seconds.",
+        parser: parse::<HTtl>,
+    },
+    CommandSpec {
+        name: "hpexpiretime",
+        arity: -5,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Returns the expiration time of a hash field as a Unix timestamp, in msec.",
+        parser: parse::<HTtl>,
+    },
+    CommandSpec {
+        name: "hpersist",
+        arity: -5,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "7.4.0",
+        summary: "Removes the expiration time for each specified field",
+        parser: parse::<HPersist>,
+    },
+    CommandSpec {
+        name: "hgetex",
+        arity: -5,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "8.0.0",
+        summary: "Get the value of one or more fields of a given hash key, and optionally set their expiration.",
+        parser: parse::<HGetEx>,
+    },
+    CommandSpec {
+        name: "hsetex",
+        arity: -6,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        since: "8.0.0",
+        summary: "Set the value of one or more fields of a given hash key, and optionally set their expiration.",
+        parser: parse::<HSetEx>,
+    },
+    CommandSpec {
+        name: "lpush",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+        parser: parse::<Push>,
+    },
+    CommandSpec {
+        name: "rpush",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        parser: parse::<Push>,
+    },
+    CommandSpec {
+        name: "lpushx",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "2.2.0",
+        summary: "Prepends one or more elements to a list only when the list exists.",
+        parser: parse::<Push>,
+    },
+    CommandSpec {
+        name: "rpushx",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "2.2.0",
+        summary: "Appends an element to a list only when the list exists.",
+        parser: parse::<Push>,
+    },
+    CommandSpec {
+        name: "lpop",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.",
+        parser: parse::<Pop>,
+    },
+    CommandSpec {
+        name: "rpop",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Returns and removes the last elements of a list. Deletes the list if the last element was popped.",
+        parser: parse::<Pop>,
+    },
+    CommandSpec {
+        name: "llen",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Returns the length of a list.",
+        parser: parse::<LLen>,
+    },
+    CommandSpec {
+        name: "lrange",
+        arity: 4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Returns a range of elements from a list.",
+        parser: parse::<LRange>,
+    },
+    CommandSpec {
+        name: "lindex",
+        arity: 3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Returns an element from a list by its index.",
+        parser: parse::<LIndex>,
+    },
+    CommandSpec {
+        name: "lset",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Sets the value of an element in a list by its index.",
+        parser: parse::<LSet>,
+    },
+    CommandSpec {
+        name: "linsert",
+        arity: 5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "2.2.0",
+        summary: "Inserts an element before or after another element in a list.",
+        parser: parse::<LInsert>,
+    },
+    CommandSpec {
+        name: "lrem",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Removes elements from a list. Deletes the list if the last element was removed.",
+        parser: parse::<LRem>,
+    },
+    CommandSpec {
+        name: "ltrim",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "1.0.0",
+        summary: "Removes elements from both ends a list. Deletes the list if all elements were trimmed.",
+        parser: parse::<LTrim>,
+    },
+    CommandSpec {
+        name: "lpos",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        since: "6.0.6",
+        summary: "Returns the index of matching elements in a list.",
+        parser: parse::<LPos>,
+    },
+    CommandSpec {
+        name: "lmove",
+        arity: 5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        group: "list",
+        since: "6.2.0",
+        summary: "Returns an element after popping it from one list and pushing it to another. Deletes the list if the last element was moved.",
+        parser: parse::<LMove>,
+    },
+    CommandSpec {
+        name: "blpop",
+        arity: -3,
+        flags: &["write", "blocking"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+        group: "list",
+        since: "2.0.0",
+        summary: "Removes and returns the first element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        parser: parse::<BPop>,
+    },
+    CommandSpec {
+        name: "brpop",
+        arity: -3,
+        flags: &["write", "blocking"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+        group: "list",
+        since: "2.0.0",
+        summary: "Removes and returns the last element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        parser: parse::<BPop>,
+    },
+    CommandSpec {
+        name: "blmove",
+        arity: 6,
+        flags: &["write", "denyoom", "blocking"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        group: "list",
+        since: "6.2.0",
+        summary: "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is available otherwise. Deletes the list if the last element was moved.",
+        parser: parse::<BLMove>,
+    },
+    CommandSpec {
+        name: "blmpop",
+        arity: -5,
+        flags: &["write", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "list",
+        since: "7.0.0",
+        summary: "Pops the first element from one of multiple lists. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        parser: parse::<BPop>,
+    },
+    CommandSpec {
+        name: "sadd",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Adds one or more members to a set. Creates the key if it doesn't exist.",
+        parser: parse::<SAdd>,
+    },
+    CommandSpec {
+        name: "srem",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Removes one or more members from a set. Deletes the set if the last member was removed.",
+        parser: parse::<SRem>,
+    },
+    CommandSpec {
+        name: "sismember",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Determines whether a member belongs to a set.",
+        parser: parse::<SIsMember>,
+    },
+    CommandSpec {
+        name: "smismember",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "6.2.0",
+        summary: "Determines whether multiple members belong to a set.",
+        parser: parse::<SMIsMember>,
+    },
+    CommandSpec {
+        name: "scard",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns the number of members in a set.",
+        parser: parse::<SCard>,
+    },
+    CommandSpec {
+        name: "smembers",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns all members of a set.",
+        parser: parse::<SMembers>,
+    },
+    CommandSpec {
+        name: "spop",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns one or more random members from a set after removing them. Deletes the set if the last member was popped.",
+        parser: parse::<SPop>,
+    },
+    CommandSpec {
+        name: "srandmember",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Get one or multiple random members from a set",
+        parser: parse::<SRandMember>,
+    },
+    CommandSpec {
+        name: "smove",
+        arity: 4,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Moves a member from one set to another.",
+        parser: parse::<SMove>,
+    },
+    CommandSpec {
+        name: "sinter",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns the intersect of multiple sets.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sinterstore",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Stores the intersect of multiple sets in a key.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sintercard",
+        arity: -3,
+        flags: &["readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "set",
+        since: "7.0.0",
+        summary: "Returns the number of members of the intersect of multiple sets.",
+        parser: parse::<SInterCard>,
+    },
+    CommandSpec {
+        name: "sunion",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns the union of multiple sets.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sunionstore",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Stores the union of multiple sets in a key.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sdiff",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Returns the difference of multiple sets.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sdiffstore",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        since: "1.0.0",
+        summary: "Stores the difference of multiple sets in a key.",
+        parser: parse::<SOp>,
+    },
+    CommandSpec {
+        name: "sscan",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        since: "2.8.0",
+        summary: "Iterates over members of a set.",
+        parser: parse::<SScan>,
+    },
+    CommandSpec {
+        name: "zadd",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Adds one or more members to a sorted set, or updates their scores. Creates the key if it doesn't exist.",
+        parser: parse::<ZAdd>,
+    },
+    CommandSpec {
+        name: "zincrby",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Increments the score of a member in a sorted set.",
+        parser: parse::<ZIncrBy>,
+    },
+    CommandSpec {
+        name: "zrem",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Removes one or more members from a sorted set. Deletes the sorted set if all members were removed.",
+        parser: parse::<ZRem>,
+    },
+    CommandSpec {
+        name: "zscore",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Returns the score of a member in a sorted set.",
+        parser: parse::<ZScore>,
+    },
+    CommandSpec {
+        name: "zmscore",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "6.2.0",
+        summary: "Returns the score of one or more members in a sorted set.",
+        parser: parse::<ZMScore>,
+    },
+    CommandSpec {
+        name: "zcard",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Returns the number of members in a sorted set.",
+        parser: parse::<ZCard>,
+    },
+    CommandSpec {
+        name: "zcount",
+        arity: 4,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "2.0.0",
+        summary: "Returns the count of members in a sorted set that have scores within a range.",
+        parser: parse::<ZCount>,
+    },
+    CommandSpec {
+        name: "zrank",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "2.0.0",
+        summary: "Returns the index of a member in a sorted set ordered by ascending scores.",
+        parser: parse::<ZRank>,
+    },
+    CommandSpec {
+        name: "zrevrank",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "2.0.0",
+        summary: "Returns the index of a member in a sorted set ordered by descending scores.",
+        parser: parse::<ZRank>,
+    },
+    CommandSpec {
+        name: "zrange",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "1.2.0",
+        summary: "Returns members in a sorted set within a range of indexes.",
+        parser: parse::<ZRange>,
+    },
+    CommandSpec {
+        name: "zrangestore",
+        arity: -5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        group: "sorted-set",
+        since: "6.2.0",
+        summary: "Stores a range of members from sorted set in a key.",
+        parser: parse::<ZRangeStore>,
+    },
+    CommandSpec {
+        name: "zpopmin",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "5.0.0",
+        summary: "Returns the lowest-scoring members from a sorted set after removing them. Deletes the sorted set if the last member was popped.",
+        parser: parse::<ZPop>,
+    },
+    CommandSpec {
+        name: "zpopmax",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "5.0.0",
+        summary: "Returns the highest-scoring members from a sorted set after removing them. Deletes the sorted set if the last member was popped.",
+        parser: parse::<ZPop>,
+    },
+    CommandSpec {
+        name: "zunionstore",
+        arity: -4,
+        flags: &["write", "denyoom", "movablekeys"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "2.0.0",
+        summary: "Stores the union of multiple sorted sets in a key.",
+        parser: parse::<ZOpStore>,
+    },
+    CommandSpec {
+        name: "zinterstore",
+        arity: -4,
+        flags: &["write", "denyoom", "movablekeys"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "sorted-set",
+        since: "2.0.0",
+        summary: "Stores the intersect of multiple sorted sets in a key.",
+        parser: parse::<ZOpStore>,
+    },
+    CommandSpec {
+        name: "bzpopmin",
+        arity: -3,
+        flags: &["write", "blocking", "fast"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+        group: "sorted-set",
+        since: "5.0.0",
+        summary: "Removes and returns the member with the lowest score from one or more sorted sets. Blocks until a member is available otherwise. Deletes the sorted set if the last element was popped.",
+        parser: parse::<BZPop>,
+    },
+    CommandSpec {
+        name: "bzpopmax",
+        arity: -3,
+        flags: &["write", "blocking", "fast"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+        group: "sorted-set",
+        since: "5.0.0",
+        summary: "Removes and returns the member with the highest score from one or more sorted sets. Blocks until a member available otherwise.  Deletes the sorted set if the last element was popped.",
+        parser: parse::<BZPop>,
+    },
+    CommandSpec {
+        name: "bzmpop",
+        arity: -5,
+        flags: &["write", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "sorted-set",
+        since: "7.0.0",
+        summary: "Removes and returns a member by score from one or more sorted sets. Blocks until a member is available otherwise. Deletes the sorted set if the last element was popped.",
+        parser: parse::<BZPop>,
+    },
+    CommandSpec {
+        name: "command",
+        arity: -1,
+        flags: &["loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        since: "2.8.13",
+        summary: "Returns detailed information about all commands.",
+        parser: parse::<CommandInfo>,
+    },
+    CommandSpec {
+        name: "hello",
+        arity: -1,
+        flags: &["noscript", "loading", "stale", "fast", "no-auth"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        since: "6.0.0",
+        summary: "Handshakes with the Redis server.",
+        parser: parse::<Hello>,
+    },
+    CommandSpec {
+        name: "ping",
+        arity: -1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the server's liveliness response.",
+        parser: parse::<Ping>,
+    },
+    CommandSpec {
+        name: "type",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "1.0.0",
+        summary: "Determines the type of value stored at a key.",
+        parser: parse::<Type>,
+    },
+    CommandSpec {
+        name: "expire",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "1.0.0",
+        summary: "Sets the expiration time of a key in seconds.",
+        parser: parse::<Expire>,
+    },
+    CommandSpec {
+        name: "pexpire",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "2.6.0",
+        summary: "Sets the expiration time of a key in milliseconds.",
+        parser: parse::<Expire>,
+    },
+    CommandSpec {
+        name: "expireat",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "1.2.0",
+        summary: "Sets the expiration time of a key to a Unix timestamp.",
+        parser: parse::<Expire>,
+    },
+    CommandSpec {
+        name: "pexpireat",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "2.6.0",
+        summary: "Sets the expiration time of a key to a Unix milliseconds timestamp.",
+        parser: parse::<Expire>,
+    },
+    CommandSpec {
+        name: "ttl",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "1.0.0",
+        summary: "Returns the expiration time in seconds of a key.",
+        parser: parse::<Ttl>,
+    },
+    CommandSpec {
+        name: "pttl",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "2.6.0",
+        summary: "Returns the expiration time in milliseconds of a key.",
+        parser: parse::<Ttl>,
+    },
+    CommandSpec {
+        name: "expiretime",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "7.0.0",
+        summary: "Returns the expiration time of a key as a Unix timestamp.",
+        parser: parse::<Ttl>,
+    },
+    CommandSpec {
+        name: "pexpiretime",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "7.0.0",
+        summary: "Returns the expiration time of a key as a Unix milliseconds timestamp.",
+        parser: parse::<Ttl>,
+    },
+    CommandSpec {
+        name: "persist",
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        since: "2.2.0",
+        summary: "Removes the expiration time of a key.",
+        parser: parse::<Persist>,
+    },
+];
+
+lazy_static! {
+    static ref COMMAND_TABLE: HashMap<&'static str, &'static CommandSpec> =
+        COMMANDS.iter().map(|spec| (spec.name, spec)).collect();
+}
+
+/// 按命令名查找，大小写不敏感
+pub fn lookup_command(name: &[u8]) -> Option<&'static CommandSpec> {
+    let name = std::str::from_utf8(name).ok()?.to_ascii_lowercase();
+    COMMAND_TABLE.get(name.as_str()).copied()
+}
+
+impl CommandSpec {
+    pub fn check_arity(&self, n: usize) -> Result<(), CommandError> {
+        let n = n as i64;
+        let ok = if self.arity >= 0 {
+            n == self.arity
+        } else {
+            n >= -self.arity
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(CommandError::InvalidArgument(format!(
+                "wrong number of arguments for '{}' command",
+                self.name
+            )))
+        }
+    }
+
+    // 先检查参数个数，再交给具体的命令去解析
+    pub(crate) fn parse(&self, value: RespArray) -> Result<Command, CommandError> {
+        self.check_arity(value.len())?;
+        (self.parser)(value)
+    }
+}
+
+pub(crate) fn dispatch(value: RespArray) -> Result<Command, CommandError> {
+    match value.first() {
+        Some(RespFrame::BulkString(ref cmd)) => match lookup_command(cmd.as_ref()) {
+            Some(spec) => spec.parse(value),
+            None => Err(unknown_command(cmd.as_ref(), &value[1..])),
+        },
+
+        _ => Err(CommandError::InvalidCommand(
+            "Protocol error: command name must be a bulk string".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_lookup_command_case_insensitive() {
+        assert_eq!(lookup_command(b"GET").unwrap().name, "get");
+        assert_eq!(lookup_command(b"hGetAll").unwrap().name, "hgetall");
+        assert!(lookup_command(b"nope").is_none());
+    }
+
+    #[test]
+    fn test_check_arity() {
+        let spec = lookup_command(b"get").unwrap();
+        assert!(spec.check_arity(2).is_ok());
+        assert!(spec.check_arity(3).is_err());
+
+        let spec = lookup_command(b"command").unwrap();
+        assert!(spec.check_arity(1).is_ok());
+        assert!(spec.check_arity(5).is_ok());
+    }
+
+    #[test]
+    fn test_dispatch_uppercase_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd = dispatch(frame)?;
+        assert!(matches!(cmd, Command::Set(_)));
+
+        Ok(())
+    }
+}
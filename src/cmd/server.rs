@@ -0,0 +1,180 @@
+use crate::cmd::registry::{lookup_command, CommandSpec, COMMANDS};
+use crate::cmd::{extract_args, CommandError, CommandExecutor, CommandInfo, CommandInfoKind};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap, RespNull, SimpleString};
+
+impl CommandExecutor for CommandInfo {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.kind {
+            CommandInfoKind::List => {
+                RespArray::new(COMMANDS.iter().map(spec_to_frame).collect::<Vec<_>>()).into()
+            }
+            CommandInfoKind::Count => (COMMANDS.len() as i64).into(),
+            CommandInfoKind::Info(names) => {
+                // 不认识的命令回复 null
+                let frames = names
+                    .iter()
+                    .map(|name| match lookup_command(name.as_bytes()) {
+                        Some(spec) => spec_to_frame(spec),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<_>>();
+                RespArray::new(frames).into()
+            }
+            CommandInfoKind::Docs(names) => {
+                let mut map = RespMap::new();
+                for name in names {
+                    // 不认识的命令直接忽略
+                    if let Some(spec) = lookup_command(name.as_bytes()) {
+                        map.insert(spec.name.to_string(), spec_to_docs(spec));
+                    }
+                }
+                map.into()
+            }
+        }
+    }
+}
+
+// name, arity, flags, first key, last key, step, acl categories
+fn spec_to_frame(spec: &CommandSpec) -> RespFrame {
+    let flags = spec
+        .flags
+        .iter()
+        .map(|flag| SimpleString::new(*flag).into())
+        .collect::<Vec<RespFrame>>();
+
+    RespArray::new(vec![
+        BulkString::new(spec.name).into(),
+        spec.arity.into(),
+        RespArray::new(flags).into(),
+        spec.first_key.into(),
+        spec.last_key.into(),
+        spec.step.into(),
+        RespArray::new([SimpleString::new(format!("@{}", spec.group)).into()]).into(),
+    ])
+    .into()
+}
+
+fn spec_to_docs(spec: &CommandSpec) -> RespFrame {
+    let mut map = RespMap::new();
+    map.insert("summary".to_string(), BulkString::new(spec.summary).into());
+    map.insert("since".to_string(), BulkString::new(spec.since).into());
+    map.insert("group".to_string(), BulkString::new(spec.group).into());
+    map.into()
+}
+
+// COMMAND [COUNT | INFO [name ...] | DOCS [name ...]]
+impl TryFrom<RespArray> for CommandInfo {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(s) => Ok(String::from_utf8_lossy(&s).to_string()),
+                _ => Err(CommandError::InvalidArgument(
+                    "Invalid argument for COMMAND".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<String>, CommandError>>()?;
+
+        let Some(sub) = args.first() else {
+            return Ok(CommandInfo {
+                kind: CommandInfoKind::List,
+            });
+        };
+
+        let names = || {
+            if args.len() > 1 {
+                args[1..].to_vec()
+            } else {
+                COMMANDS.iter().map(|spec| spec.name.to_string()).collect()
+            }
+        };
+
+        let kind = match sub.to_ascii_lowercase().as_str() {
+            "count" if args.len() == 1 => CommandInfoKind::Count,
+            "info" => CommandInfoKind::Info(names()),
+            "docs" => CommandInfoKind::Docs(names()),
+            "count" => {
+                return Err(CommandError::InvalidArgument(
+                    "wrong number of arguments for 'command|count' command".to_string(),
+                ))
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand '{}'. Try COMMAND HELP.",
+                    sub
+                )))
+            }
+        };
+
+        Ok(CommandInfo { kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_command_count() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$7\r\nCOMMAND\r\n$5\r\nCOUNT\r\n");
+
+        let cmd: CommandInfo = RespArray::decode(&mut buf)?.try_into()?;
+        let backend = Backend::new();
+
+        assert_eq!(cmd.execute(&backend), (COMMANDS.len() as i64).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_info() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$7\r\ncommand\r\n$4\r\ninfo\r\n$3\r\nGET\r\n$4\r\nnope\r\n");
+
+        let cmd: CommandInfo = RespArray::decode(&mut buf)?.try_into()?;
+        let backend = Backend::new();
+
+        let expected = RespArray::new(vec![
+            RespArray::new(vec![
+                BulkString::new("get").into(),
+                2.into(),
+                RespArray::new([
+                    SimpleString::new("readonly").into(),
+                    SimpleString::new("fast").into(),
+                ])
+                .into(),
+                1.into(),
+                1.into(),
+                1.into(),
+                RespArray::new([SimpleString::new("@string").into()]).into(),
+            ])
+            .into(),
+            RespFrame::Null(RespNull),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_docs() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$7\r\ncommand\r\n$4\r\ndocs\r\n$4\r\nhget\r\n");
+
+        let cmd: CommandInfo = RespArray::decode(&mut buf)?.try_into()?;
+        let backend = Backend::new();
+
+        let RespFrame::Map(map) = cmd.execute(&backend) else {
+            panic!("expect a map");
+        };
+        assert_eq!(map.len(), 1);
+        assert!(map.contains_key("hget"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+use crate::cmd::{
+    bulk_array, bytes_arg, command_name, extract_args, key_and_values, parse_count, parse_i64,
+    parse_scan_args, syntax_error, validate_command, wrong_args, CommandError, CommandExecutor,
+    SAdd, SCard, SInterCard, SIsMember, SMIsMember, SMembers, SMove, SOp, SPop, SRandMember, SRem,
+    SScan,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, RespSet, SetOperation};
+use bytes::Bytes;
+
+impl CommandExecutor for SAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sadd(self.key, self.members) {
+            Ok(added) => (added as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.srem(self.key, &self.members) {
+            Ok(removed) => (removed as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sismember(&self.key, &self.member) {
+            Ok(found) => (found as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SMIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smismember(&self.key, &self.members) {
+            Ok(found) => {
+                let found = found
+                    .into_iter()
+                    .map(|found| (found as i64).into())
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(found).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.scard(&self.key) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SMembers {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smembers(&self.key) {
+            Ok(members) => bulk_set(members),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let popped = match backend.spop(self.key, self.count.unwrap_or(1)) {
+            Ok(popped) => popped,
+            Err(e) => return e.into(),
+        };
+
+        match self.count {
+            Some(_) => bulk_set(popped),
+            None => popped
+                .into_iter()
+                .next()
+                .map_or(RespFrame::Null(RespNull), |member| {
+                    BulkString::from(member).into()
+                }),
+        }
+    }
+}
+
+impl CommandExecutor for SRandMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let members = match backend.srandmember(&self.key, self.count.unwrap_or(1)) {
+            Ok(members) => members,
+            Err(e) => return e.into(),
+        };
+
+        // 有 count 时可能有重复的成员，所以回复数组而不是集合
+        match self.count {
+            Some(_) => bulk_array(members),
+            None => members
+                .into_iter()
+                .next()
+                .map_or(RespFrame::Null(RespNull), |member| {
+                    BulkString::from(member).into()
+                }),
+        }
+    }
+}
+
+impl CommandExecutor for SMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smove(self.source, self.destination, self.member) {
+            Ok(moved) => (moved as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SOp {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.destination {
+            Some(destination) => match backend.sopstore(self.op, destination, &self.keys) {
+                Ok(len) => (len as i64).into(),
+                Err(e) => e.into(),
+            },
+            None => match backend.sop(self.op, &self.keys) {
+                Ok(members) => bulk_set(members),
+                Err(e) => e.into(),
+            },
+        }
+    }
+}
+
+impl CommandExecutor for SInterCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sintercard(&self.keys, self.limit) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sscan(&self.key, self.cursor, &self.options) {
+            Ok((cursor, members)) => {
+                let cursor = BulkString::new(cursor.to_string()).into();
+                RespArray::new([cursor, bulk_array(members)]).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// RESP3 下回复 set，RESP2 下会变成数组
+fn bulk_set(members: impl IntoIterator<Item = Bytes>) -> RespFrame {
+    let members = members
+        .into_iter()
+        .map(|member| BulkString::from(member).into())
+        .collect::<Vec<_>>();
+    RespSet::new(members).into()
+}
+
+// SADD key member [member ...]
+impl TryFrom<RespArray> for SAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = key_and_values(value, "sadd")?;
+        Ok(SAdd { key, members })
+    }
+}
+
+// SREM key member [member ...]
+impl TryFrom<RespArray> for SRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = key_and_values(value, "srem")?;
+        Ok(SRem { key, members })
+    }
+}
+
+// SISMEMBER key member
+impl TryFrom<RespArray> for SIsMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sismember"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(member)) = (args.next(), args.next()) else {
+            return Err(wrong_args("sismember"));
+        };
+
+        Ok(SIsMember {
+            key: bytes_arg(key)?,
+            member: bytes_arg(member)?,
+        })
+    }
+}
+
+// SMISMEMBER key member [member ...]
+impl TryFrom<RespArray> for SMIsMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = key_and_values(value, "smismember")?;
+        Ok(SMIsMember { key, members })
+    }
+}
+
+// SCARD key
+impl TryFrom<RespArray> for SCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["scard"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("scard"))?)?;
+        Ok(SCard { key })
+    }
+}
+
+// SMEMBERS key
+impl TryFrom<RespArray> for SMembers {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smembers"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("smembers"))?)?;
+        Ok(SMembers { key })
+    }
+}
+
+// SPOP key [count]
+impl TryFrom<RespArray> for SPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), count, None) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args("spop"));
+        };
+        let count = count
+            .map(|count| parse_count(&bytes_arg(count)?))
+            .transpose()?;
+
+        Ok(SPop {
+            key: bytes_arg(key)?,
+            count,
+        })
+    }
+}
+
+// SRANDMEMBER key [count]
+impl TryFrom<RespArray> for SRandMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), count, None) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args("srandmember"));
+        };
+        let count = count
+            .map(|count| parse_i64(&bytes_arg(count)?))
+            .transpose()?;
+
+        // 和 HRANDFIELD 一样限制 count 的范围，负数时返回的个数由 backend 限制
+        if count.is_some_and(|count| !(-i64::MAX / 2..=i64::MAX / 2).contains(&count)) {
+            return Err(CommandError::InvalidArgument(
+                "value is out of range".to_string(),
+            ));
+        }
+
+        Ok(SRandMember {
+            key: bytes_arg(key)?,
+            count,
+        })
+    }
+}
+
+// SMOVE source destination member
+impl TryFrom<RespArray> for SMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smove"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(source), Some(destination), Some(member)) =
+            (args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("smove"));
+        };
+
+        Ok(SMove {
+            source: bytes_arg(source)?,
+            destination: bytes_arg(destination)?,
+            member: bytes_arg(member)?,
+        })
+    }
+}
+
+// SINTER key [key ...]
+// SINTERSTORE destination key [key ...]
+// SUNION、SUNIONSTORE、SDIFF、SDIFFSTORE 的参数一样
+impl TryFrom<RespArray> for SOp {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let op = if name.starts_with("sinter") {
+            SetOperation::Inter
+        } else if name.starts_with("sunion") {
+            SetOperation::Union
+        } else {
+            SetOperation::Diff
+        };
+
+        let mut keys = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+        let destination = match name.ends_with("store") {
+            true if keys.len() >= 2 => Some(keys.remove(0)),
+            false if !keys.is_empty() => None,
+            _ => return Err(wrong_args(&name)),
+        };
+
+        Ok(SOp {
+            op,
+            destination,
+            keys,
+        })
+    }
+}
+
+// SINTERCARD numkeys key [key ...] [LIMIT limit]
+impl TryFrom<RespArray> for SInterCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+        let numkeys = parse_i64(&args.next().ok_or_else(|| wrong_args("sintercard"))??)?;
+        if numkeys <= 0 {
+            return Err(CommandError::InvalidArgument(
+                "numkeys should be greater than 0".to_string(),
+            ));
+        }
+
+        let keys = args
+            .by_ref()
+            .take(numkeys as usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.len() != numkeys as usize {
+            return Err(CommandError::InvalidArgument(
+                "Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+
+        let limit = match (
+            args.next().transpose()?,
+            args.next().transpose()?,
+            args.next(),
+        ) {
+            (None, None, None) => 0,
+            (Some(opt), Some(limit), None) if opt.eq_ignore_ascii_case(b"limit") => {
+                usize::try_from(parse_i64(&limit)?).map_err(|_| {
+                    CommandError::InvalidArgument("LIMIT can't be negative".to_string())
+                })?
+            }
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+// SSCAN key cursor [MATCH pattern] [COUNT count]
+impl TryFrom<RespArray> for SScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("sscan"))?)?;
+        let (cursor, options) = parse_scan_args(args, "sscan", |_| false)?;
+
+        Ok(SScan {
+            key,
+            cursor,
+            options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::exec;
+    use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, SimpleError};
+    use anyhow::Result;
+
+    // set 的顺序是不确定的，排序之后再比较
+    fn sorted(frame: RespFrame) -> Vec<RespFrame> {
+        let mut members = match frame {
+            RespFrame::Set(set) => set.to_vec(),
+            RespFrame::Array(array) => array.to_vec(),
+            frame => panic!("expected set, got {:?}", frame),
+        };
+        members.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        members
+    }
+
+    fn bulks(items: &[&str]) -> Vec<RespFrame> {
+        items
+            .iter()
+            .map(|item| BulkString::new(*item).into())
+            .collect()
+    }
+
+    #[test]
+    fn test_set_commands() -> Result<()> {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["sadd", "s", "a", "b", "c", "a"]), 3.into());
+        assert_eq!(exec(&backend, &["srem", "s", "c", "x"]), 1.into());
+        assert_eq!(exec(&backend, &["scard", "s"]), 2.into());
+        assert_eq!(exec(&backend, &["sismember", "s", "a"]), 1.into());
+        assert_eq!(
+            exec(&backend, &["smismember", "s", "a", "x"]),
+            RespArray::new(vec![1.into(), 0.into()]).into()
+        );
+
+        let members = exec(&backend, &["smembers", "s"]);
+        assert!(matches!(members, RespFrame::Set(_)));
+        assert_eq!(sorted(members), bulks(&["a", "b"]));
+        // RESP2 下是数组
+        assert!(matches!(
+            exec(&backend, &["smembers", "s"]).into_resp2(),
+            RespFrame::Array(_)
+        ));
+
+        let RespFrame::Array(random) = exec(&backend, &["srandmember", "s", "-3"]) else {
+            panic!("expected array");
+        };
+        assert_eq!(random.len(), 3);
+        // 很大的 count 不会按照 count 分配内存，只返回整个集合
+        assert_eq!(
+            sorted(exec(&backend, &["srandmember", "s", "1000000000000"])),
+            bulks(&["a", "b"])
+        );
+        assert_eq!(
+            exec(&backend, &["srandmember", "s", "-1000000000000"]),
+            SimpleError::new(
+                "ERR count is too large, at most 1048576 random elements can be returned"
+            )
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["srandmember", "missing"]),
+            RespFrame::Null(RespNull)
+        );
+
+        assert_eq!(exec(&backend, &["smove", "s", "t", "a"]), 1.into());
+        assert_eq!(exec(&backend, &["smove", "s", "t", "a"]), 0.into());
+        assert_eq!(sorted(exec(&backend, &["spop", "s", "5"])), bulks(&["b"]));
+        assert_eq!(exec(&backend, &["spop", "s"]), RespFrame::Null(RespNull));
+        assert_eq!(
+            exec(&backend, &["spop", "t", "-1"]),
+            SimpleError::new("ERR value is out of range, must be positive").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_operation_commands() -> Result<()> {
+        let backend = Backend::new();
+        exec(&backend, &["sadd", "a", "1", "2", "3"]);
+        exec(&backend, &["sadd", "b", "2", "3", "4"]);
+
+        assert_eq!(
+            sorted(exec(&backend, &["sinter", "a", "b"])),
+            bulks(&["2", "3"])
+        );
+        assert_eq!(
+            sorted(exec(&backend, &["sinter", "a"])),
+            bulks(&["1", "2", "3"])
+        );
+        assert_eq!(
+            sorted(exec(&backend, &["sunion", "a", "b"])),
+            bulks(&["1", "2", "3", "4"])
+        );
+        assert_eq!(sorted(exec(&backend, &["sdiff", "a", "b"])), bulks(&["1"]));
+
+        assert_eq!(exec(&backend, &["sdiffstore", "d", "b", "a"]), 1.into());
+        assert_eq!(sorted(exec(&backend, &["smembers", "d"])), bulks(&["4"]));
+        assert_eq!(exec(&backend, &["sinterstore", "d", "a", "none"]), 0.into());
+        assert_eq!(
+            exec(&backend, &["type", "d"]),
+            crate::SimpleString::new("none").into()
+        );
+        assert_eq!(
+            exec(&backend, &["sunionstore", "d"]),
+            SimpleError::new("ERR wrong number of arguments for 'sunionstore' command").into()
+        );
+
+        assert_eq!(exec(&backend, &["sintercard", "2", "a", "b"]), 2.into());
+        assert_eq!(
+            exec(&backend, &["sintercard", "2", "a", "b", "limit", "1"]),
+            1.into()
+        );
+        assert_eq!(
+            exec(&backend, &["sintercard", "3", "a", "b"]),
+            SimpleError::new("ERR Number of keys can't be greater than number of args").into()
+        );
+        assert_eq!(
+            exec(&backend, &["sintercard", "1", "a", "limit", "-1"]),
+            SimpleError::new("ERR LIMIT can't be negative").into()
+        );
+
+        let RespFrame::Array(reply) = exec(&backend, &["sscan", "a", "0", "count", "100"]) else {
+            panic!("expected array");
+        };
+        assert_eq!(reply[0], BulkString::new("0").into());
+        assert_eq!(sorted(reply[1].clone()), bulks(&["1", "2", "3"]));
+        Ok(())
+    }
+}
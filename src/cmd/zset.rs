@@ -0,0 +1,1019 @@
+use crate::cmd::{
+    bytes_arg, command_name, extract_args, key_and_values, parse_count, parse_f64, parse_i64,
+    parse_timeout, syntax_error, validate_command, wait_until, wrong_args, BZPop, CommandError,
+    CommandExecutor, ZAdd, ZCard, ZCount, ZIncrBy, ZMScore, ZOpStore, ZPop, ZRange, ZRangeStore,
+    ZRank, ZRem, ZScore,
+};
+use crate::{
+    Aggregate, Backend, BulkString, LexBound, RespArray, RespFrame, RespNull, RespNullArray,
+    ScoreBound, SetOperation, ZAddOptions, ZEnd, ZPopped, ZRangeBy, ZRangeSpec,
+};
+use bytes::Bytes;
+use std::future::Future;
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.incr {
+            // 解析时已经保证 INCR 只有一个成员
+            let Some((increment, member)) = self.members.into_iter().next() else {
+                return RespFrame::Null(RespNull);
+            };
+            return match backend.zincrby(self.key, &self.options, increment, member) {
+                Ok(score) => score.map_or(RespFrame::Null(RespNull), RespFrame::Double),
+                Err(e) => e.into(),
+            };
+        }
+
+        match backend.zadd(self.key, &self.options, self.members) {
+            Ok(count) => (count as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let options = ZAddOptions::default();
+        match backend.zincrby(self.key, &options, self.increment, self.member) {
+            Ok(score) => score.map_or(RespFrame::Null(RespNull), RespFrame::Double),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zrem(self.key, &self.members) {
+            Ok(removed) => (removed as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zscore(&self.key, &self.member) {
+            Ok(score) => score.map_or(RespFrame::Null(RespNull), RespFrame::Double),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZMScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zmscore(&self.key, &self.members) {
+            Ok(scores) => {
+                let scores = scores
+                    .into_iter()
+                    .map(|score| score.map_or(RespFrame::Null(RespNull), RespFrame::Double))
+                    .collect::<Vec<_>>();
+                RespArray::new(scores).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zcard(&self.key) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zcount(&self.key, &self.min, &self.max) {
+            Ok(count) => (count as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zrank(&self.key, &self.member, self.rev) {
+            Ok(Some((rank, score))) if self.with_score => {
+                RespArray::new([(rank as i64).into(), RespFrame::Double(score)]).into()
+            }
+            Ok(Some((rank, _))) => (rank as i64).into(),
+            Ok(None) if self.with_score => RespNullArray.into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zrange(&self.key, &self.spec) {
+            Ok(members) => scored_array(members, self.with_scores),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRangeStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zrangestore(self.destination, &self.source, &self.spec) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zpop(self.key, self.end, self.count.unwrap_or(1)) {
+            Ok(popped) => scored_array(popped, true),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZOpStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zopstore(
+            self.op,
+            self.destination,
+            &self.keys,
+            &self.weights,
+            self.aggregate,
+        ) {
+            Ok(len) => (len as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BZPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let popped = backend.zmpop(&self.keys, self.end, self.count.unwrap_or(1));
+        bzpop_reply(popped, self.count.is_some())
+    }
+}
+
+impl BZPop {
+    pub(crate) async fn wait(
+        self,
+        backend: &Backend,
+        closed: impl Future<Output = ()>,
+    ) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        let until = wait_until(self.timeout, closed);
+        let popped = backend.bzpop(self.keys, self.end, count, until).await;
+        bzpop_reply(popped, self.count.is_some())
+    }
+}
+
+// BZPOPMIN 回复 [key, member, score]，BZMPOP 回复 [key, [[member, score] ...]]，超时回复 null array
+fn bzpop_reply(popped: Result<Option<ZPopped>, CommandError>, multi: bool) -> RespFrame {
+    match popped {
+        Ok(Some((key, members))) if multi => {
+            let members = members
+                .into_iter()
+                .map(|member| scored_array(vec![member], true))
+                .collect::<Vec<_>>();
+            RespArray::new(vec![
+                BulkString::from(key).into(),
+                RespArray::new(members).into(),
+            ])
+            .into()
+        }
+        Ok(Some((key, members))) => {
+            let mut frames = vec![BulkString::from(key).into()];
+            if let Some((member, score)) = members.into_iter().next() {
+                frames.push(BulkString::from(member).into());
+                frames.push(RespFrame::Double(score));
+            }
+            RespArray::new(frames).into()
+        }
+        Ok(None) => RespNullArray.into(),
+        Err(e) => e.into(),
+    }
+}
+
+// member 和 score 交替排列的数组，和 HRANDFIELD WITHVALUES 一样不分组
+// score 在 RESP3 下是 double，RESP2 下会变成 bulk string
+fn scored_array(members: Vec<(Bytes, f64)>, with_scores: bool) -> RespFrame {
+    let mut frames = Vec::with_capacity(members.len() * if with_scores { 2 } else { 1 });
+    for (member, score) in members {
+        frames.push(BulkString::from(member).into());
+        if with_scores {
+            frames.push(RespFrame::Double(score));
+        }
+    }
+    RespArray::new(frames).into()
+}
+
+// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+impl TryFrom<RespArray> for ZAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .peekable();
+        let key = args.next().ok_or_else(|| wrong_args("zadd"))?;
+
+        let mut options = ZAddOptions::default();
+        let mut incr = false;
+        while let Some(arg) = args.peek() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => options.nx = true,
+                b"xx" => options.xx = true,
+                b"gt" => options.gt = true,
+                b"lt" => options.lt = true,
+                b"ch" => options.ch = true,
+                b"incr" => incr = true,
+                _ => break,
+            }
+            args.next();
+        }
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(syntax_error());
+        }
+        if options.nx && options.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if [options.nx, options.gt, options.lt]
+            .iter()
+            .filter(|set| **set)
+            .count()
+            > 1
+        {
+            return Err(CommandError::InvalidArgument(
+                "GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if incr && args.len() > 2 {
+            return Err(CommandError::InvalidArgument(
+                "INCR option supports a single increment-element pair".to_string(),
+            ));
+        }
+
+        let members = args
+            .chunks(2)
+            .map(|pair| Ok((parse_f64(&pair[0])?, pair[1].clone())))
+            .collect::<Result<Vec<_>, CommandError>>()?;
+
+        Ok(ZAdd {
+            key,
+            options,
+            incr,
+            members,
+        })
+    }
+}
+
+// ZINCRBY key increment member
+impl TryFrom<RespArray> for ZIncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zincrby"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(increment), Some(member)) = (args.next(), args.next(), args.next())
+        else {
+            return Err(wrong_args("zincrby"));
+        };
+
+        Ok(ZIncrBy {
+            key: bytes_arg(key)?,
+            increment: parse_f64(&bytes_arg(increment)?)?,
+            member: bytes_arg(member)?,
+        })
+    }
+}
+
+// ZREM key member [member ...]
+impl TryFrom<RespArray> for ZRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = key_and_values(value, "zrem")?;
+        Ok(ZRem { key, members })
+    }
+}
+
+// ZSCORE key member
+impl TryFrom<RespArray> for ZScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zscore"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(member)) = (args.next(), args.next()) else {
+            return Err(wrong_args("zscore"));
+        };
+
+        Ok(ZScore {
+            key: bytes_arg(key)?,
+            member: bytes_arg(member)?,
+        })
+    }
+}
+
+// ZMSCORE key member [member ...]
+impl TryFrom<RespArray> for ZMScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = key_and_values(value, "zmscore")?;
+        Ok(ZMScore { key, members })
+    }
+}
+
+// ZCARD key
+impl TryFrom<RespArray> for ZCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcard"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = bytes_arg(args.next().ok_or_else(|| wrong_args("zcard"))?)?;
+        Ok(ZCard { key })
+    }
+}
+
+// ZCOUNT key min max
+impl TryFrom<RespArray> for ZCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcount"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), Some(min), Some(max)) = (args.next(), args.next(), args.next()) else {
+            return Err(wrong_args("zcount"));
+        };
+
+        Ok(ZCount {
+            key: bytes_arg(key)?,
+            min: parse_score_bound(&bytes_arg(min)?)?,
+            max: parse_score_bound(&bytes_arg(max)?)?,
+        })
+    }
+}
+
+// ZRANK key member [WITHSCORE]
+// ZREVRANK key member [WITHSCORE]
+impl TryFrom<RespArray> for ZRank {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let rev = command_name(&value) == "zrevrank";
+        let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+        let (Some(key), Some(member)) = (args.next(), args.next()) else {
+            return Err(wrong_args(if rev { "zrevrank" } else { "zrank" }));
+        };
+
+        let with_score = match (args.next().transpose()?, args.next()) {
+            (None, _) => false,
+            (Some(opt), None) if opt.eq_ignore_ascii_case(b"withscore") => true,
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(ZRank {
+            key: key?,
+            member: member?,
+            rev,
+            with_score,
+        })
+    }
+}
+
+// ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+impl TryFrom<RespArray> for ZRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+        let key = args.next().ok_or_else(|| wrong_args("zrange"))??;
+        let (spec, with_scores) = parse_range_spec(args, true)?;
+
+        Ok(ZRange {
+            key,
+            spec,
+            with_scores,
+        })
+    }
+}
+
+// ZRANGESTORE dst src min max [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+impl TryFrom<RespArray> for ZRangeStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+        let (Some(destination), Some(source)) = (args.next(), args.next()) else {
+            return Err(wrong_args("zrangestore"));
+        };
+        let (spec, _) = parse_range_spec(args, false)?;
+
+        Ok(ZRangeStore {
+            destination: destination?,
+            source: source?,
+            spec,
+        })
+    }
+}
+
+// ZPOPMIN key [count]
+// ZPOPMAX key [count]
+impl TryFrom<RespArray> for ZPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let end = if name == "zpopmax" {
+            ZEnd::Max
+        } else {
+            ZEnd::Min
+        };
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(key), count, None) = (args.next(), args.next(), args.next()) else {
+            return Err(syntax_error());
+        };
+        let count = count
+            .map(|count| parse_count(&bytes_arg(count)?))
+            .transpose()?;
+
+        Ok(ZPop {
+            key: bytes_arg(key)?,
+            end,
+            count,
+        })
+    }
+}
+
+// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]]
+//   [AGGREGATE <SUM | MIN | MAX>]
+// ZINTERSTORE 的参数一样
+impl TryFrom<RespArray> for ZOpStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let op = if name == "zinterstore" {
+            SetOperation::Inter
+        } else {
+            SetOperation::Union
+        };
+
+        let mut args = extract_args(value, 1)?.into_iter().map(bytes_arg);
+        let (Some(destination), Some(numkeys)) = (args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let numkeys = parse_i64(&numkeys?)?;
+        if numkeys <= 0 {
+            return Err(CommandError::InvalidArgument(format!(
+                "at least 1 input key is needed for '{}' command",
+                name
+            )));
+        }
+
+        let keys = args
+            .by_ref()
+            .take(numkeys as usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.len() != numkeys as usize {
+            return Err(syntax_error());
+        }
+
+        let mut weights = Vec::new();
+        let mut aggregate = Aggregate::default();
+        while let Some(opt) = args.next() {
+            match opt?.to_ascii_lowercase().as_slice() {
+                b"weights" => {
+                    weights = args
+                        .by_ref()
+                        .take(keys.len())
+                        .map(|weight| {
+                            parse_f64(&weight?).map_err(|_| {
+                                CommandError::InvalidArgument(
+                                    "weight value is not a float".to_string(),
+                                )
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if weights.len() != keys.len() {
+                        return Err(syntax_error());
+                    }
+                }
+                b"aggregate" => {
+                    let arg = args.next().ok_or_else(syntax_error)??;
+                    aggregate = match arg.to_ascii_lowercase().as_slice() {
+                        b"sum" => Aggregate::Sum,
+                        b"min" => Aggregate::Min,
+                        b"max" => Aggregate::Max,
+                        _ => return Err(syntax_error()),
+                    };
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        Ok(ZOpStore {
+            op,
+            destination: destination?,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+// BZPOPMIN key [key ...] timeout
+// BZPOPMAX key [key ...] timeout
+// BZMPOP timeout numkeys key [key ...] <MIN | MAX> [COUNT count]
+impl TryFrom<RespArray> for BZPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&value);
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(bytes_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if name != "bzmpop" {
+            let timeout = args.pop().ok_or_else(|| wrong_args(&name))?;
+            if args.is_empty() {
+                return Err(wrong_args(&name));
+            }
+            return Ok(BZPop {
+                keys: args,
+                end: if name == "bzpopmax" {
+                    ZEnd::Max
+                } else {
+                    ZEnd::Min
+                },
+                count: None,
+                timeout: parse_timeout(&timeout)?,
+            });
+        }
+
+        let mut args = args.into_iter();
+        let (Some(timeout), Some(numkeys)) = (args.next(), args.next()) else {
+            return Err(wrong_args(&name));
+        };
+        let timeout = parse_timeout(&timeout)?;
+        let numkeys = parse_i64(&numkeys)?;
+        if numkeys <= 0 {
+            return Err(CommandError::InvalidArgument(
+                "numkeys should be greater than 0".to_string(),
+            ));
+        }
+        let keys = args.by_ref().take(numkeys as usize).collect::<Vec<_>>();
+        let end = match args.next() {
+            Some(end) if keys.len() == numkeys as usize => parse_zend(&end)?,
+            _ => return Err(syntax_error()),
+        };
+
+        let count = match (args.next(), args.next(), args.next()) {
+            (None, None, None) => 1,
+            (Some(opt), Some(count), None) if opt.eq_ignore_ascii_case(b"count") => {
+                match parse_i64(&count)? {
+                    count if count > 0 => count as usize,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "count should be greater than 0".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => return Err(syntax_error()),
+        };
+
+        Ok(BZPop {
+            keys,
+            end,
+            count: Some(count),
+            timeout,
+        })
+    }
+}
+
+fn parse_zend(arg: &[u8]) -> Result<ZEnd, CommandError> {
+    match arg.to_ascii_lowercase().as_slice() {
+        b"min" => Ok(ZEnd::Min),
+        b"max" => Ok(ZEnd::Max),
+        _ => Err(syntax_error()),
+    }
+}
+
+// ZRANGE 和 ZRANGESTORE 共用的 start stop 和选项，只有 ZRANGE 支持 WITHSCORES
+fn parse_range_spec(
+    mut args: impl Iterator<Item = Result<Bytes, CommandError>>,
+    allow_with_scores: bool,
+) -> Result<(ZRangeSpec, bool), CommandError> {
+    let (Some(start), Some(stop)) = (args.next(), args.next()) else {
+        return Err(syntax_error());
+    };
+    let (start, stop) = (start?, stop?);
+
+    let (mut by_score, mut by_lex, mut rev, mut with_scores) = (false, false, false, false);
+    let mut limit = None;
+    while let Some(opt) = args.next() {
+        match opt?.to_ascii_lowercase().as_slice() {
+            b"byscore" => by_score = true,
+            b"bylex" => by_lex = true,
+            b"rev" => rev = true,
+            b"withscores" if allow_with_scores => with_scores = true,
+            b"limit" => {
+                let (Some(offset), Some(count)) = (args.next(), args.next()) else {
+                    return Err(syntax_error());
+                };
+                limit = Some((parse_i64(&offset?)?, parse_i64(&count?)?));
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    if by_score && by_lex {
+        return Err(syntax_error());
+    }
+    if limit.is_some() && !by_score && !by_lex {
+        return Err(CommandError::InvalidArgument(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .to_string(),
+        ));
+    }
+    if with_scores && by_lex {
+        return Err(CommandError::InvalidArgument(
+            "syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+        ));
+    }
+
+    // REV 时 BYSCORE 和 BYLEX 的参数是先 max 后 min
+    let (min, max) = if rev {
+        (&stop, &start)
+    } else {
+        (&start, &stop)
+    };
+    let by = if by_score {
+        ZRangeBy::Score(parse_score_bound(min)?, parse_score_bound(max)?)
+    } else if by_lex {
+        ZRangeBy::Lex(parse_lex_bound(min)?, parse_lex_bound(max)?)
+    } else {
+        ZRangeBy::Rank(parse_i64(&start)?, parse_i64(&stop)?)
+    };
+
+    Ok((ZRangeSpec { by, rev, limit }, with_scores))
+}
+
+// 1.5、(1.5、-inf、+inf
+fn parse_score_bound(arg: &[u8]) -> Result<ScoreBound, CommandError> {
+    let (value, exclusive) = match arg.strip_prefix(b"(") {
+        Some(value) => (value, true),
+        None => (arg, false),
+    };
+    let value = parse_f64(value)
+        .map_err(|_| CommandError::InvalidArgument("min or max is not a float".to_string()))?;
+    Ok(ScoreBound { value, exclusive })
+}
+
+// -、+、[member、(member
+fn parse_lex_bound(arg: &Bytes) -> Result<LexBound, CommandError> {
+    match arg.first() {
+        Some(b'-') if arg.len() == 1 => Ok(LexBound::NegInf),
+        Some(b'+') if arg.len() == 1 => Ok(LexBound::PosInf),
+        Some(b'[') => Ok(LexBound::Inclusive(arg.slice(1..))),
+        Some(b'(') => Ok(LexBound::Exclusive(arg.slice(1..))),
+        _ => Err(CommandError::InvalidArgument(
+            "min or max not valid string range item".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{exec, Command};
+    use crate::{Backend, BulkString, RespArray, RespFrame, RespNull, RespNullArray, SimpleError};
+    use anyhow::Result;
+    use std::future::pending;
+
+    fn scored(items: &[(&str, f64)]) -> RespFrame {
+        let frames = items
+            .iter()
+            .flat_map(|(member, score)| {
+                [BulkString::new(*member).into(), RespFrame::Double(*score)]
+            })
+            .collect::<Vec<_>>();
+        RespArray::new(frames).into()
+    }
+
+    // BZPOPMIN、BZPOPMAX 的回复
+    fn popped(key: &str, member: &str, score: f64) -> RespFrame {
+        RespArray::new(vec![
+            BulkString::new(key).into(),
+            BulkString::new(member).into(),
+            RespFrame::Double(score),
+        ])
+        .into()
+    }
+
+    fn bulks(items: &[&str]) -> RespFrame {
+        let frames = items
+            .iter()
+            .map(|item| BulkString::new(*item).into())
+            .collect::<Vec<RespFrame>>();
+        RespArray::new(frames).into()
+    }
+
+    #[test]
+    fn test_zadd_commands() -> Result<()> {
+        let backend = Backend::new();
+
+        assert_eq!(exec(&backend, &["zadd", "z", "1", "a", "2", "b"]), 2.into());
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "xx", "ch", "5", "a", "1", "c"]),
+            1.into()
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "incr", "1.5", "a"]),
+            RespFrame::Double(6.5)
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "nx", "incr", "1", "a"]),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            exec(&backend, &["zincrby", "z", "-1", "b"]),
+            RespFrame::Double(1.0)
+        );
+        assert_eq!(
+            exec(&backend, &["zscore", "z", "a"]),
+            RespFrame::Double(6.5)
+        );
+        // RESP2 下分数是 bulk string
+        assert_eq!(
+            exec(&backend, &["zscore", "z", "a"]).into_resp2(),
+            BulkString::new("6.5").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zmscore", "z", "b", "x"]),
+            RespArray::new(vec![RespFrame::Double(1.0), RespFrame::Null(RespNull)]).into()
+        );
+        assert_eq!(exec(&backend, &["zcard", "z"]), 2.into());
+        assert_eq!(exec(&backend, &["zrem", "z", "b", "x"]), 1.into());
+
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "nx", "xx", "1", "a"]),
+            SimpleError::new("ERR XX and NX options at the same time are not compatible").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "gt", "lt", "1", "a"]),
+            SimpleError::new("ERR GT, LT, and/or NX options at the same time are not compatible")
+                .into()
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "incr", "1", "a", "2", "b"]),
+            SimpleError::new("ERR INCR option supports a single increment-element pair").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "1", "a", "2"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zadd", "z", "nan", "a"]),
+            SimpleError::new("ERR value is not a valid float").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_commands() -> Result<()> {
+        let backend = Backend::new();
+        exec(
+            &backend,
+            &["zadd", "z", "1", "a", "2", "b", "3", "c", "4", "d"],
+        );
+
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "0", "1"]),
+            bulks(&["a", "b"])
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "0", "0", "rev", "withscores"]),
+            scored(&[("d", 4.0)])
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["zrange", "z", "(1", "+inf", "byscore", "limit", "1", "5"]
+            ),
+            bulks(&["c", "d"])
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "3", "-inf", "byscore", "rev"]),
+            bulks(&["c", "b", "a"])
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "[b", "(d", "bylex"]),
+            bulks(&["b", "c"])
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "0", "1", "limit", "0", "1"]),
+            SimpleError::new(
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+            )
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "x", "1", "byscore"]),
+            SimpleError::new("ERR min or max is not a float").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "z", "a", "b", "bylex"]),
+            SimpleError::new("ERR min or max not valid string range item").into()
+        );
+
+        assert_eq!(exec(&backend, &["zcount", "z", "2", "(4"]), 2.into());
+        assert_eq!(exec(&backend, &["zrank", "z", "c"]), 2.into());
+        assert_eq!(
+            exec(&backend, &["zrevrank", "z", "c", "withscore"]),
+            RespArray::new(vec![1.into(), RespFrame::Double(3.0)]).into()
+        );
+        assert_eq!(
+            exec(&backend, &["zrank", "z", "x"]),
+            RespFrame::Null(RespNull)
+        );
+
+        assert_eq!(
+            exec(
+                &backend,
+                &["zrangestore", "dst", "z", "2", "+inf", "byscore"]
+            ),
+            3.into()
+        );
+        assert_eq!(exec(&backend, &["zcard", "dst"]), 3.into());
+
+        assert_eq!(exec(&backend, &["zpopmin", "z"]), scored(&[("a", 1.0)]));
+        assert_eq!(
+            exec(&backend, &["zpopmax", "z", "2"]),
+            scored(&[("d", 4.0), ("c", 3.0)])
+        );
+        assert_eq!(exec(&backend, &["zpopmax", "missing"]), bulks(&[]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zopstore_commands() -> Result<()> {
+        let backend = Backend::new();
+        exec(&backend, &["zadd", "a", "1", "x", "2", "y"]);
+        exec(&backend, &["zadd", "b", "3", "y", "4", "z"]);
+
+        assert_eq!(
+            exec(
+                &backend,
+                &["zunionstore", "u", "2", "a", "b", "weights", "2", "1"]
+            ),
+            3.into()
+        );
+        assert_eq!(
+            exec(&backend, &["zrange", "u", "0", "-1", "withscores"]),
+            scored(&[("x", 2.0), ("z", 4.0), ("y", 7.0)])
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["zinterstore", "i", "2", "a", "b", "aggregate", "min"]
+            ),
+            1.into()
+        );
+        assert_eq!(
+            exec(&backend, &["zscore", "i", "y"]),
+            RespFrame::Double(2.0)
+        );
+
+        assert_eq!(
+            exec(&backend, &["zunionstore", "u", "0", "a"]),
+            SimpleError::new("ERR at least 1 input key is needed for 'zunionstore' command").into()
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["zunionstore", "u", "2", "a", "b", "weights", "1"]
+            ),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(
+            exec(&backend, &["zinterstore", "u", "1", "a", "weights", "x"]),
+            SimpleError::new("ERR weight value is not a float").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_pop_commands_without_waiting() -> Result<()> {
+        let backend = Backend::new();
+        exec(&backend, &["zadd", "b", "1", "x", "2", "y", "3", "z"]);
+
+        // 直接执行的时候不会等待
+        assert_eq!(
+            exec(&backend, &["bzpopmax", "a", "b", "0"]),
+            popped("b", "z", 3.0)
+        );
+        assert_eq!(
+            exec(
+                &backend,
+                &["bzmpop", "0", "2", "a", "b", "min", "count", "5"]
+            ),
+            RespArray::new(vec![
+                BulkString::new("b").into(),
+                RespArray::new(vec![scored(&[("x", 1.0)]), scored(&[("y", 2.0)])]).into()
+            ])
+            .into()
+        );
+        assert_eq!(
+            exec(&backend, &["bzpopmin", "a", "b", "0"]),
+            RespNullArray.into()
+        );
+
+        assert_eq!(
+            exec(&backend, &["bzpopmin", "a", "-1"]),
+            SimpleError::new("ERR timeout is negative").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bzmpop", "0", "1", "a", "left"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+        assert_eq!(
+            exec(&backend, &["bzmpop", "0", "1", "a", "max", "count", "0"]),
+            SimpleError::new("ERR count should be greater than 0").into()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop_commands_wait() -> Result<()> {
+        let backend = Backend::new();
+        let command = |args: &[&str]| {
+            let frames = args
+                .iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>();
+            Command::try_from(RespArray::new(frames))
+        };
+
+        let cmd = command(&["bzpopmin", "q", "0.01"])?;
+        assert_eq!(
+            cmd.execute_async(&backend, pending()).await,
+            RespNullArray.into()
+        );
+
+        // 先阻塞的客户端先拿到分数最小的成员
+        let mut waiters = Vec::new();
+        for args in [["bzpopmin", "q", "0"], ["bzpopmin", "q", "0"]] {
+            let (client, cmd) = (backend.clone(), command(&args)?);
+            waiters.push(tokio::spawn(async move {
+                cmd.execute_async(&client, pending()).await
+            }));
+            while backend.blocked_clients() < waiters.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        assert_eq!(
+            exec(&backend, &["zadd", "q", "2", "b", "1", "a", "3", "c"]),
+            3.into()
+        );
+        assert_eq!(waiters.remove(0).await?, popped("q", "a", 1.0));
+        assert_eq!(waiters.remove(0).await?, popped("q", "b", 2.0));
+        assert_eq!(exec(&backend, &["zcard", "q"]), 1.into());
+
+        // ZUNIONSTORE 写入的 key 也会唤醒等待的客户端
+        let waiter = {
+            let (client, cmd) = (backend.clone(), command(&["bzmpop", "0", "1", "u", "max"])?);
+            tokio::spawn(async move { cmd.execute_async(&client, pending()).await })
+        };
+        while backend.blocked_clients() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(exec(&backend, &["zunionstore", "u", "1", "q"]), 1.into());
+        assert_eq!(
+            waiter.await?,
+            RespArray::new(vec![
+                BulkString::new("u").into(),
+                RespArray::new(vec![scored(&[("c", 3.0)])]).into()
+            ])
+            .into()
+        );
+        Ok(())
+    }
+}
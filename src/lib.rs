@@ -3,7 +3,6 @@ pub mod cmd;
 pub mod network;
 pub mod resp;
 
-pub use backend::*;
 pub use backend::*;
 pub use network::*;
 pub use resp::*;
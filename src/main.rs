@@ -1,4 +1,5 @@
-use rs_simple_redis::{stream_handler, Backend};
+use anyhow::{anyhow, bail};
+use rs_simple_redis::{stream_handler, Backend, ProtocolLimits};
 use tokio::net::TcpListener;
 use tracing::{info, warn};
 use tracing_subscriber::fmt::Subscriber;
@@ -21,7 +22,12 @@ async fn main() -> anyhow::Result<()> {
 
     let listener = TcpListener::bind(addr).await?;
 
+    let limits = parse_limits(std::env::args().skip(1))?;
+    info!("Protocol limits: {:?}", limits);
+
     let backend = Backend::new();
+    // 后台定期清理已经过期的 key
+    backend.spawn_active_expire();
 
     loop {
         let (stream, remote_socket_addr) = listener.accept().await?;
@@ -29,7 +35,7 @@ async fn main() -> anyhow::Result<()> {
         let cloned_backend = backend.clone();
 
         tokio::spawn(async move {
-            match stream_handler(stream, cloned_backend).await {
+            match stream_handler(stream, cloned_backend, limits).await {
                 Ok(_) => {
                     info!("Connection from {} exited", remote_socket_addr);
                 }
@@ -41,3 +47,26 @@ async fn main() -> anyhow::Result<()> {
         });
     }
 }
+
+// 和 redis 的配置项同名: --proto-max-bulk-len 1048576 --proto-max-multibulk-len 1024 ...
+fn parse_limits(mut args: impl Iterator<Item = String>) -> anyhow::Result<ProtocolLimits> {
+    let mut limits = ProtocolLimits::default();
+
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("missing value for {}", name))?
+            .parse::<usize>()
+            .map_err(|e| anyhow!("invalid value for {}: {}", name, e))?;
+
+        match name.trim_start_matches("--") {
+            "proto-max-bulk-len" => limits.max_bulk_len = value,
+            "proto-max-multibulk-len" => limits.max_multibulk_len = value,
+            "proto-max-nesting-depth" => limits.max_depth = value,
+            "proto-max-inline-len" => limits.max_inline_len = value,
+            _ => bail!("unknown option {}", name),
+        }
+    }
+
+    Ok(limits)
+}
@@ -1,15 +1,27 @@
-use crate::cmd::{Command, CommandExecutor};
-use crate::{Backend, RespDecode, RespEncode, RespError, RespFrame};
+use crate::cmd::{Command, Session};
+use crate::{
+    Backend, ProtocolLimits, RespEncode, RespFrame, RespFrameDecoder, RespProtocol, SimpleError,
+};
 use anyhow::Result;
 use bytes::BytesMut;
 use futures::SinkExt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::net::TcpStream;
 use tokio_stream::StreamExt;
 use tokio_util::codec::{Decoder, Encoder, Framed};
-use tracing::info;
+use tracing::{info, warn};
+
+// 每个连接分配一个递增的 id，HELLO 会回复这个 id
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
 
 #[derive(Debug)]
-struct RespFrameCodec;
+struct RespFrameCodec {
+    // 回复时使用的协议版本，RESP2 连接会把 RESP3 的 frame 降级
+    protocol: RespProtocol,
+    // 流式解码器，数据不完整时保存解析进度，下次收到数据后继续
+    decoder: RespFrameDecoder,
+}
 
 #[derive(Debug)]
 struct RedisRequest {
@@ -22,13 +34,23 @@ struct RedisResponse {
     frame: RespFrame,
 }
 
-pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+pub async fn stream_handler(
+    stream: TcpStream,
+    backend: Backend,
+    limits: ProtocolLimits,
+) -> Result<()> {
     // 使用 tokio 框架对网络流进行封装
     // Framed 提供了一种方便的方式来处理网络协议
     // stream 代表底层的网络连接
     // RespFrameCodec 定义了如何对数据进行编码和解码
     // 自定义网络协议，使用 Frame 来处理
-    let mut framed = Framed::new(stream, RespFrameCodec);
+    // 解码器按照 limits 检查声明的长度和嵌套深度，超出限制时当作协议错误处理
+    let codec = RespFrameCodec {
+        protocol: RespProtocol::default(),
+        decoder: RespFrameDecoder::for_requests(limits),
+    };
+    let mut framed = Framed::new(stream, codec);
+    let mut session = Session::new(NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed));
 
     loop {
         match framed.next().await {
@@ -41,24 +63,63 @@ pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
                     backend: backend.clone(),
                 };
 
-                let response = request_handler(request).await?;
+                // 阻塞命令等待的时候客户端可能断开连接，这时候不再等待，和超时一样回复
+                // 已经替它取走的数据仍然会回复，客户端只是关闭了写的一端时也能收到
+                let closed = closed(framed.get_ref());
+                let response = request_handler(request, &mut session, closed).await?;
+
+                // HELLO 可能切换了协议，回复本身就要使用新的协议
+                framed.codec_mut().protocol = session.protocol;
 
                 info!("Sending response: {:?}", response);
                 // send 方法是 SinkExt 这个 trait 中的
                 framed.send(response.frame).await?;
             }
-            Some(Err(e)) => return Err(e),
+            Some(Err(e)) => {
+                // 字节流已经无法解析了，和 redis 一样先回复 protocol error，再关闭连接
+                let frame = SimpleError::new(format!("ERR Protocol error: {}", e)).into();
+                framed.send(frame).await?;
+                return Err(e);
+            }
             None => return Ok(()),
         }
     }
 }
 
-async fn request_handler(request: RedisRequest) -> Result<RedisResponse> {
+// 客户端关闭连接时返回，有新的请求到达时就没法再判断了，一直等待
+async fn closed(stream: &TcpStream) {
+    let mut buf = [0; 1];
+    if let Ok(n) = stream.peek(&mut buf).await {
+        if n > 0 {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+async fn request_handler(
+    request: RedisRequest,
+    session: &mut Session,
+    closed: impl Future<Output = ()>,
+) -> Result<RedisResponse> {
     let (frame, backend) = (request.frame, request.backend);
-    let cmd = Command::try_from(frame)?;
-    info!("Executing command: {:?}", cmd);
 
-    let frame = cmd.execute(&backend);
+    // 命令解析失败时回复错误，连接继续可用
+    let frame = match Command::try_from(frame) {
+        // HELLO 需要修改连接的状态，不走 backend
+        Ok(Command::Hello(hello)) => {
+            info!("Executing command: {:?}", hello);
+            hello.apply(session)
+        }
+        Ok(cmd) => {
+            info!("Executing command: {:?}", cmd);
+            cmd.execute_async(&backend, closed).await
+        }
+        Err(e) => {
+            warn!("Invalid command: {}", e);
+            e.into()
+        }
+    };
+
     Ok(RedisResponse { frame })
 }
 
@@ -71,8 +132,12 @@ impl Encoder<RespFrame> for RespFrameCodec {
         item: RespFrame,
         dst: &mut BytesMut,
     ) -> std::result::Result<(), Self::Error> {
-        let encoded = item.encode();
-        dst.extend_from_slice(&encoded);
+        let item = match self.protocol {
+            RespProtocol::Resp2 => item.into_resp2(),
+            RespProtocol::Resp3 => item.into_resp3(),
+        };
+
+        item.encode_to(dst);
         Ok(())
     }
 }
@@ -88,11 +153,7 @@ impl Decoder for RespFrameCodec {
     ) -> std::result::Result<Option<Self::Item>, Self::Error> {
         info!("decode stream to frame");
 
-        // 调用 decode 模块，将 bytes 转换为 frame
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+        // 调用 decode 模块，将 bytes 转换为 frame，数据不完整时返回 None
+        Ok(self.decoder.decode(src)?)
     }
 }
@@ -14,427 +14,583 @@
     - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
     - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
     - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
+    - verbatim string: "=<length>\r\n<format>:<data>\r\n"
+    - big number: "(<big number>\r\n"
+    - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+    - attribute: "|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><frame>"
  */
 
 use crate::{
-    BulkString, RespArray, RespDecode, RespError, RespFrame, RespMap, RespNull, RespNullArray,
-    RespNullBulkString, RespSet, SimpleError, SimpleString,
+    BigNumber, BulkError, BulkString, RespArray, RespAttribute, RespDecode, RespError, RespFrame,
+    RespMap, RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet, SimpleError,
+    SimpleString, VerbatimString,
 };
-use bytes::{Buf, BytesMut};
-use tracing::info;
+use bytes::{Bytes, BytesMut};
+use std::ops::Deref;
 
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
+// 超过这个长度的 bulk string 不拷贝，直接共享读缓冲区
+const ZERO_COPY_THRESHOLD: usize = 1024;
+
+/// 解码时的协议限制，防止客户端声明一个超大的长度或者很深的嵌套，让服务器无限制地缓存数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// 单个 bulk string 的最大长度，对应 redis 的 proto-max-bulk-len
+    pub max_bulk_len: usize,
+    /// array/set/push 的最大元素个数，map/attribute 的最大 entry 个数
+    pub max_multibulk_len: usize,
+    /// 聚合类型的最大嵌套深度
+    pub max_depth: usize,
+    /// inline 命令以及类型、长度这一行的最大长度
+    pub max_inline_len: usize,
+}
 
-impl RespDecode for RespFrame {
-    const PREFIX: &'static str = "";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'+') => {
-                let frame = SimpleString::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'-') => {
-                let frame = SimpleError::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b':') => {
-                let frame = i64::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'$') => {
-                // try null bulk string first
-                match RespNullBulkString::decode(buf) {
-                    Ok(frame) => Ok(frame.into()),
-                    Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                    Err(_) => {
-                        let frame = BulkString::decode(buf)?;
-                        Ok(frame.into())
-                    }
-                }
-            }
-            Some(b'*') => {
-                // try null array first
-                info!("decode buf start with *");
-                match RespNullArray::decode(buf) {
-                    Ok(frame) => Ok(frame.into()),
-                    Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                    Err(_) => {
-                        let frame = RespArray::decode(buf)?;
-                        Ok(frame.into())
-                    }
-                }
-            }
-            Some(b'_') => {
-                let frame = RespNull::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'#') => {
-                let frame = bool::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b',') => {
-                let frame = f64::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'%') => {
-                let frame = RespMap::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'~') => {
-                let frame = RespSet::decode(buf)?;
-                Ok(frame.into())
-            }
-            _ => Err(RespError::InvalidFrameType(format!(
-                "expect_length: unknown frame type: {:?}",
-                buf
-            ))),
-        }
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
-            Some(b'$') => BulkString::expect_length(buf),
-            Some(b':') => i64::expect_length(buf),
-            Some(b'+') => SimpleString::expect_length(buf),
-            Some(b'-') => SimpleError::expect_length(buf),
-            Some(b'#') => bool::expect_length(buf),
-            Some(b',') => f64::expect_length(buf),
-            Some(b'_') => RespNull::expect_length(buf),
-            _ => Err(RespError::NotComplete),
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: i32::MAX as usize,
+            max_depth: 128,
+            max_inline_len: 64 * 1024,
         }
     }
 }
 
-impl RespDecode for SimpleString {
-    const PREFIX: &'static str = "+";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        // split the buffer
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(SimpleString::new(s.to_string()))
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
-    }
+/// 流式的 RESP 解码器，用于 RespFrameCodec
+///
+/// 它不会先计算整个 frame 的长度再重新解析一遍，
+/// 而是每解析完一个 token 就把它从 buf 中取走，未完成的 array/map/set 保存在栈上，
+/// 数据不完整时记住扫描到的位置，下次收到数据后从这里继续，每个字节只会被扫描一次
+#[derive(Debug, Default)]
+pub struct RespFrameDecoder {
+    // 还没有收齐元素的聚合类型，栈顶是最内层
+    stack: Vec<Pending>,
+    // 已经读取了长度，还在等待数据的 bulk string / bulk error / verbatim string
+    bulk: Option<(u8, usize)>,
+    // 当前行已经扫描过、确定没有 CRLF 的字节数
+    scanned: usize,
+    limits: ProtocolLimits,
+    // 解码客户端的请求，只有 * 开头的才是 RESP，其他的都是 inline 命令
+    request: bool,
 }
 
-impl RespDecode for SimpleError {
-    const PREFIX: &'static str = "-";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        // split the buffer
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(SimpleError::new(s.to_string()))
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
-    }
+#[derive(Debug)]
+struct Pending {
+    prefix: u8,
+    // 还需要多少个 frame，map 每个 entry 需要两个，attribute 最后还要一个真正的回复
+    remaining: usize,
+    frames: Vec<RespFrame>,
 }
 
-impl RespDecode for RespNull {
-    const PREFIX: &'static str = "_";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "_\r\n", "Null")?;
-        Ok(RespNull)
-    }
+// 解码器读取的缓冲区，每解析完一个 token 就从前面取走
+// BytesMut 是连接的读缓冲区，Bytes 用于 RespDecode 在不修改调用方缓冲区的情况下试探解码
+trait DecodeBuf: Deref<Target = [u8]> {
+    fn take(&mut self, n: usize) -> Bytes;
+}
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(3)
+impl DecodeBuf for BytesMut {
+    fn take(&mut self, n: usize) -> Bytes {
+        self.split_to(n).freeze()
     }
 }
 
-impl RespDecode for RespNullArray {
-    const PREFIX: &'static str = "*";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "*-1\r\n", "NullArray")?;
-        Ok(RespNullArray)
+impl DecodeBuf for Bytes {
+    fn take(&mut self, n: usize) -> Bytes {
+        self.split_to(n)
     }
+}
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(4)
-    }
+// 解析一个 token 的结果
+enum Token {
+    Frame(RespFrame),
+    Aggregate(u8, usize),
 }
 
-impl RespDecode for RespNullBulkString {
-    const PREFIX: &'static str = "$";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "$-1\r\n", "NullBulkString")?;
-        Ok(RespNullBulkString)
+impl RespFrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(5)
+    pub fn with_limits(limits: ProtocolLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
     }
-}
 
-impl RespDecode for i64 {
-    const PREFIX: &'static str = ":";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        // split the buffer
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(s.parse()?)
+    /// 服务端解码请求用的解码器，和 redis 一样只把 * 开头的数据当作 RESP，
+    /// 这样 inline 命令可以以 -、:、( 这些字符开头
+    pub fn for_requests(limits: ProtocolLimits) -> Self {
+        Self {
+            limits,
+            request: true,
+            ..Default::default()
+        }
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+    /// 解析出一个完整的 frame，数据不够时返回 Ok(None)，已经读取的数据保存在解码器内部
+    pub fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+        self.decode_from(buf)
     }
-}
 
-impl RespDecode for bool {
-    const PREFIX: &'static str = "#";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        match extract_fixed_data(buf, "#t\r\n", "Bool") {
-            Ok(_) => Ok(true),
-            Err(RespError::NotComplete) => Err(RespError::NotComplete),
-            Err(_) => match extract_fixed_data(buf, "#f\r\n", "Bool") {
-                Ok(_) => Ok(false),
-                Err(e) => Err(e),
-            },
+    fn decode_from(&mut self, buf: &mut impl DecodeBuf) -> Result<Option<RespFrame>, RespError> {
+        let ret = self.decode_inner(buf);
+        if ret.is_err() {
+            // 出错之后字节流已经无法恢复，状态也没有意义了
+            self.stack.clear();
+            self.bulk = None;
+            self.scanned = 0;
         }
+        ret
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(4)
-    }
-}
+    fn decode_inner(&mut self, buf: &mut impl DecodeBuf) -> Result<Option<RespFrame>, RespError> {
+        // 不是以 RESP 类型开头的是 inline 命令，比如 telnet 里直接输入的 "SET a b\r\n"
+        // 解码请求时只认 *，其他前缀都当作 inline 命令
+        if self.stack.is_empty() && self.bulk.is_none() {
+            while let Some(b) = buf.first() {
+                if *b == b'*' || (!self.request && is_resp_prefix(*b)) {
+                    break;
+                }
 
-impl RespDecode for BulkString {
-    const PREFIX: &'static str = "$";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let remained = &buf[end + CRLF_LEN..];
-        if remained.len() < len + CRLF_LEN {
-            return Err(RespError::NotComplete);
+                match self.next_inline(buf)? {
+                    None => return Ok(None),
+                    // 空行直接忽略
+                    Some(args) if args.is_empty() => continue,
+                    Some(args) => return Ok(Some(RespArray::new(args).into())),
+                }
+            }
         }
 
-        buf.advance(end + CRLF_LEN);
+        loop {
+            let mut frame = match self.next_token(buf)? {
+                None => return Ok(None),
+                Some(Token::Frame(frame)) => frame,
+                Some(Token::Aggregate(prefix, n)) => {
+                    let remaining = match prefix {
+                        b'%' => n * 2,
+                        b'|' => n * 2 + 1,
+                        _ => n,
+                    };
+
+                    if remaining == 0 {
+                        build_aggregate(prefix, Vec::new())?
+                    } else if self.stack.len() >= self.limits.max_depth {
+                        return Err(RespError::NestingTooDeep(self.limits.max_depth));
+                    } else {
+                        self.stack.push(Pending {
+                            prefix,
+                            remaining,
+                            frames: Vec::with_capacity(remaining.min(1024)),
+                        });
+                        continue;
+                    }
+                }
+            };
+
+            // 把完成的 frame 放入外层，外层也完成了就继续向上
+            loop {
+                let Some(pending) = self.stack.last_mut() else {
+                    return Ok(Some(frame));
+                };
+
+                pending.frames.push(frame);
+                pending.remaining -= 1;
+                if pending.remaining > 0 {
+                    break;
+                }
 
-        let data = buf.split_to(len + CRLF_LEN);
-        Ok(BulkString::new(data[..len].to_vec()))
+                let pending = self.stack.pop().expect("stack must not be empty");
+                frame = build_aggregate(pending.prefix, pending.frames)?;
+            }
+        }
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN + len + CRLF_LEN)
+    fn next_token(&mut self, buf: &mut impl DecodeBuf) -> Result<Option<Token>, RespError> {
+        if let Some((prefix, len)) = self.bulk {
+            return self.next_bulk(buf, prefix, len);
+        }
+
+        let Some(end) = self.find_line(buf)? else {
+            return Ok(None);
+        };
+
+        let line = buf.take(end + CRLF_LEN);
+        let prefix = line[0];
+        let data = &line[1..end];
+
+        let frame: RespFrame = match prefix {
+            b'+' => SimpleString::new(String::from_utf8_lossy(data)).into(),
+            b'-' => SimpleError::new(String::from_utf8_lossy(data)).into(),
+            b':' => std::str::from_utf8(data)?.parse::<i64>()?.into(),
+            b',' => std::str::from_utf8(data)?.parse::<f64>()?.into(),
+            b'(' => parse_big_number(data)?.into(),
+            b'_' if data.is_empty() => RespNull.into(),
+            b'#' if data == b"t" => true.into(),
+            b'#' if data == b"f" => false.into(),
+            b'$' | b'!' | b'=' => match parse_line_length(data)? {
+                None if prefix == b'$' => RespNullBulkString.into(),
+                None => return Err(RespError::InvalidFrameLength(-1)),
+                Some(len) if len > self.limits.max_bulk_len => {
+                    return Err(RespError::InvalidBulkLength)
+                }
+                Some(len) => {
+                    self.bulk = Some((prefix, len));
+                    return self.next_bulk(buf, prefix, len);
+                }
+            },
+            b'*' | b'~' | b'%' | b'>' | b'|' => match parse_line_length(data)? {
+                None if prefix == b'*' => RespNullArray.into(),
+                None => return Err(RespError::InvalidFrameLength(-1)),
+                Some(n) if n > self.limits.max_multibulk_len => {
+                    return Err(RespError::InvalidMultibulkLength)
+                }
+                Some(n) => return Ok(Some(Token::Aggregate(prefix, n))),
+            },
+            _ => {
+                return Err(RespError::InvalidFrameType(format!(
+                    "unknown frame type: {:?}",
+                    line
+                )))
+            }
+        };
+
+        Ok(Some(Token::Frame(frame)))
     }
-}
 
-// 把一个 respArray 解析成 Frame 的数组，因为一个 Array 中包含多个 frame
-// - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
-// - "*2\r\n$3\r\nget\r\n$5\r\nhello\r\n"
-// FIXME: need to handle incomplete
-impl RespDecode for RespArray {
-    const PREFIX: &'static str = "*";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+    fn next_bulk(
+        &mut self,
+        buf: &mut impl DecodeBuf,
+        prefix: u8,
+        len: usize,
+    ) -> Result<Option<Token>, RespError> {
+        // 不受限制的解码器里 len 可能接近 usize::MAX，不能直接加 CRLF_LEN
+        if buf.len() < CRLF_LEN || buf.len() - CRLF_LEN < len {
+            return Ok(None);
+        }
 
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
+        self.bulk = None;
+        let data = buf.take(len + CRLF_LEN);
+        if &data[len..] != CRLF {
+            return Err(RespError::InvalidFrame(format!(
+                "expect: CRLF after {} bytes of data",
+                len
+            )));
         }
 
-        buf.advance(end + CRLF_LEN);
+        let frame: RespFrame = match prefix {
+            b'$' => bulk_string_from(data, len).into(),
+            b'!' => BulkError::new(data[..len].to_vec()).into(),
+            _ => parse_verbatim(&data[..len])?.into(),
+        };
 
-        let mut frames = Vec::with_capacity(len);
-        for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
-        }
+        Ok(Some(Token::Frame(frame)))
+    }
 
-        Ok(RespArray::new(frames))
+    // inline 命令以 \n 结尾，\r 可有可无
+    fn next_inline(
+        &mut self,
+        buf: &mut impl DecodeBuf,
+    ) -> Result<Option<Vec<RespFrame>>, RespError> {
+        let start = self.scanned;
+        let Some(pos) = buf[start..].iter().position(|b| *b == b'\n') else {
+            if buf.len() > self.limits.max_inline_len {
+                return Err(RespError::TooBigInlineRequest);
+            }
+            self.scanned = buf.len();
+            return Ok(None);
+        };
+
+        self.scanned = 0;
+        let line = buf.take(start + pos + 1);
+        let line = &line[..start + pos];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        let args = split_inline_args(line)?
+            .into_iter()
+            .map(|arg| BulkString::new(arg).into())
+            .collect();
+        Ok(Some(args))
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+    // 从上次扫描结束的地方继续找 CRLF，返回 \r 的位置
+    fn find_line(&mut self, buf: &[u8]) -> Result<Option<usize>, RespError> {
+        let start = self.scanned.max(1);
+        match buf
+            .get(start..)
+            .and_then(|data| data.windows(CRLF_LEN).position(|w| w == CRLF))
+        {
+            Some(pos) => {
+                self.scanned = 0;
+                Ok(Some(start + pos))
+            }
+            // 长度行过长是 count string 太大，simple string 这类的单行数据按 inline 的限制报错
+            None if buf.len() > self.limits.max_inline_len => match buf[0] {
+                b'$' | b'!' | b'=' | b'*' | b'~' | b'%' | b'>' | b'|' => {
+                    Err(RespError::TooBigCountString)
+                }
+                _ => Err(RespError::TooBigInlineRequest),
+            },
+            None => {
+                // 最后一个字节可能是 \r，下次要重新看一下
+                self.scanned = buf.len().saturating_sub(1).max(start);
+                Ok(None)
+            }
+        }
     }
 }
 
-// - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
-impl RespDecode for f64 {
-    const PREFIX: &'static str = ",";
+impl RespDecode for RespFrame {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(s.parse()?)
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        let len = frame_len(buf, |_| Ok(()))?;
+        Ok(decode_complete(buf, len))
     }
 }
 
-// - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
-impl RespDecode for RespMap {
-    const PREFIX: &'static str = "%";
+impl RespDecode for RespArray {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
-
-        buf.advance(end + CRLF_LEN);
-
-        let mut frames = RespMap::new();
-        for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
-            let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
+        let len = frame_len(buf, |frame| match frame {
+            RespFrame::Array(_) => Ok(()),
+            frame => Err(RespError::InvalidFrameType(format!(
+                "expect: Array, got: {:?}",
+                frame
+            ))),
+        })?;
+        match decode_complete(buf, len) {
+            RespFrame::Array(array) => Ok(array),
+            _ => unreachable!("the frame was checked to be an array"),
         }
-
-        Ok(frames)
     }
+}
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
-    }
+// RespDecode 每次解码 buf 开头的一个完整 frame，数据不完整或者出错时 buf 保持原样
+// 先在 buf 冻结之后的 Bytes 上试探解码，和 buf 共享内存，不复制数据，
+// 结束之后 Bytes 又是唯一的引用，可以原样换回 BytesMut，返回 frame 的长度
+fn frame_len(
+    buf: &mut BytesMut,
+    check: impl FnOnce(&RespFrame) -> Result<(), RespError>,
+) -> Result<usize, RespError> {
+    let whole = std::mem::take(buf).freeze();
+    let mut view = whole.clone();
+    let ret = match RespFrameDecoder::new().decode_from(&mut view) {
+        Ok(Some(frame)) => check(&frame),
+        Ok(None) => Err(RespError::NotComplete),
+        Err(e) => Err(e),
+    };
+    let len = whole.len() - view.len();
+    drop(view);
+
+    *buf = whole
+        .try_into_mut()
+        .unwrap_or_else(|whole| BytesMut::from(&whole[..]));
+    ret.map(|_| len)
 }
 
-// - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
-impl RespDecode for RespSet {
-    const PREFIX: &'static str = "~";
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
+// 把 frame_len 确认过的 frame 从 buf 里切出来解码，bulk string 指向 buf 的内存，剩下的数据留在 buf 里
+fn decode_complete(buf: &mut BytesMut, len: usize) -> RespFrame {
+    let mut data = buf.split_to(len);
+    RespFrameDecoder::new()
+        .decode(&mut data)
+        .ok()
+        .flatten()
+        .expect("frame_len only accepts a complete frame")
+}
 
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+fn is_resp_prefix(b: u8) -> bool {
+    matches!(
+        b,
+        b'+' | b'-'
+            | b':'
+            | b'$'
+            | b'*'
+            | b'_'
+            | b'#'
+            | b','
+            | b'%'
+            | b'~'
+            | b'!'
+            | b'='
+            | b'('
+            | b'>'
+            | b'|'
+    )
+}
 
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
+// 和 redis 的 sdssplitargs 一样拆分 inline 命令的参数：
+// - 参数之间用空白分隔
+// - 双引号中支持 \n \r \t \b \a \\ \" 和 \xHH 转义
+// - 单引号中只支持 \' 转义
+// - 引号结束之后必须是空白或者行尾
+fn split_inline_args(line: &[u8]) -> Result<Vec<Vec<u8>>, RespError> {
+    let unbalanced = || RespError::InvalidFrame("unbalanced quotes in request".to_string());
+
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
         }
-
-        buf.advance(end + CRLF_LEN);
-
-        let mut frames = Vec::new();
-        for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
+        if i >= line.len() {
+            return Ok(args);
         }
 
-        Ok(RespSet::new(frames))
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+        let mut arg = Vec::new();
+        match line[i] {
+            b'"' => {
+                i += 1;
+                loop {
+                    match line.get(i..) {
+                        Some([b'\\', b'x', h, l, ..])
+                            if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() =>
+                        {
+                            let hex = [*h, *l];
+                            let hex = std::str::from_utf8(&hex).expect("hex digits are ascii");
+                            arg.push(u8::from_str_radix(hex, 16).expect("valid hex digits"));
+                            i += 4;
+                        }
+                        Some([b'\\', c, ..]) => {
+                            arg.push(match c {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                c => *c,
+                            });
+                            i += 2;
+                        }
+                        Some([b'"', rest @ ..]) => {
+                            if rest.first().is_some_and(|c| !c.is_ascii_whitespace()) {
+                                return Err(unbalanced());
+                            }
+                            i += 1;
+                            break;
+                        }
+                        Some([c, ..]) => {
+                            arg.push(*c);
+                            i += 1;
+                        }
+                        _ => return Err(unbalanced()),
+                    }
+                }
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match line.get(i..) {
+                        Some([b'\\', b'\'', ..]) => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        Some([b'\'', rest @ ..]) => {
+                            if rest.first().is_some_and(|c| !c.is_ascii_whitespace()) {
+                                return Err(unbalanced());
+                            }
+                            i += 1;
+                            break;
+                        }
+                        Some([c, ..]) => {
+                            arg.push(*c);
+                            i += 1;
+                        }
+                        _ => return Err(unbalanced()),
+                    }
+                }
+            }
+            _ => {
+                while i < line.len() && !line[i].is_ascii_whitespace() {
+                    arg.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+        args.push(arg);
     }
 }
 
-fn extract_fixed_data(
-    buf: &mut BytesMut,
-    expect: &str,
-    expect_type: &str,
-) -> Result<(), RespError> {
-    if buf.len() < expect.len() {
-        return Err(RespError::NotComplete);
+// 大的 bulk string 直接从读缓冲区切出来，不拷贝数据；
+// 小的拷贝一份，避免一个很小的 value 一直占着整块读缓冲区
+fn bulk_string_from(mut data: Bytes, len: usize) -> BulkString {
+    data.truncate(len);
+    if len < ZERO_COPY_THRESHOLD {
+        BulkString::from(&data[..])
+    } else {
+        data.into()
     }
-
-    if !buf.starts_with(expect.as_bytes()) {
-        return Err(RespError::InvalidFrameType(format!(
-            "expect: {}, got: {:?}",
-            expect_type, buf
-        )));
-    }
-
-    buf.advance(expect.len());
-    Ok(())
 }
 
-fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
-    if buf.len() < 3 {
-        return Err(RespError::NotComplete);
-    }
-
-    if !buf.starts_with(prefix.as_bytes()) {
-        return Err(RespError::InvalidFrameType(format!(
-            "expect: SimpleString({}), got: {:?}",
-            prefix, buf
-        )));
+// 长度为 -1 表示 null
+fn parse_line_length(data: &[u8]) -> Result<Option<usize>, RespError> {
+    let len = std::str::from_utf8(data)?.parse::<isize>()?;
+    match len {
+        -1 => Ok(None),
+        len if len < 0 => Err(RespError::InvalidFrameLength(len)),
+        len => Ok(Some(len as usize)),
     }
-
-    let end = find_crlf(buf, 1).ok_or(RespError::NotComplete)?;
-
-    Ok(end)
 }
 
-// find nth CRLF in the buffer
-fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
-    let mut count = 0;
-    for i in 1..buf.len() - 1 {
-        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-            count += 1;
-            if count == nth {
-                return Some(i);
+fn build_aggregate(prefix: u8, mut frames: Vec<RespFrame>) -> Result<RespFrame, RespError> {
+    let frame = match prefix {
+        b'*' => RespArray::new(frames).into(),
+        b'~' => RespSet::new(frames).into(),
+        b'>' => RespPush::new(frames).into(),
+        b'%' | b'|' => {
+            let reply = if prefix == b'|' { frames.pop() } else { None };
+
+            let mut map = RespMap::new();
+            let mut iter = frames.into_iter();
+            while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+                map.insert(frame_to_map_key(key)?, value);
             }
-        }
-    }
 
-    None
-}
-
-fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
-    let end = extract_simple_frame_data(buf, prefix)?;
-    let s = String::from_utf8_lossy(&buf[prefix.len()..end]);
-    Ok((end, s.parse()?))
-}
-
-fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
-    let mut total = end + CRLF_LEN;
-    let mut data = &buf[total..];
-    match prefix {
-        "*" | "~" => {
-            // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
-            for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
+            match reply {
+                Some(reply) => RespAttribute::new(map, reply).into(),
+                None => map.into(),
             }
-            Ok(total)
         }
-        "%" => {
-            // find nth CRLF in the buffer. For map, we need to find 2 CRLF for each key-value pair
-            for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
+        _ => unreachable!("unknown aggregate type"),
+    };
+
+    Ok(frame)
+}
 
-                data = &data[len..];
-                total += len;
+fn parse_big_number(data: &[u8]) -> Result<BigNumber, RespError> {
+    let s = std::str::from_utf8(data)?;
 
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
-            }
-            Ok(total)
-        }
-        _ => Ok(len + CRLF_LEN),
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RespError::InvalidFrame(format!(
+            "expect: BigNumber, got: {}",
+            s
+        )));
     }
-}
 
-impl RespDecode for Vec<u8> {
-    const PREFIX: &'static str = "/";
+    Ok(BigNumber::new(s))
+}
 
-    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        todo!()
+// 前 4 个字节是 "txt:" 这样的格式
+fn parse_verbatim(data: &[u8]) -> Result<VerbatimString, RespError> {
+    if data.len() < 4 || data[3] != b':' {
+        return Err(RespError::InvalidFrame(format!(
+            "expect: VerbatimString <format>:<data>, got: {:?}",
+            data
+        )));
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        todo!()
+    let format = [data[0], data[1], data[2]];
+    Ok(VerbatimString::new(format, data[4..].to_vec()))
+}
+
+// map 的 key 可以是 simple string，也可以是 bulk string
+fn frame_to_map_key(frame: RespFrame) -> Result<BulkString, RespError> {
+    match frame {
+        RespFrame::SimpleString(s) => Ok(s.0.into()),
+        RespFrame::BulkString(s) => Ok(s),
+        frame => Err(RespError::InvalidFrameType(format!(
+            "expect: map key (SimpleString or BulkString), got: {:?}",
+            frame
+        ))),
     }
 }
 
@@ -449,17 +605,17 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"+OK\r\n");
 
-        let frame = SimpleString::decode(&mut buf)?;
-        assert_eq!(frame, SimpleString::new("OK".to_string()));
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, SimpleString::new("OK".to_string()).into());
 
         buf.extend_from_slice(b"+hello\r");
 
-        let ret = SimpleString::decode(&mut buf);
+        let ret = RespFrame::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
         buf.put_u8(b'\n');
-        let frame = SimpleString::decode(&mut buf)?;
-        assert_eq!(frame, SimpleString::new("hello".to_string()));
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, SimpleString::new("hello".to_string()).into());
 
         Ok(())
     }
@@ -469,8 +625,8 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"-Error message\r\n");
 
-        let frame = SimpleError::decode(&mut buf)?;
-        assert_eq!(frame, SimpleError::new("Error message".to_string()));
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, SimpleError::new("Error message".to_string()).into());
 
         Ok(())
     }
@@ -480,13 +636,13 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b":+123\r\n");
 
-        let frame = i64::decode(&mut buf)?;
-        assert_eq!(frame, 123);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, 123.into());
 
         buf.extend_from_slice(b":-123\r\n");
 
-        let frame = i64::decode(&mut buf)?;
-        assert_eq!(frame, -123);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, (-123).into());
 
         Ok(())
     }
@@ -496,16 +652,16 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"$5\r\nhello\r\n");
 
-        let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString::new(b"hello"));
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, BulkString::new(b"hello").into());
 
         buf.extend_from_slice(b"$5\r\nhello");
-        let ret = BulkString::decode(&mut buf);
+        let ret = RespFrame::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
         buf.extend_from_slice(b"\r\n");
-        let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString::new(b"hello"));
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, BulkString::new(b"hello").into());
 
         Ok(())
     }
@@ -515,8 +671,8 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"$-1\r\n");
 
-        let frame = RespNullBulkString::decode(&mut buf)?;
-        assert_eq!(frame, RespNullBulkString);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, RespNullBulkString.into());
 
         Ok(())
     }
@@ -526,8 +682,8 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"*-1\r\n");
 
-        let frame = RespNullArray::decode(&mut buf)?;
-        assert_eq!(frame, RespNullArray);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, RespNullArray.into());
 
         Ok(())
     }
@@ -537,8 +693,8 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"_\r\n");
 
-        let frame = RespNull::decode(&mut buf)?;
-        assert_eq!(frame, RespNull);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, RespNull.into());
 
         Ok(())
     }
@@ -548,21 +704,21 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"#t\r\n");
 
-        let frame = bool::decode(&mut buf)?;
-        assert!(frame);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, true.into());
 
         buf.extend_from_slice(b"#f\r\n");
 
-        let frame = bool::decode(&mut buf)?;
-        assert!(!frame);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, false.into());
 
         buf.extend_from_slice(b"#f\r");
-        let ret = bool::decode(&mut buf);
+        let ret = RespFrame::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
         buf.put_u8(b'\n');
-        let frame = bool::decode(&mut buf)?;
-        assert!(!frame);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, false.into());
 
         Ok(())
     }
@@ -591,12 +747,12 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b",123.45\r\n");
 
-        let frame = f64::decode(&mut buf)?;
-        assert_eq!(frame, 123.45);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, 123.45.into());
 
         buf.extend_from_slice(b",+1.23456e-9\r\n");
-        let frame = f64::decode(&mut buf)?;
-        assert_eq!(frame, 1.23456e-9);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, 1.23456e-9.into());
 
         Ok(())
     }
@@ -606,14 +762,14 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n");
 
-        let frame = RespMap::decode(&mut buf)?;
+        let frame = RespFrame::decode(&mut buf)?;
         let mut map = RespMap::new();
         map.insert(
             "hello".to_string(),
             BulkString::new(b"world".to_vec()).into(),
         );
         map.insert("foo".to_string(), BulkString::new(b"bar".to_vec()).into());
-        assert_eq!(frame, map);
+        assert_eq!(frame, map.into());
 
         Ok(())
     }
@@ -623,30 +779,458 @@ mod tests {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"~2\r\n$3\r\nset\r\n$5\r\nhello\r\n");
 
-        let frame = RespSet::decode(&mut buf)?;
+        let frame = RespFrame::decode(&mut buf)?;
         assert_eq!(
             frame,
             RespSet::new(vec![
                 BulkString::new(b"set".to_vec()).into(),
                 BulkString::new(b"hello".to_vec()).into()
             ])
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_decode_bulk_string_keys() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"%1\r\n$5\r\nproto\r\n:3\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        let mut map = RespMap::new();
+        map.insert("proto".to_string(), 3.into());
+        assert_eq!(frame, map.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_error_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"!21\r\nSYNTAX invalid syntax\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, BulkError::new("SYNTAX invalid syntax").into());
+
+        buf.extend_from_slice(b"!21\r\nSYNTAX");
+        let ret = RespFrame::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verbatim_string_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"=15\r\ntxt:Some string\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, VerbatimString::new(*b"txt", "Some string").into());
+
+        buf.extend_from_slice(b"=3\r\ntxt\r\n");
+        let ret = RespFrame::decode(&mut buf);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_number_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"(-3492890328409238509324850943850943825024385\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            BigNumber::new("-3492890328409238509324850943850943825024385").into()
         );
 
+        buf.extend_from_slice(b"(12a\r\n");
+        let ret = RespFrame::decode(&mut buf);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+
         Ok(())
     }
 
     #[test]
-    fn test_calc_array_length() -> Result<()> {
-        let buf = b"*2\r\n$3\r\nset\r\n$5\r\nhello\r\n";
-        let (end, len) = parse_length(buf, "*")?;
-        let total_len = calc_total_length(buf, end, len, "*")?;
-        assert_eq!(total_len, buf.len());
-
-        let buf = b"*2\r\n$3\r\nset\r\n";
-        let (end, len) = parse_length(buf, "*")?;
-        let ret = calc_total_length(buf, end, len, "*");
+    fn test_push_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespPush::new([b"message".into(), b"ch".into()]).into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribute_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"|1\r\n+ttl\r\n:3600\r\n$5\r\nvalue");
+
+        let ret = RespFrame::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
+        buf.extend_from_slice(b"\r\n");
+        let frame = RespFrame::decode(&mut buf)?;
+
+        let mut attrs = RespMap::new();
+        attrs.insert("ttl".to_string(), 3600.into());
+        assert_eq!(
+            frame,
+            RespAttribute::new(attrs, BulkString::new("value")).into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_decoder() -> Result<()> {
+        let mut decoder = RespFrameDecoder::new();
+        let mut buf = BytesMut::new();
+
+        buf.extend_from_slice(b"*2\r\n$3\r\nset\r\n$5\r\nhel");
+        assert_eq!(decoder.decode(&mut buf)?, None);
+        // 已经解析过的 token 被取走了，剩下的是还没收齐的 bulk string
+        assert_eq!(&buf[..], b"hel");
+
+        buf.extend_from_slice(b"lo\r\n%1\r\n+foo\r");
+        let frame = decoder.decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            Some(RespArray::new([b"set".into(), b"hello".into()]).into())
+        );
+
+        assert_eq!(decoder.decode(&mut buf)?, None);
+        buf.extend_from_slice(b"\n~0\r\n*-1\r\n");
+
+        let mut map = RespMap::new();
+        map.insert("foo".to_string(), RespSet::new([]).into());
+        assert_eq!(decoder.decode(&mut buf)?, Some(map.into()));
+        assert_eq!(decoder.decode(&mut buf)?, Some(RespNullArray.into()));
+        assert_eq!(decoder.decode(&mut buf)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_decoder_byte_by_byte() -> Result<()> {
+        let data = b"|1\r\n+ttl\r\n:3600\r\n*3\r\n=7\r\ntxt:abc\r\n!3\r\nERR\r\n>1\r\n#t\r\n";
+
+        let mut decoder = RespFrameDecoder::new();
+        let mut buf = BytesMut::new();
+        let mut frames = Vec::new();
+        for b in data {
+            buf.put_u8(*b);
+            if let Some(frame) = decoder.decode(&mut buf)? {
+                frames.push(frame);
+            }
+        }
+
+        let mut attrs = RespMap::new();
+        attrs.insert("ttl".to_string(), 3600.into());
+        let reply = RespArray::new([
+            VerbatimString::new(*b"txt", "abc").into(),
+            BulkError::new("ERR").into(),
+            RespPush::new([true.into()]).into(),
+        ]);
+        assert_eq!(frames, vec![RespAttribute::new(attrs, reply).into()]);
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_decoder_invalid() {
+        let mut decoder = RespFrameDecoder::new();
+        let mut buf = BytesMut::from(&b"$3\r\nfooXX"[..]);
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+
+        let mut buf = BytesMut::from(&b"*1\r\n?\r\n"[..]);
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(RespError::InvalidFrameType(_))
+        ));
+    }
+
+    #[test]
+    fn test_bulk_string_zero_copy() -> Result<()> {
+        let mut decoder = RespFrameDecoder::new();
+        let mut buf = BytesMut::new();
+
+        let value = vec![b'x'; ZERO_COPY_THRESHOLD];
+        buf.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+        buf.extend_from_slice(&value);
+        buf.extend_from_slice(b"\r\n");
+        let start = buf.as_ptr() as usize;
+
+        let Some(RespFrame::BulkString(s)) = decoder.decode(&mut buf)? else {
+            panic!("expect a bulk string");
+        };
+        assert_eq!(&s[..], &value[..]);
+        // 数据就在原来的读缓冲区里
+        assert_eq!(s.as_ptr() as usize, start + 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resp_decode_does_not_copy_buffer() -> Result<()> {
+        // 流水线里的多个请求一个一个地解码，每次都不复制 buf
+        let value = vec![b'x'; ZERO_COPY_THRESHOLD];
+        let frame = format!("*1\r\n${}\r\n{}\r\n", value.len(), "x".repeat(value.len()));
+        let mut buf = BytesMut::new();
+        for _ in 0..3 {
+            buf.extend_from_slice(frame.as_bytes());
+        }
+        buf.extend_from_slice(b"*1\r\n$4\r\nPI");
+        let start = buf.as_ptr() as usize;
+
+        for i in 0..3 {
+            let array = RespArray::decode(&mut buf)?;
+            let RespFrame::BulkString(s) = &array[0] else {
+                panic!("expect a bulk string");
+            };
+            assert_eq!(&s[..], &value[..]);
+            // 数据就在调用方的缓冲区里
+            assert_eq!(s.as_ptr() as usize, start + i * frame.len() + 11);
+            assert_eq!(buf.as_ptr() as usize, start + (i + 1) * frame.len());
+        }
+
+        // 数据不完整、类型不对时 buf 保持原样，也没有被复制
+        let rest = start + 3 * frame.len();
+        assert_eq!(RespArray::decode(&mut buf), Err(RespError::NotComplete));
+        assert_eq!(buf.as_ptr() as usize, rest);
+        assert_eq!(&buf[..], b"*1\r\n$4\r\nPI");
+        buf.extend_from_slice(b"NG\r\n+OK\r\n");
+        assert_eq!(RespArray::decode(&mut buf)?.len(), 1);
+        assert!(RespArray::decode(&mut buf).is_err());
+        assert_eq!(RespFrame::decode(&mut buf)?, SimpleString::new("OK").into());
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_command_decode() -> Result<()> {
+        let mut decoder = RespFrameDecoder::new();
+        let mut buf = BytesMut::new();
+
+        buf.extend_from_slice(b"PING\r\n\r\nSET a");
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"PING".into()]).into())
+        );
+        assert_eq!(decoder.decode(&mut buf)?, None);
+
+        buf.extend_from_slice(b" \"b c\\x41\\n\" 'it\\'s'\n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(
+                RespArray::new([b"SET".into(), b"a".into(), b"b cA\n".into(), b"it's".into()])
+                    .into()
+            )
+        );
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"PING".into()]).into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_decoder_inline() -> Result<()> {
+        let mut decoder = RespFrameDecoder::for_requests(ProtocolLimits::default());
+        let mut buf = BytesMut::from(&b"-1 :2 (3\r\n+OK\r\n*1\r\n$4\r\nPING\r\n"[..]);
+
+        // 请求里只有 * 开头的是 RESP，其他类型的前缀都按 inline 命令解析
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"-1".into(), b":2".into(), b"(3".into()]).into())
+        );
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"+OK".into()]).into())
+        );
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"PING".into()]).into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_command_unbalanced_quotes() {
+        assert!(split_inline_args(b"set \"a b").is_err());
+        assert!(split_inline_args(b"set \"a\"b").is_err());
+        assert!(split_inline_args(b"set 'a").is_err());
+        assert_eq!(
+            split_inline_args(b"  set  '' \"\"  ").unwrap(),
+            vec![b"set".to_vec(), vec![], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_frame_decoder_limits() {
+        let limits = ProtocolLimits {
+            max_bulk_len: 8,
+            max_multibulk_len: 2,
+            max_depth: 2,
+            max_inline_len: 16,
+        };
+
+        let mut decoder = RespFrameDecoder::with_limits(limits);
+        let mut buf = BytesMut::from(&b"$99999999999\r\n"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::InvalidBulkLength
+        );
+
+        let mut buf = BytesMut::from(&b"*3\r\n"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::InvalidMultibulkLength
+        );
+
+        let mut buf = BytesMut::from(&b"*1\r\n*1\r\n*1\r\n"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::NestingTooDeep(2)
+        );
+
+        let mut buf = BytesMut::from(&b"set key a-very-long-value"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::TooBigInlineRequest
+        );
+
+        let mut buf = BytesMut::from(&b"*11111111111111111111"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::TooBigCountString
+        );
+
+        let mut buf = BytesMut::from(&b"+a-very-long-simple-string"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::TooBigInlineRequest
+        );
+
+        // 出错之后限制仍然有效，在限制之内的数据可以正常解析
+        let mut buf = BytesMut::from(&b"*1\r\n*1\r\n$8\r\n12345678\r\n"[..]);
+        assert!(decoder.decode(&mut buf).unwrap().is_some());
+    }
+
+    fn decode_err(data: &[u8]) -> RespError {
+        RespFrame::decode(&mut BytesMut::from(data)).unwrap_err()
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode_err(b"$5\r\nhel"), RespError::NotComplete);
+        assert!(matches!(
+            decode_err(b"$3\r\nfooXX"),
+            RespError::InvalidFrame(_)
+        ));
+        assert!(matches!(
+            decode_err(b"%1\r\n:1\r\n:2\r\n"),
+            RespError::InvalidFrameType(_)
+        ));
+        assert_eq!(decode_err(b"$-2\r\n"), RespError::InvalidFrameLength(-2));
+        assert_eq!(decode_err(b"~-1\r\n"), RespError::InvalidFrameLength(-1));
+        assert!(matches!(
+            decode_err(b":abc\r\n"),
+            RespError::ParseIntError(_)
+        ));
+        // 超出 isize 范围的长度是解析错误，不会溢出
+        assert!(matches!(
+            decode_err(b"$18446744073709551615\r\n"),
+            RespError::ParseIntError(_)
+        ));
+        assert!(matches!(decode_err(b":\xff\r\n"), RespError::Utf8Error(_)));
+        assert!(matches!(
+            decode_err(b",1.2.3\r\n"),
+            RespError::ParseFloatError(_)
+        ));
+
+        // RespDecode 和网络连接一样使用默认的限制
+        let limits = ProtocolLimits::default();
+        let data = format!("${}\r\n", limits.max_bulk_len + 1);
+        assert_eq!(decode_err(data.as_bytes()), RespError::InvalidBulkLength);
+        let data = format!("*{}\r\n", limits.max_multibulk_len + 1);
+        assert_eq!(
+            decode_err(data.as_bytes()),
+            RespError::InvalidMultibulkLength
+        );
+        let data = "*1\r\n".repeat(limits.max_depth + 1);
+        assert_eq!(
+            decode_err(data.as_bytes()),
+            RespError::NestingTooDeep(limits.max_depth)
+        );
+        let data = format!("*{}", "1".repeat(limits.max_inline_len));
+        assert_eq!(decode_err(data.as_bytes()), RespError::TooBigCountString);
+        let data = "x".repeat(limits.max_inline_len + 1);
+        assert_eq!(decode_err(data.as_bytes()), RespError::TooBigInlineRequest);
+    }
+
+    #[test]
+    fn test_frame_decoder_limit_boundaries() -> Result<()> {
+        let limits = ProtocolLimits {
+            max_bulk_len: 4,
+            max_multibulk_len: 2,
+            max_depth: 2,
+            max_inline_len: 8,
+        };
+
+        // 正好等于限制的可以解析，多一个就报错
+        let mut decoder = RespFrameDecoder::with_limits(limits);
+        let mut buf = BytesMut::from(&b"$4\r\nabcd\r\n*2\r\n*2\r\n:1\r\n:2\r\n:3\r\n"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(BulkString::new("abcd").into())
+        );
+        assert!(decoder.decode(&mut buf)?.is_some());
+
+        let mut buf = BytesMut::from(&b"$5\r\n"[..]);
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::InvalidBulkLength
+        );
+
+        // 一行数据不超过 max_inline_len 时还在等待 CRLF
+        let mut buf = BytesMut::from(&b"+1234567"[..]);
+        assert_eq!(decoder.decode(&mut buf)?, None);
+        buf.extend_from_slice(b"8");
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap_err(),
+            RespError::TooBigInlineRequest
+        );
+
+        let mut buf = BytesMut::from(&b"set a bc"[..]);
+        assert_eq!(decoder.decode(&mut buf)?, None);
+        buf.extend_from_slice(b"\n");
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"set".into(), b"a".into(), b"bc".into()]).into())
+        );
+
+        // 不限制长度时，声明一个超大的长度也不会溢出
+        let mut decoder = RespFrameDecoder::with_limits(ProtocolLimits {
+            max_bulk_len: usize::MAX,
+            ..Default::default()
+        });
+        let mut buf = BytesMut::from(format!("${}\r\nabc", isize::MAX).as_bytes());
+        assert_eq!(decoder.decode(&mut buf)?, None);
+
         Ok(())
     }
 }
@@ -14,161 +14,317 @@
     - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
     - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
     - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
+    - verbatim string: "=<length>\r\n<format>:<data>\r\n"
+    - big number: "(<big number>\r\n"
+    - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+    - attribute: "|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><frame>"
  */
 use crate::{
-    BulkString, RespArray, RespEncode, RespFrame, RespMap, RespNull, RespNullArray,
-    RespNullBulkString, RespSet, SimpleError, SimpleString,
+    BigNumber, BulkError, BulkString, RespArray, RespAttribute, RespEncode, RespFrame, RespMap,
+    RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet, SimpleError, SimpleString,
+    VerbatimString,
 };
+use bytes::{BufMut, BytesMut};
+use std::fmt::Write;
 
-/// encode 结构体数据 --> 字节数组
+// encode 结构体数据 --> 字节数组
+// 所有的 frame 都直接写入同一个输出缓冲区，嵌套的 frame 也不会产生临时的 Vec<u8>
 
-const BUF_CAP: usize = 1024 * 4;
-
-/// #[enum_dispatch(RespEncode)] 这个注解所生成的代码
+// #[enum_dispatch(RespEncode)] 这个注解所生成的代码
 /*impl RespEncode for RespFrame {
-    fn encode(self) -> Vec<u8> {
+    fn encode_to(&self, buf: &mut BytesMut) {
         match self {
-            RespFrame::SimpleString(inner) => inner.encode(),
-            RespFrame::Error(inner) => inner.encode(),
-            RespFrame::Integer(inner) => inner.encode(),
-            RespFrame::BulkString(inner) => inner.encode(),
-            RespFrame::NullBulkString(inner) => inner.encode(),
-            RespFrame::Array(inner) => inner.encode(),
-            RespFrame::NullArray(inner) => inner.encode(),
-            RespFrame::Null(inner) => inner.encode(),
-            RespFrame::Boolean(inner) => inner.encode(),
-            RespFrame::Double(inner) => inner.encode(),
-            RespFrame::Map(inner) => inner.encode(),
-            RespFrame::Set(inner) => inner.encode(),
+            RespFrame::SimpleString(inner) => inner.encode_to(buf),
+            RespFrame::Error(inner) => inner.encode_to(buf),
+            RespFrame::Integer(inner) => inner.encode_to(buf),
+            RespFrame::BulkString(inner) => inner.encode_to(buf),
+            ...
         }
     }
 }*/
 
 // simple string: "+OK\r\n"
 impl RespEncode for SimpleString {
-    fn encode(self) -> Vec<u8> {
-        format!("+{}\r\n", self.0).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_line(buf, b'+', self.as_bytes());
     }
 }
 
 // error: "-Error message\r\n"
 impl RespEncode for SimpleError {
-    fn encode(self) -> Vec<u8> {
-        format!("-{}\r\n", self.0).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_line(buf, b'-', self.as_bytes());
     }
 }
 
 // integer: ":[<+|->]<value>\r\n"
 impl RespEncode for i64 {
-    fn encode(self) -> Vec<u8> {
+    fn encode_to(&self, buf: &mut BytesMut) {
         let sign = if self.is_negative() { "" } else { "+" };
-        format!(":{}{}\r\n", sign, self).into_bytes()
+        put_fmt(buf, format_args!(":{}{}\r\n", sign, self));
     }
 }
 
 // bulk string: "$<length>\r\n<data>\r\n"
 impl RespEncode for BulkString {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(self.len() + 16);
-
-        buf.extend_from_slice(&format!("${}\r\n", self.len()).into_bytes());
-        buf.extend_from_slice(&self);
-        buf.extend_from_slice(b"\r\n");
-        buf
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_blob(buf, b'$', &[self]);
     }
 }
 
 // null bulk string: "$-1\r\n"
 impl RespEncode for RespNullBulkString {
-    fn encode(self) -> Vec<u8> {
-        b"$-1\r\n".to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(b"$-1\r\n");
     }
 }
 
 // array: "*<number-of-elements>\r\n<element-1>...<element-n>"
 //         - "*2\r\n$3\r\nget\r\n$5\r\nhello\r\n"
 impl RespEncode for RespArray {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-
-        let prefix = format!("*{}\r\n", self.0.len()).into_bytes();
-        buf.extend_from_slice(&prefix);
-
-        for frame in self.0 {
-            // 这里的 encode 方法由 enum dispatch 路由
-            buf.extend_from_slice(&frame.encode());
-        }
-
-        buf
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_aggregate(buf, b'*', &self.0);
     }
 }
 
 // null array: "*-1\r\n"
 impl RespEncode for RespNullArray {
-    fn encode(self) -> Vec<u8> {
-        b"*-1\r\n".to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(b"*-1\r\n");
     }
 }
 
 // null: "_\r\n"
 impl RespEncode for RespNull {
-    fn encode(self) -> Vec<u8> {
-        b"_\r\n".to_vec()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(b"_\r\n");
     }
 }
 
 //boolean: "#<t|f>\r\n"
 impl RespEncode for bool {
-    fn encode(self) -> Vec<u8> {
-        let result = if self { "t" } else { "f" };
-        format!("#{}\r\n", result).into_bytes()
+    fn encode_to(&self, buf: &mut BytesMut) {
+        let result: &[u8] = if *self { b"#t\r\n" } else { b"#f\r\n" };
+        buf.extend_from_slice(result);
     }
 }
 
 // double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
 impl RespEncode for f64 {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(32);
-
-        let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
-            format!(",{:+e}\r\n", self)
+    fn encode_to(&self, buf: &mut BytesMut) {
+        if self.abs() > 1e+8 || self.abs() < 1e-8 {
+            put_fmt(buf, format_args!(",{:+e}\r\n", self));
         } else {
-            let sign = if self < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self)
-        };
-
-        buf.extend_from_slice(&ret.into_bytes());
-        buf
+            let sign = if *self < 0.0 { "" } else { "+" };
+            put_fmt(buf, format_args!(",{}{}\r\n", sign, self));
+        }
     }
 }
 
 //  map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
 impl RespEncode for RespMap {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-
-        buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
-        for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
-            buf.extend_from_slice(&value.encode());
-        }
-
-        buf
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_map(buf, b'%', self);
     }
 }
 
 // set: "~<number-of-elements>\r\n<element-1>...<element-n>"
 impl RespEncode for RespSet {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_aggregate(buf, b'~', &self.0);
+    }
+}
+
+// bulk error: "!<length>\r\n<error>\r\n"
+impl RespEncode for BulkError {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_blob(buf, b'!', &[self]);
+    }
+}
+
+// verbatim string: "=<length>\r\n<format>:<data>\r\n"
+impl RespEncode for VerbatimString {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_blob(buf, b'=', &[&self.format, b":", &self.data]);
+    }
+}
 
-        buf.extend_from_slice(&format!("~{}\r\n", self.len()).into_bytes());
+// big number: "(<big number>\r\n"
+impl RespEncode for BigNumber {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_line(buf, b'(', self.as_bytes());
+    }
+}
 
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
+// push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for RespPush {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_aggregate(buf, b'>', &self.0);
+    }
+}
+
+// attribute: "|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><frame>"
+impl RespEncode for RespAttribute {
+    fn encode_to(&self, buf: &mut BytesMut) {
+        put_map(buf, b'|', &self.attributes);
+        self.frame.encode_to(buf);
+    }
+}
+
+fn put_fmt(buf: &mut BytesMut, args: std::fmt::Arguments) {
+    // 写入 BytesMut 不会失败
+    buf.write_fmt(args).expect("write to BytesMut never fails");
+}
+
+fn put_line(buf: &mut BytesMut, prefix: u8, data: &[u8]) {
+    buf.reserve(data.len() + 3);
+    buf.put_u8(prefix);
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"\r\n");
+}
+
+// 带长度的数据，parts 拼接在一起作为内容
+fn put_blob(buf: &mut BytesMut, prefix: u8, parts: &[&[u8]]) {
+    let len = parts.iter().map(|part| part.len()).sum::<usize>();
+    buf.reserve(len + 16);
+    put_fmt(buf, format_args!("{}{}\r\n", prefix as char, len));
+    for part in parts {
+        buf.extend_from_slice(part);
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+fn put_aggregate(buf: &mut BytesMut, prefix: u8, frames: &[RespFrame]) {
+    put_fmt(buf, format_args!("{}{}\r\n", prefix as char, frames.len()));
+    for frame in frames {
+        // 这里的 encode_to 方法由 enum dispatch 路由
+        frame.encode_to(buf);
+    }
+}
+
+fn put_map(buf: &mut BytesMut, prefix: u8, map: &RespMap) {
+    put_fmt(buf, format_args!("{}{}\r\n", prefix as char, map.len()));
+    for (key, value) in map.iter() {
+        // 普通的文本 key 用 simple string，二进制的 key 只能用 bulk string
+        match std::str::from_utf8(key) {
+            Ok(s) if !s.contains(['\r', '\n']) => put_line(buf, b'+', key),
+            _ => key.encode_to(buf),
         }
+        value.encode_to(buf);
+    }
+}
 
-        buf
+impl RespFrame {
+    /// 把 RESP3 才有的类型降级成 RESP2 能表示的类型，用于没有发送过 HELLO 3 的连接
+    /// - map 展开成 [key1, value1, key2, value2...] 的数组
+    /// - set 变成数组
+    /// - null 变成 null bulk string
+    /// - boolean 变成整数 1/0
+    /// - double、big number、verbatim string 变成 bulk string
+    /// - bulk error 变成 simple error，push 变成数组，attribute 直接丢掉
+    pub fn into_resp2(self) -> RespFrame {
+        match self {
+            RespFrame::Array(array) => RespArray::new(
+                array
+                    .0
+                    .into_iter()
+                    .map(|f| f.into_resp2())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Map(map) => {
+                let mut frames = Vec::with_capacity(map.len() * 2);
+                for (key, value) in map.0 {
+                    frames.push(key.into());
+                    frames.push(value.into_resp2());
+                }
+                RespArray::new(frames).into()
+            }
+            RespFrame::Set(set) => RespArray::new(
+                set.0
+                    .into_iter()
+                    .map(|f| f.into_resp2())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Null(_) => RespNullBulkString.into(),
+            RespFrame::Boolean(b) => (b as i64).into(),
+            RespFrame::Double(d) => BulkString::from(format_double(d)).into(),
+            RespFrame::BigNumber(n) => BulkString::from(n.0).into(),
+            RespFrame::VerbatimString(s) => BulkString::new(s.data).into(),
+            RespFrame::BulkError(e) => {
+                let msg = String::from_utf8_lossy(&e).replace(['\r', '\n'], " ");
+                SimpleError::new(msg).into()
+            }
+            RespFrame::Push(push) => RespArray::new(
+                push.0
+                    .into_iter()
+                    .map(|f| f.into_resp2())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Attribute(attr) => attr.frame.into_resp2(),
+            frame => frame,
+        }
+    }
+}
+
+impl RespFrame {
+    /// RESP3 里没有 null array 和 null bulk string，命令回复里的这两种 null 在 RESP3 连接上变成 null
+    /// 这样 LPOP key count 这类命令在两种协议下都和 redis 的回复一样
+    /// 嵌套在 map、set、push 和 attribute 里面的 null 也一起转换
+    pub fn into_resp3(self) -> RespFrame {
+        match self {
+            RespFrame::NullArray(_) | RespFrame::NullBulkString(_) => RespNull.into(),
+            RespFrame::Array(array) => RespArray::new(
+                array
+                    .0
+                    .into_iter()
+                    .map(|f| f.into_resp3())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Map(map) => map.into_resp3().into(),
+            RespFrame::Set(set) => RespSet::new(
+                set.0
+                    .into_iter()
+                    .map(|f| f.into_resp3())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Push(push) => RespPush::new(
+                push.0
+                    .into_iter()
+                    .map(|f| f.into_resp3())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Attribute(attr) => {
+                RespAttribute::new(attr.attributes.into_resp3(), attr.frame.into_resp3()).into()
+            }
+            frame => frame,
+        }
+    }
+}
+
+impl RespMap {
+    fn into_resp3(self) -> RespMap {
+        let mut map = RespMap::new();
+        for (key, value) in self.0 {
+            map.insert(key, value.into_resp3());
+        }
+        map
+    }
+}
+
+/// 和 redis 一样格式化浮点数：整数不带小数点，inf/-inf/nan 用小写
+pub(crate) fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{}", d)
     }
 }
 
@@ -293,6 +449,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_map_encode_binary_key() {
+        let mut map = RespMap::new();
+        map.insert(&b"\xff\r\n"[..], 1.into());
+
+        let frame: RespFrame = map.into();
+        assert_eq!(&frame.encode(), b"%1\r\n$3\r\n\xff\r\n\r\n:+1\r\n");
+    }
+
     #[test]
     fn test_set_encode() {
         let frame: RespFrame = RespSet::new([
@@ -305,4 +470,102 @@ mod tests {
             b"~2\r\n*2\r\n:+1234\r\n#t\r\n$5\r\nworld\r\n"
         );
     }
+
+    #[test]
+    fn test_into_resp2() {
+        let mut map = RespMap::new();
+        map.insert("hello".to_string(), RespFrame::Null(RespNull));
+        map.insert("foo".to_string(), 1.5.into());
+
+        let frame: RespFrame =
+            RespArray::new([map.into(), RespSet::new([true.into()]).into(), 3.0.into()]).into();
+
+        assert_eq!(
+            frame.into_resp2().encode(),
+            b"*3\r\n*4\r\n$3\r\nfoo\r\n$3\r\n1.5\r\n$5\r\nhello\r\n$-1\r\n*1\r\n:+1\r\n$1\r\n3\r\n"
+        );
+    }
+
+    #[test]
+    fn test_into_resp3() {
+        let frame: RespFrame = RespArray::new([RespNullArray.into(), 1.into()]).into();
+        assert_eq!(frame.clone().into_resp2().encode(), b"*2\r\n*-1\r\n:+1\r\n");
+        assert_eq!(frame.into_resp3().encode(), b"*2\r\n_\r\n:+1\r\n");
+
+        // 嵌套在 map、set、push 和 attribute 里的 null 也要转换
+        let mut map = RespMap::new();
+        map.insert("k", RespNullArray.into());
+        let frame: RespFrame = RespPush::new([
+            map.clone().into(),
+            RespSet::new([RespNullBulkString.into()]).into(),
+            RespAttribute::new(map, RespNullArray).into(),
+        ])
+        .into();
+        assert_eq!(
+            frame.into_resp3().encode(),
+            b">3\r\n%1\r\n+k\r\n_\r\n~1\r\n_\r\n|1\r\n+k\r\n_\r\n_\r\n"
+        );
+    }
+
+    #[test]
+    fn test_bulk_error_encode() {
+        let frame: RespFrame = BulkError::new("SYNTAX invalid syntax").into();
+        assert_eq!(frame.encode(), b"!21\r\nSYNTAX invalid syntax\r\n");
+    }
+
+    #[test]
+    fn test_verbatim_string_encode() {
+        let frame: RespFrame = VerbatimString::new(*b"txt", "Some string").into();
+        assert_eq!(frame.encode(), b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_big_number_encode() {
+        let frame: RespFrame = BigNumber::new("3492890328409238509324850943850943825024385").into();
+        assert_eq!(
+            frame.encode(),
+            b"(3492890328409238509324850943850943825024385\r\n"
+        );
+    }
+
+    #[test]
+    fn test_push_encode() {
+        let frame: RespFrame = RespPush::new([
+            BulkString::new("message").into(),
+            BulkString::new("ch").into(),
+        ])
+        .into();
+        assert_eq!(frame.encode(), b">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n");
+    }
+
+    #[test]
+    fn test_attribute_encode() {
+        let mut attrs = RespMap::new();
+        attrs.insert("ttl".to_string(), 3600.into());
+
+        let frame: RespFrame = RespAttribute::new(attrs, BulkString::new("value")).into();
+        assert_eq!(frame.encode(), b"|1\r\n+ttl\r\n:+3600\r\n$5\r\nvalue\r\n");
+
+        let mut attrs = RespMap::new();
+        attrs.insert("ttl".to_string(), 3600.into());
+        let frame: RespFrame = RespAttribute::new(attrs, BulkError::new("ERR x")).into();
+        assert_eq!(frame.into_resp2().encode(), b"-ERR x\r\n");
+    }
+
+    #[test]
+    fn test_encode_to_shared_buffer() {
+        let mut buf = BytesMut::new();
+
+        let frame: RespFrame = RespArray::new([
+            BulkString::new("hello").into(),
+            RespArray::new([1.into(), RespNull.into()]).into(),
+        ])
+        .into();
+        frame.encode_to(&mut buf);
+        SimpleString::new("OK").encode_to(&mut buf);
+
+        // frame 没有被 consume，还可以继续使用
+        assert!(matches!(frame, RespFrame::Array(_)));
+        assert_eq!(&buf[..], b"*2\r\n$5\r\nhello\r\n*2\r\n:+1\r\n_\r\n+OK\r\n");
+    }
 }
@@ -1,8 +1,11 @@
 mod decode;
 mod encode;
 
-use bytes::BytesMut;
+pub use decode::{ProtocolLimits, RespFrameDecoder};
+
+use bytes::{Bytes, BytesMut};
 use enum_dispatch::enum_dispatch;
+use std::borrow::Borrow;
 use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
 use thiserror::Error;
@@ -10,20 +13,32 @@ use thiserror::Error;
 // trait 上也要注明 enum dispatch
 #[enum_dispatch]
 pub trait RespEncode {
-    // 为什么要把 self 直接 consume
-    fn encode(self) -> Vec<u8>;
+    /// 直接写入输出缓冲区，嵌套的 frame 写在同一个缓冲区里，不需要 consume frame
+    fn encode_to(&self, buf: &mut BytesMut);
+
+    fn encode(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = BytesMut::new();
+        self.encode_to(&mut buf);
+        buf.to_vec()
+    }
 }
 
-/// decode 将字节数据转为 需要的数据结构
-/// 表示一个类型的大小在编译期是已知的，也就是说，编译器可以在编译时确定该类型所占的内存空间
-/// 实现 RespDecode 的 类型必须有固定的大小，意味着实现这个 trait 的类型不能是动态大小的类型，例如 Vec<T> String
+/// decode 将一段完整的字节数据转为 需要的数据结构
+/// 内部使用 RespFrameDecoder，和网络连接一样受默认的 ProtocolLimits 限制
+/// 数据不完整时返回 NotComplete，不会修改 buf
 pub trait RespDecode: Sized {
-    const PREFIX: &'static str;
-
-    // 拿到一个 bytesMut，然后对里面的数据进行 decode
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError>;
+}
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError>;
+/// 连接使用的协议版本，默认 RESP2，客户端发送 HELLO 3 之后切换到 RESP3
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RespProtocol {
+    #[default]
+    Resp2,
+    Resp3,
 }
 
 /// anyhow 帮你自动 convert error
@@ -50,6 +65,21 @@ pub enum RespError {
 
     #[error("Parse float error: {0}")]
     ParseFloatError(#[from] std::num::ParseFloatError),
+
+    #[error("invalid bulk length")]
+    InvalidBulkLength,
+
+    #[error("invalid multibulk length")]
+    InvalidMultibulkLength,
+
+    #[error("too big inline request")]
+    TooBigInlineRequest,
+
+    #[error("too big mbulk count string")]
+    TooBigCountString,
+
+    #[error("nesting depth exceeds {0}")]
+    NestingTooDeep(usize),
 }
 
 // 在 enum 上注明你要使用哪个 trait ，然后 enum 的成员都要实现 这个 trait
@@ -81,6 +111,16 @@ pub enum RespFrame {
     Map(RespMap),
 
     Set(RespSet),
+
+    BulkError(BulkError),
+
+    VerbatimString(VerbatimString),
+
+    BigNumber(BigNumber),
+
+    Push(RespPush),
+
+    Attribute(RespAttribute),
 }
 
 // impl RespFrame {
@@ -95,8 +135,9 @@ pub struct SimpleString(String);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct SimpleError(String);
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+/// 使用 Bytes 保存数据，解码时直接从读缓冲区中切出来，clone 只是增加引用计数
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BulkString(pub(crate) Bytes);
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct RespNull;
@@ -111,11 +152,36 @@ pub struct RespNullArray;
 pub struct RespNullBulkString;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespMap(BTreeMap<String, RespFrame>);
+pub struct RespMap(BTreeMap<BulkString, RespFrame>);
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespSet(Vec<RespFrame>);
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct BulkError(pub(crate) Vec<u8>);
+
+/// 带格式的字符串，format 是三个字节，比如 txt、mkd
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; 3],
+    pub(crate) data: Vec<u8>,
+}
+
+/// 超出 i64 范围的整数，保存十进制的字符串
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct BigNumber(String);
+
+/// 服务端主动推送的数据，比如 pub/sub 的消息
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
+
+/// 附加在一个回复之前的属性，attributes 之后紧跟着真正的回复
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespAttribute {
+    pub(crate) attributes: RespMap,
+    pub(crate) frame: Box<RespFrame>,
+}
+
 // 我们要直接访问里面包裹的数据，所以需要 deref
 impl Deref for SimpleString {
     type Target = String;
@@ -134,7 +200,7 @@ impl Deref for SimpleError {
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = Bytes;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -150,7 +216,7 @@ impl Deref for RespArray {
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = BTreeMap<BulkString, RespFrame>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -172,6 +238,30 @@ impl Deref for RespSet {
     }
 }
 
+impl Deref for BulkError {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for BigNumber {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl SimpleString {
     pub fn new(s: impl Into<String>) -> SimpleString {
         SimpleString(s.into())
@@ -186,7 +276,11 @@ impl SimpleError {
 
 impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> BulkString {
-        BulkString(s.into())
+        BulkString(Bytes::from(s.into()))
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
     }
 }
 
@@ -196,10 +290,30 @@ impl RespArray {
     }
 }
 
+// map 的 key 是二进制安全的，可以直接用 &[u8] 查找
+impl Borrow<[u8]> for BulkString {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl RespMap {
     pub fn new() -> RespMap {
         RespMap(BTreeMap::new())
     }
+
+    // 这几个方法覆盖了 BTreeMap 上的同名方法，key 可以直接传 &str、String 或者 Bytes
+    pub fn insert(&mut self, key: impl Into<BulkString>, value: RespFrame) -> Option<RespFrame> {
+        self.0.insert(key.into(), value)
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&RespFrame> {
+        self.0.get(key.as_ref())
+    }
+
+    pub fn contains_key(&self, key: impl AsRef<[u8]>) -> bool {
+        self.0.contains_key(key.as_ref())
+    }
 }
 
 impl Default for RespMap {
@@ -214,6 +328,58 @@ impl RespSet {
     }
 }
 
+impl BulkError {
+    pub fn new(s: impl Into<Vec<u8>>) -> BulkError {
+        BulkError(s.into())
+    }
+}
+
+impl VerbatimString {
+    pub fn new(format: [u8; 3], data: impl Into<Vec<u8>>) -> VerbatimString {
+        VerbatimString {
+            format,
+            data: data.into(),
+        }
+    }
+
+    pub fn format(&self) -> &[u8; 3] {
+        &self.format
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl BigNumber {
+    pub fn new(s: impl Into<String>) -> BigNumber {
+        BigNumber(s.into())
+    }
+}
+
+impl RespPush {
+    pub fn new(s: impl Into<Vec<RespFrame>>) -> RespPush {
+        RespPush(s.into())
+    }
+}
+
+impl RespAttribute {
+    pub fn new(attributes: RespMap, frame: impl Into<RespFrame>) -> RespAttribute {
+        RespAttribute {
+            attributes,
+            frame: Box::new(frame.into()),
+        }
+    }
+
+    pub fn attributes(&self) -> &RespMap {
+        &self.attributes
+    }
+
+    pub fn frame(&self) -> &RespFrame {
+        &self.frame
+    }
+}
+
 impl From<&str> for SimpleString {
     fn from(s: &str) -> Self {
         SimpleString(s.to_string())
@@ -234,37 +400,43 @@ impl From<&str> for SimpleError {
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<String> for BulkString {
     fn from(value: String) -> Self {
-        BulkString(value.as_bytes().to_vec())
+        BulkString(Bytes::from(value))
+    }
+}
+
+impl From<Bytes> for BulkString {
+    fn from(value: Bytes) -> Self {
+        BulkString(value)
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::from(s).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::from(s).into()
     }
 }
 
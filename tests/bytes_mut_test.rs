@@ -1,3 +1,6 @@
+// bytes 用法的练习代码，保持原来的写法，不需要满足 lint 要求
+#![allow(clippy::needless_borrow)]
+
 use bytes::{Buf, BufMut, BytesMut};
 
 #[test]
@@ -61,7 +64,7 @@ fn test_bytes_mut_split() {
 
 #[test]
 fn test_bytes_mut_advance() {
-    let mut buf = BytesMut::from(b"hello world");
+    let mut buf = BytesMut::from(&b"hello world"[..]);
 
     buf.advance(3);
 }
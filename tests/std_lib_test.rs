@@ -1,3 +1,13 @@
+// 标准库用法的练习代码，保持原来的写法，不需要满足 lint 要求
+#![allow(
+    dead_code,
+    unused_mut,
+    unused_variables,
+    unreachable_patterns,
+    clippy::to_string_in_format_args,
+    clippy::useless_conversion
+)]
+
 use dashmap::DashMap;
 use enum_dispatch::enum_dispatch;
 use std::borrow::Cow;